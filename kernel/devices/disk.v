@@ -9,6 +9,14 @@ const reg_ctrl = disk0_base + 0x00
 const reg_sector = disk0_base + 0x04
 const reg_data = disk0_base + 0x08
 const reg_status = disk0_base + 0x0c
+const reg_error = disk0_base + 0x10
+const reg_sector_count = disk0_base + 0x14
+
+const status_error = u32(1 << 1)
+const status_out_of_range = u32(1 << 2)
+const status_short_read = u32(1 << 3)
+
+const err_read_only = u32(6)
 
 fn mmio_read_u32(addr u32) u32 {
 	unsafe {
@@ -25,8 +33,26 @@ fn mmio_write_u32(addr u32, val u32) {
 
 pub struct Disk {}
 
+fn check_status(sector u32) ! {
+	status := mmio_read_u32(reg_status)
+	if status & status_error == 0 {
+		return
+	}
+	if status & status_out_of_range != 0 {
+		return error('disk: sector ${sector} out of range')
+	}
+	if status & status_short_read != 0 {
+		return error('disk: short read at sector ${sector}')
+	}
+	err := mmio_read_u32(reg_error)
+	if err == err_read_only {
+		return error('disk: sector ${sector} is read-only')
+	}
+	return error('disk: i/o error ${err} at sector ${sector}')
+}
+
 pub fn (disk Disk) sector_count() u32 {
-	return u32(disk0_size / disk0_sector_size)
+	return mmio_read_u32(reg_sector_count)
 }
 
 pub fn (disk Disk) sector_size() u32 {
@@ -38,6 +64,7 @@ pub fn (disk Disk) read(sector u32, mut buf [512]u8) ! {
 
     mmio_write_u32(reg_sector, sector)
     mmio_write_u32(reg_ctrl, 1)
+    check_status(sector)!
 
     for i in 0 .. disk0_sector_size {
         buf[i] = u8(mmio_read_u32(reg_data))
@@ -54,4 +81,5 @@ pub fn (disk Disk) write(sector u32, mut buf [512]u8) ! {
     }
 
     mmio_write_u32(reg_ctrl, 2)
+    check_status(sector)!
 }
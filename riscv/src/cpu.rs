@@ -1,48 +1,228 @@
-use crate::csrs::CsrFile;
-use crate::devices::Bus;
-use crate::instructions::{privileged, rv32i, zicsr};
-use crate::isa::opcodes::{AUIPC, BRANCH, JAL, JALR, LOAD, LUI, OP_IMM, OP_REG, STORE, SYSTEM};
-use crate::isa::{INSTRUCTION_SIZE, Instr, PrivilegeMode};
+use crate::csrs::{CsrFile, SATP_MODE_SV32, SATP_MODE_SV39, SATP_MODE_SV48};
+use crate::devices::{Bus, PowerControl};
+use crate::instructions::{privileged, rv32i, rv64i, rvv, zicond, zicsr, zkn};
+use crate::isa::opcodes::{
+    AUIPC, BRANCH, JAL, JALR, LOAD, LOAD_FP, LUI, OP_32, OP_IMM, OP_IMM_32, OP_REG, OP_V, STORE,
+    STORE_FP, SYSTEM,
+};
+use crate::isa::{INSTRUCTION_SIZE, Instr, PrivilegeMode, Xlen};
 use crate::regs::RegFile;
 use crate::trap::{Exception, Trap};
+use crate::vregs::VRegFile;
 
-const DEFAULT_RESET_VECTOR: u32 = 0x8000_0000;
+const DEFAULT_RESET_VECTOR: u64 = 0x8000_0000;
+
+const PAGE_SHIFT: u64 = 12;
+
+const PTE_V: u64 = 1 << 0;
+const PTE_R: u64 = 1 << 1;
+const PTE_W: u64 = 1 << 2;
+const PTE_X: u64 = 1 << 3;
+const PTE_U: u64 = 1 << 4;
+const PTE_A: u64 = 1 << 6;
+const PTE_D: u64 = 1 << 7;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CpuConfig {
+    pub xlen: Xlen,
+    /// VLEN in bits; `None` leaves the v extension unimplemented.
+    pub vlen: Option<u32>,
+    pub extensions: Extensions,
+}
+
+impl CpuConfig {
+    /// ISA string for the device tree, e.g. `rv64i_zicsr_zicond_zve64x`.
+    /// Only lists what the hart executes: M is in misa but not decoded, and
+    /// vectors are the integer embedded profile, as F and D are missing.
+    pub fn isa_string(&self) -> String {
+        let mut isa = format!("rv{}i", self.xlen.bits());
+        let ext = &self.extensions;
+        let optional = [
+            ("zicsr", true),
+            ("zicond", ext.zicond),
+            ("zbkb", ext.zbkb),
+            ("zknd", ext.zknd),
+            ("zkne", ext.zkne),
+            ("zknh", ext.zknh),
+            ("zve64x", self.vlen.is_some()),
+        ];
+        for (name, enabled) in optional {
+            if enabled {
+                isa.push('_');
+                isa.push_str(name);
+            }
+        }
+        isa
+    }
+
+    /// The largest translation scheme the hart supports, as a device tree
+    /// `mmu-type`.
+    pub fn mmu_type(&self) -> &'static str {
+        match self.xlen {
+            Xlen::Rv32 => "riscv,sv32",
+            Xlen::Rv64 => "riscv,sv48",
+        }
+    }
+}
+
+/// Optional extensions without architectural state of their own.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Extensions {
+    pub zbkb: bool,
+    pub zknh: bool,
+    pub zkne: bool,
+    pub zknd: bool,
+    pub zicond: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessType {
+    Fetch,
+    Load,
+    Store,
+}
+
+impl AccessType {
+    /// Leaf bit that grants this access.
+    fn permission(self) -> u64 {
+        match self {
+            AccessType::Fetch => PTE_X,
+            AccessType::Load => PTE_R,
+            AccessType::Store => PTE_W,
+        }
+    }
+
+    fn access_fault(self, addr: u64) -> Trap {
+        match self {
+            AccessType::Fetch => Trap::Exception(Exception::InstructionAccessFault(addr)),
+            AccessType::Load => Trap::Exception(Exception::LoadAccessFault(addr)),
+            AccessType::Store => Trap::Exception(Exception::StoreAccessFault(addr)),
+        }
+    }
+
+    fn page_fault(self, addr: u64) -> Trap {
+        match self {
+            AccessType::Fetch => Trap::Exception(Exception::InstructionPageFault(addr)),
+            AccessType::Load => Trap::Exception(Exception::LoadPageFault(addr)),
+            AccessType::Store => Trap::Exception(Exception::StorePageFault(addr)),
+        }
+    }
+}
+
+/// Shape of a page table walk: `levels` tables indexed by `vpn_bits` each,
+/// with `pte_size`-byte entries.
+struct PagingScheme {
+    levels: u32,
+    vpn_bits: u32,
+    pte_size: u8,
+    ppn_mask: u64,
+}
+
+const SV32: PagingScheme = PagingScheme {
+    levels: 2,
+    vpn_bits: 10,
+    pte_size: 4,
+    ppn_mask: 0x003f_ffff,
+};
+
+const SV39: PagingScheme = PagingScheme {
+    levels: 3,
+    vpn_bits: 9,
+    pte_size: 8,
+    ppn_mask: 0x0fff_ffff_ffff,
+};
+
+const SV48: PagingScheme = PagingScheme {
+    levels: 4,
+    vpn_bits: 9,
+    pte_size: 8,
+    ppn_mask: 0x0fff_ffff_ffff,
+};
 
 pub struct Cpu {
-    pub pc: u32,
-    pub next_pc: u32,
+    pub pc: u64,
+    pub next_pc: u64,
+    pub xlen: Xlen,
+    pub extensions: Extensions,
     pub reg_file: RegFile,
+    pub vreg_file: Option<VRegFile>,
     pub csr_file: CsrFile,
     pub bus: Bus,
     pub priv_mode: PrivilegeMode,
+    /// Checked after the devices tick; a pending request stops the hart
+    /// before it executes another instruction.
+    power: Option<PowerControl>,
 }
 
 impl Cpu {
-    pub fn new(bus: Bus, reset_vector: Option<u32>) -> Self {
+    pub fn new(bus: Bus, config: CpuConfig, reset_vector: Option<u64>) -> Self {
         let reset_vector = reset_vector.unwrap_or(DEFAULT_RESET_VECTOR);
 
         Self {
             pc: reset_vector,
             next_pc: reset_vector,
-            reg_file: RegFile::default(),
-            csr_file: CsrFile::default(),
+            xlen: config.xlen,
+            extensions: config.extensions,
+            reg_file: RegFile::new(config.xlen),
+            vreg_file: config.vlen.map(VRegFile::new),
+            csr_file: CsrFile::new(config.xlen, config.vlen),
             bus,
             priv_mode: PrivilegeMode::Machine,
+            power: None,
         }
     }
 
+    /// Lets power requests, e.g. a watchdog bite, stop the hart on the
+    /// cycle they are made. Until the request is taken, `step` does
+    /// nothing.
+    pub fn with_power(mut self, power: PowerControl) -> Self {
+        self.power = Some(power);
+        self
+    }
+
+    /// Puts the hart back into its reset state, starting at `pc`. Memory
+    /// and devices are left as they are.
+    pub fn reset(&mut self, pc: u64) {
+        let vlen = self
+            .vreg_file
+            .as_ref()
+            .map(|vregs| vregs.vlenb() as u32 * 8);
+
+        self.pc = pc;
+        self.next_pc = pc;
+        self.reg_file = RegFile::new(self.xlen);
+        self.vreg_file = vlen.map(VRegFile::new);
+        self.csr_file = CsrFile::new(self.xlen, vlen);
+        self.priv_mode = PrivilegeMode::Machine;
+    }
+
+    /// Resets the hart and every device on the bus, like pulling the reset
+    /// line of the whole machine. Memory keeps its contents.
+    pub fn reset_machine(&mut self, pc: u64) {
+        self.bus.reset();
+        self.reset(pc);
+    }
+
     pub fn fetch(&mut self) -> Result<Instr, Trap> {
-        let phys_pc = self.translate(self.pc)?;
+        let phys_pc = self.translate(self.pc, AccessType::Fetch)?;
         self.bus
             .load(phys_pc, INSTRUCTION_SIZE)
-            .map(Instr::from)
-            .map_err(Trap::from)
+            .map(|word| Instr::from(word as u32))
+            .map_err(|_| AccessType::Fetch.access_fault(self.pc))
     }
 
     pub fn execute(&mut self, instr: Instr) -> Result<(), Trap> {
         match instr.opcode() {
-            OP_IMM => rv32i::exec_op_imm(self, instr),
-            OP_REG => rv32i::exec_op_reg(self, instr),
+            OP_IMM if rv32i::is_base_op_imm(instr) => rv32i::exec_op_imm(self, instr),
+            OP_REG if rv32i::is_base_op_reg(instr) => rv32i::exec_op_reg(self, instr),
+            OP_REG if zicond::is_czero(instr) => zicond::exec_czero(self, instr),
+            OP_IMM_32 if self.xlen == Xlen::Rv64 && rv32i::is_base_op_imm(instr) => {
+                rv64i::exec_op_imm_32(self, instr)
+            }
+            OP_32 if self.xlen == Xlen::Rv64 && rv32i::is_base_op_reg(instr) => {
+                rv64i::exec_op_32(self, instr)
+            }
+            OP_IMM | OP_REG | OP_IMM_32 | OP_32 => zkn::exec_zkn(self, instr),
             LUI => rv32i::exec_lui(self, instr),
             AUIPC => rv32i::exec_auipc(self, instr),
             JAL => rv32i::exec_jal(self, instr),
@@ -50,6 +230,9 @@ impl Cpu {
             BRANCH => rv32i::exec_branch(self, instr),
             LOAD => rv32i::exec_load(self, instr),
             STORE => rv32i::exec_store(self, instr),
+            OP_V if self.vreg_file.is_some() => rvv::exec_op_v(self, instr),
+            LOAD_FP if self.vreg_file.is_some() => rvv::exec_load(self, instr),
+            STORE_FP if self.vreg_file.is_some() => rvv::exec_store(self, instr),
             SYSTEM => {
                 if privileged::is_privileged(instr) {
                     privileged::exec_privileged(self, instr)
@@ -62,70 +245,161 @@ impl Cpu {
     }
 
     pub fn step(&mut self) {
-        if let Err(trap) = self.try_step() {
+        self.bus.tick(self.cycles());
+        if self.stopped() {
+            return;
+        }
+
+        let irq = self.bus.irq_pending();
+        self.csr_file.set_meip(irq);
+
+        let result = match self.csr_file.pending_interrupt(self.priv_mode) {
+            Some(interrupt) => Err(Trap::Interrupt(interrupt)),
+            None => self.try_step(),
+        };
+
+        if let Err(trap) = result {
             self.handle_trap(trap);
         } else {
             self.csr_file.increment_instret();
         }
 
-        self.pc = self.next_pc;
+        self.pc = self.xlen.zext(self.next_pc);
         self.csr_file.increment_cycle();
     }
 
+    /// Whether a power request is waiting to be taken; `step` does nothing
+    /// until it is.
+    pub fn stopped(&self) -> bool {
+        self.power.as_ref().is_some_and(PowerControl::pending)
+    }
+
     pub fn cycles(&self) -> u64 {
         self.csr_file.get_cycle()
     }
 
     fn try_step(&mut self) -> Result<(), Trap> {
         let instr = self.fetch()?;
-        self.next_pc = self.pc.wrapping_add(INSTRUCTION_SIZE as u32);
+        self.next_pc = self.pc.wrapping_add(INSTRUCTION_SIZE as u64);
         self.execute(instr)?;
         Ok(())
     }
 
     fn handle_trap(&mut self, trap: Trap) {
-        let phys_pc = self.translate(self.pc).unwrap_or(0);
-        println!(
-            "Trap occurred: {:?} at PC={:#010x}({:#010x}), {:?}",
-            trap, self.pc, phys_pc, self.priv_mode
-        );
+        // no physical pc: translating would set A bits in the guest's page
+        // tables, or fault again
+        if !trap.is_interrupt() {
+            println!(
+                "Trap occurred: {:?} at PC={:#010x}, {:?}",
+                trap, self.pc, self.priv_mode
+            );
+        }
+
+        let mut cause = trap.cause_code() as u64;
+        if trap.is_interrupt() {
+            cause |= self.xlen.sign_bit();
+        }
 
         self.csr_file.set_exception_pc(self.pc);
-        self.csr_file.set_cause(trap.cause_code() as u32);
+        self.csr_file.set_cause(cause);
         self.csr_file.set_mtval(trap.value());
         let prev_priv = self.priv_mode;
         self.csr_file.enter_exception_mode(prev_priv);
 
         self.priv_mode = PrivilegeMode::Machine;
 
-        self.next_pc = self.csr_file.get_mtvec();
+        self.next_pc = self.csr_file.get_trap_vector(&trap);
     }
 
-    pub fn translate(&mut self, virt_addr: u32) -> Result<u32, Trap> {
-        let satp = self.csr_file.get_satp();
-        if satp & 0x8000_0000 == 0 {
-            return Ok(virt_addr);
+    /// Effective address of a load/store: `base + offset`, truncated to XLEN.
+    pub fn effective_addr(&self, base: u64, offset: i32) -> u64 {
+        self.xlen.zext(base.wrapping_add(offset as i64 as u64))
+    }
+
+    pub fn load(&mut self, virt_addr: u64, size: u8) -> Result<u64, Trap> {
+        let phys_addr = self.translate(virt_addr, AccessType::Load)?;
+        Ok(self.bus.load(phys_addr, size)?)
+    }
+
+    pub fn store(&mut self, virt_addr: u64, size: u8, val: u64) -> Result<(), Trap> {
+        let phys_addr = self.translate(virt_addr, AccessType::Store)?;
+        Ok(self.bus.store(phys_addr, size, val)?)
+    }
+
+    /// Leaf permission check. There is no S-mode, so M-mode with
+    /// translation on is held to the S-mode rules with SUM set: it may
+    /// load and store user pages but not execute them. MXR is not
+    /// implemented.
+    fn pte_allows(&self, pte: u64, access: AccessType) -> bool {
+        let user_page = pte & PTE_U != 0;
+        let privilege_ok = match self.priv_mode {
+            PrivilegeMode::User => user_page,
+            _ => !user_page || access != AccessType::Fetch,
+        };
+        privilege_ok && pte & access.permission() != 0
+    }
+
+    pub fn translate(&mut self, virt_addr: u64, access: AccessType) -> Result<u64, Trap> {
+        let scheme = match (self.xlen, self.csr_file.get_satp_mode()) {
+            (Xlen::Rv32, SATP_MODE_SV32) => &SV32,
+            (Xlen::Rv64, SATP_MODE_SV39) => &SV39,
+            (Xlen::Rv64, SATP_MODE_SV48) => &SV48,
+            _ => return Ok(virt_addr),
+        };
+
+        // rv64 virtual addresses must be sign-extended from the top vpn bit
+        if self.xlen == Xlen::Rv64 {
+            let va_bits = PAGE_SHIFT as u32 + scheme.levels * scheme.vpn_bits;
+            let canonical = ((virt_addr << (64 - va_bits)) as i64 >> (64 - va_bits)) as u64;
+            if canonical != virt_addr {
+                return Err(access.page_fault(virt_addr));
+            }
         }
 
-        let root_ppn = satp & 0x003f_ffff;
-        let root_pt_addr = root_ppn << 12;
+        let vpn_mask = (1 << scheme.vpn_bits) - 1;
+        let mut table_addr = self.csr_file.get_satp_ppn() << PAGE_SHIFT;
+
+        for level in (0..scheme.levels).rev() {
+            let vpn_shift = PAGE_SHIFT + (level * scheme.vpn_bits) as u64;
+            let vpn = (virt_addr >> vpn_shift) & vpn_mask;
+            let pte_addr = table_addr + vpn * scheme.pte_size as u64;
+            // a bad table address faults the access that needed the walk
+            let pte = self
+                .bus
+                .load(pte_addr, scheme.pte_size)
+                .map_err(|_| access.access_fault(virt_addr))?;
 
-        let vpn1 = (virt_addr >> 22) & 0x3ff;
-        let pte1_addr = root_pt_addr + (vpn1 * 4);
-        let pte1 = self.bus.load(pte1_addr, 4)?;
+            if pte & PTE_V == 0 || (pte & PTE_R == 0 && pte & PTE_W != 0) {
+                return Err(access.page_fault(virt_addr));
+            }
 
-        let pt0_ppn = (pte1 >> 10) & 0x003f_ffff;
-        let pt0_addr = pt0_ppn << 12;
+            let ppn = (pte >> 10) & scheme.ppn_mask;
 
-        let vpn0 = (virt_addr >> 12) & 0x3ff;
-        let pte0_addr = pt0_addr + (vpn0 * 4);
-        let pte0 = self.bus.load(pte0_addr, 4)?;
+            if pte & (PTE_R | PTE_X) != 0 {
+                // leaf, possibly a superpage whose low ppn bits must be zero
+                let page_mask = (1 << vpn_shift) - 1;
+                let page_base = ppn << PAGE_SHIFT;
+                if page_base & page_mask != 0 || !self.pte_allows(pte, access) {
+                    return Err(access.page_fault(virt_addr));
+                }
 
-        let final_ppn = (pte0 >> 10) & 0x003f_ffff;
+                // A and D are kept up to date by the hart rather than
+                // faulting, so page tables that leave them clear still work
+                let mut updated = pte | PTE_A;
+                if access == AccessType::Store {
+                    updated |= PTE_D;
+                }
+                if updated != pte {
+                    self.bus
+                        .store(pte_addr, scheme.pte_size, updated)
+                        .map_err(|_| access.access_fault(virt_addr))?;
+                }
+                return Ok(page_base | (virt_addr & page_mask));
+            }
 
-        let offset = virt_addr & 0xfff;
+            table_addr = ppn << PAGE_SHIFT;
+        }
 
-        let phys_addr = (final_ppn << 12) | offset;
-        Ok(phys_addr)
+        Err(access.page_fault(virt_addr))
     }
 }
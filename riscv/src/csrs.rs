@@ -1,8 +1,11 @@
 use std::fmt;
 
-use crate::isa::PrivilegeMode;
+use crate::isa::{PrivilegeMode, Xlen};
+use crate::trap::{Interrupt, Trap};
 
 pub mod csr_addr {
+    pub const MHARTID: u16 = 0xF14;
+
     pub const MSTATUS: u16 = 0x300;
     pub const MISA: u16 = 0x301;
     pub const MIE: u16 = 0x304;
@@ -16,37 +19,84 @@ pub mod csr_addr {
 
     pub const SATP: u16 = 0x180;
 
+    pub const VSTART: u16 = 0x008;
+    pub const VXSAT: u16 = 0x009;
+    pub const VXRM: u16 = 0x00A;
+    pub const VCSR: u16 = 0x00F;
+    pub const VL: u16 = 0xC20;
+    pub const VTYPE: u16 = 0xC21;
+    pub const VLENB: u16 = 0xC22;
+
     pub const MCYCLE: u16 = 0xB00;
     pub const MINSTRET: u16 = 0xB02;
+    pub const MCYCLEH: u16 = 0xB80;
+    pub const MINSTRETH: u16 = 0xB82;
 }
 
-const MSTATUS_MIE: u32 = 1 << 3;
-const MSTATUS_MPIE: u32 = 1 << 7;
-const MSTATUS_MPP: u32 = 0b11 << 11;
+const MSTATUS_MIE: u64 = 1 << 3;
+const MSTATUS_MPIE: u64 = 1 << 7;
+const MSTATUS_VS: u64 = 0b11 << 9;
+const MSTATUS_MPP: u64 = 0b11 << 11;
+const MSTATUS_UXL_SHIFT: u64 = 32;
+
+const MSTATUS_VS_OFF: u64 = 0b00 << 9;
+const MSTATUS_VS_DIRTY: u64 = 0b11 << 9;
+
+const MIP_MEIP: u64 = 1 << 11;
+
+const MTVEC_MODE_VECTORED: u64 = 0b01;
+
+// V stays clear even with vectors on: the hart implements a subset of the
+// integer part only, which misa has no bit for
+const MISA_EXTENSIONS: u64 = 0x0000_1100; // i, m
+
+pub const SATP_MODE_SV32: u64 = 1;
+pub const SATP_MODE_SV39: u64 = 8;
+pub const SATP_MODE_SV48: u64 = 9;
 
 pub struct CsrFile {
-    mstatus: u32,
-    misa: u32,
-    mie: u32,
-    mtvec: u32,
+    xlen: Xlen,
 
-    mscratch: u32,
-    mepc: u32,
-    mcause: u32,
-    mtval: u32,
-    mip: u32,
+    mstatus: u64,
+    misa: u64,
+    mie: u64,
+    mtvec: u64,
 
-    satp: u32,
+    mscratch: u64,
+    mepc: u64,
+    mcause: u64,
+    mtval: u64,
+    mip: u64,
+
+    satp: u64,
 
     mcycle: u64,
     minstret: u64,
+
+    // vector state, only present with the v extension
+    vlenb: Option<u64>,
+    vstart: u64,
+    vxsat: u64,
+    vxrm: u64,
+    vl: u64,
+    vtype: u64,
 }
 
 impl CsrFile {
-    pub fn new() -> Self {
+    /// `vlen` enables the vector CSRs; `None` means no v extension.
+    pub fn new(xlen: Xlen, vlen: Option<u32>) -> Self {
+        let mstatus = match xlen {
+            Xlen::Rv32 => 0,
+            Xlen::Rv64 => xlen.mxl() << MSTATUS_UXL_SHIFT,
+        };
+
+        let misa = (xlen.mxl() << (xlen.bits() - 2)) | MISA_EXTENSIONS;
+
         Self {
-            mstatus: 0,
-            misa: 0x40001100, // rv32i
+            xlen,
+
+            mstatus,
+            misa,
             mie: 0,
             mtvec: 0,
 
@@ -60,39 +110,66 @@ impl CsrFile {
 
             mcycle: 0,
             minstret: 0,
+
+            vlenb: vlen.map(|vlen| (vlen / 8) as u64),
+            vstart: 0,
+            vxsat: 0,
+            vxrm: 0,
+            vl: 0,
+            vtype: xlen.sign_bit(), // vill
         }
     }
 
-    pub fn read(&self, addr: u16) -> Result<u32, ()> {
+    pub fn read(&self, addr: u16) -> Result<u64, ()> {
         // TODO: privilege checks
 
-        match addr {
-            csr_addr::MSTATUS => Ok(self.mstatus),
-            csr_addr::MISA => Ok(self.misa),
-            csr_addr::MIE => Ok(self.mie),
-            csr_addr::MTVEC => Ok(self.mtvec),
-            csr_addr::MSCRATCH => Ok(self.mscratch),
-            csr_addr::MEPC => Ok(self.mepc),
-            csr_addr::MCAUSE => Ok(self.mcause),
-            csr_addr::MTVAL => Ok(self.mtval),
-            csr_addr::MIP => Ok(self.mip),
-            csr_addr::SATP => Ok(self.satp),
-            csr_addr::MCYCLE => Ok((self.mcycle & 0xFFFF_FFFF) as u32),
-            csr_addr::MINSTRET => Ok((self.minstret & 0xFFFF_FFFF) as u32),
-            _ => Err(()),
-        }
+        let val = match addr {
+            // a single hart
+            csr_addr::MHARTID => 0,
+            csr_addr::MSTATUS => self.get_mstatus(),
+            csr_addr::MISA => self.misa,
+            csr_addr::MIE => self.mie,
+            csr_addr::MTVEC => self.mtvec,
+            csr_addr::MSCRATCH => self.mscratch,
+            csr_addr::MEPC => self.mepc,
+            csr_addr::MCAUSE => self.mcause,
+            csr_addr::MTVAL => self.mtval,
+            csr_addr::MIP => self.mip,
+            csr_addr::SATP => self.satp,
+            csr_addr::MCYCLE => self.mcycle,
+            csr_addr::MINSTRET => self.minstret,
+            csr_addr::MCYCLEH if self.xlen == Xlen::Rv32 => self.mcycle >> 32,
+            csr_addr::MINSTRETH if self.xlen == Xlen::Rv32 => self.minstret >> 32,
+            csr_addr::VSTART if self.vector_enabled() => self.vstart,
+            csr_addr::VXSAT if self.vector_enabled() => self.vxsat,
+            csr_addr::VXRM if self.vector_enabled() => self.vxrm,
+            csr_addr::VCSR if self.vector_enabled() => (self.vxrm << 1) | self.vxsat,
+            csr_addr::VL if self.vector_enabled() => self.vl,
+            csr_addr::VTYPE if self.vector_enabled() => self.vtype,
+            csr_addr::VLENB if self.vector_enabled() => self.vlenb.unwrap_or(0),
+            _ => return Err(()),
+        };
+
+        Ok(self.xlen.zext(val))
     }
 
-    pub fn write(&mut self, addr: u16, val: u32) -> Result<(), ()> {
+    pub fn write(&mut self, addr: u16, val: u64) -> Result<(), ()> {
         // TODO: privilege checks
 
+        let val = self.xlen.zext(val);
+
         match addr {
             csr_addr::MSTATUS => {
-                self.mstatus = val & 0x00001888; // wpri
+                // wpri, uxl is read-only, vs only exists with the v extension
+                let mut mask = 0x00001888;
+                if self.vlenb.is_some() {
+                    mask |= MSTATUS_VS;
+                }
+                self.mstatus = (self.mstatus & !mask) | (val & mask);
                 Ok(())
             }
             csr_addr::MISA => {
-                self.misa = val;
+                // mxl and the extension set are fixed
                 Ok(())
             }
             csr_addr::MIE => {
@@ -120,45 +197,159 @@ impl CsrFile {
                 Ok(())
             }
             csr_addr::MIP => {
-                self.mip = val & 0x888; // msip, mtip, meip only
+                // msip, mtip only; meip follows the external interrupt line
+                self.mip = (val & 0x88) | (self.mip & MIP_MEIP);
                 Ok(())
             }
             csr_addr::SATP => {
-                self.satp = val;
+                // unsupported modes leave satp unchanged (warl)
+                if self.xlen == Xlen::Rv32
+                    || matches!(val >> 60, 0 | SATP_MODE_SV39 | SATP_MODE_SV48)
+                {
+                    self.satp = val;
+                }
                 Ok(())
             }
             csr_addr::MCYCLE => {
-                self.mcycle = (self.mcycle & 0xFFFF_FFFF_0000_0000) | (val as u64);
+                self.mcycle = match self.xlen {
+                    Xlen::Rv32 => (self.mcycle & 0xFFFF_FFFF_0000_0000) | val,
+                    Xlen::Rv64 => val,
+                };
                 Ok(())
             }
             csr_addr::MINSTRET => {
-                self.minstret = (self.minstret & 0xFFFF_FFFF_0000_0000) | (val as u64);
+                self.minstret = match self.xlen {
+                    Xlen::Rv32 => (self.minstret & 0xFFFF_FFFF_0000_0000) | val,
+                    Xlen::Rv64 => val,
+                };
+                Ok(())
+            }
+            csr_addr::MCYCLEH if self.xlen == Xlen::Rv32 => {
+                self.mcycle = (self.mcycle & 0xFFFF_FFFF) | (val << 32);
+                Ok(())
+            }
+            csr_addr::MINSTRETH if self.xlen == Xlen::Rv32 => {
+                self.minstret = (self.minstret & 0xFFFF_FFFF) | (val << 32);
+                Ok(())
+            }
+            csr_addr::VSTART if self.vector_enabled() => {
+                self.vstart = val;
+                self.set_vector_dirty();
+                Ok(())
+            }
+            csr_addr::VXSAT if self.vector_enabled() => {
+                self.vxsat = val & 0x1;
+                self.set_vector_dirty();
+                Ok(())
+            }
+            csr_addr::VXRM if self.vector_enabled() => {
+                self.vxrm = val & 0x3;
+                self.set_vector_dirty();
+                Ok(())
+            }
+            csr_addr::VCSR if self.vector_enabled() => {
+                self.vxsat = val & 0x1;
+                self.vxrm = (val >> 1) & 0x3;
+                self.set_vector_dirty();
                 Ok(())
             }
             _ => Err(()),
         }
     }
 
-    pub fn set_exception_pc(&mut self, pc: u32) {
-        self.mepc = pc;
+    pub fn set_exception_pc(&mut self, pc: u64) {
+        self.mepc = self.xlen.zext(pc);
     }
 
-    pub fn set_cause(&mut self, cause: u32) {
-        self.mcause = cause;
+    pub fn set_cause(&mut self, cause: u64) {
+        self.mcause = self.xlen.zext(cause);
     }
 
-    pub fn set_mtval(&mut self, value: u32) {
-        self.mtval = value;
+    pub fn set_mtval(&mut self, value: u64) {
+        self.mtval = self.xlen.zext(value);
     }
 
-    pub fn get_mtvec(&self) -> u32 {
+    pub fn get_mtvec(&self) -> u64 {
         self.mtvec & !0b11
     }
 
-    pub fn get_mepc(&self) -> u32 {
+    /// Trap handler address: interrupts jump to `base + 4 * cause` when mtvec
+    /// is in vectored mode.
+    pub fn get_trap_vector(&self, trap: &Trap) -> u64 {
+        if trap.is_interrupt() && self.mtvec & 0b11 == MTVEC_MODE_VECTORED {
+            self.get_mtvec() + 4 * trap.cause_code() as u64
+        } else {
+            self.get_mtvec()
+        }
+    }
+
+    pub fn set_meip(&mut self, pending: bool) {
+        if pending {
+            self.mip |= MIP_MEIP;
+        } else {
+            self.mip &= !MIP_MEIP;
+        }
+    }
+
+    /// Highest priority interrupt that is pending, enabled and not masked by
+    /// mstatus.MIE (which only applies while in M-mode).
+    pub fn pending_interrupt(&self, priv_mode: PrivilegeMode) -> Option<Interrupt> {
+        let globally_enabled = priv_mode != PrivilegeMode::Machine || self.get_mie();
+        if globally_enabled && self.mip & self.mie & MIP_MEIP != 0 {
+            Some(Interrupt::MachineExternal)
+        } else {
+            None
+        }
+    }
+
+    pub fn get_mepc(&self) -> u64 {
         self.mepc
     }
 
+    /// mstatus with the read-only sd summary bit filled in.
+    pub fn get_mstatus(&self) -> u64 {
+        if self.mstatus & MSTATUS_VS == MSTATUS_VS_DIRTY {
+            self.mstatus | self.xlen.sign_bit()
+        } else {
+            self.mstatus
+        }
+    }
+
+    /// Whether vector instructions may execute: v is implemented and
+    /// mstatus.vs is not off.
+    pub fn vector_enabled(&self) -> bool {
+        self.vlenb.is_some() && self.mstatus & MSTATUS_VS != MSTATUS_VS_OFF
+    }
+
+    pub fn set_vector_dirty(&mut self) {
+        self.mstatus |= MSTATUS_VS_DIRTY;
+    }
+
+    pub fn get_vl(&self) -> u64 {
+        self.vl
+    }
+
+    pub fn get_vtype(&self) -> u64 {
+        self.vtype
+    }
+
+    pub fn set_vl_vtype(&mut self, vl: u64, vtype: u64) {
+        self.vl = vl;
+        self.vtype = vtype;
+    }
+
+    pub fn get_vstart(&self) -> u64 {
+        self.vstart
+    }
+
+    pub fn set_vstart(&mut self, vstart: u64) {
+        self.vstart = vstart;
+    }
+
+    pub fn set_vxsat(&mut self) {
+        self.vxsat = 1;
+    }
+
     pub fn get_cycle(&self) -> u64 {
         self.mcycle
     }
@@ -175,12 +366,24 @@ impl CsrFile {
         ((self.mstatus & MSTATUS_MPP) >> 11) as u8
     }
 
-    pub fn get_satp(&self) -> u32 {
+    pub fn get_satp(&self) -> u64 {
         self.satp
     }
 
-    pub fn set_satp(&mut self, value: u32) {
-        self.satp = value;
+    /// Translation mode encoded in satp, normalized to the RV64 encoding
+    /// (Sv32 is reported as `SATP_MODE_SV32`).
+    pub fn get_satp_mode(&self) -> u64 {
+        match self.xlen {
+            Xlen::Rv32 => self.satp >> 31,
+            Xlen::Rv64 => self.satp >> 60,
+        }
+    }
+
+    pub fn get_satp_ppn(&self) -> u64 {
+        match self.xlen {
+            Xlen::Rv32 => self.satp & 0x003f_ffff,
+            Xlen::Rv64 => self.satp & 0x0fff_ffff_ffff,
+        }
     }
 
     pub fn increment_cycle(&mut self) {
@@ -203,7 +406,7 @@ impl CsrFile {
         self.mstatus &= !MSTATUS_MIE;
 
         self.mstatus &= !MSTATUS_MPP;
-        self.mstatus |= (prev_priv as u32) << 11;
+        self.mstatus |= (prev_priv as u64) << 11;
     }
 
     pub fn return_from_exception_mode(&mut self) {
@@ -220,7 +423,7 @@ impl CsrFile {
 
 impl Default for CsrFile {
     fn default() -> Self {
-        Self::new()
+        Self::new(Xlen::default(), None)
     }
 }
 
@@ -254,6 +457,13 @@ impl fmt::Debug for CsrFile {
         if self.minstret != 0 {
             ds.field("minstret", &format_args!("{:#018x}", self.minstret));
         }
+        if self.vlenb.is_some() {
+            ds.field("vl", &self.vl);
+            ds.field("vtype", &format_args!("{:#010x}", self.vtype));
+            if self.vstart != 0 {
+                ds.field("vstart", &self.vstart);
+            }
+        }
         ds.finish()
     }
 }
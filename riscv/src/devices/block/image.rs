@@ -0,0 +1,120 @@
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::devices::block::{BlockBackend, RawFile, SparseImage};
+
+/// A block backend shared by the devices exposing it. Clones are handles to
+/// the same backend, so the emulator can keep one to commit a snapshot
+/// while a disk controller owns another.
+///
+/// In snapshot mode writes land in an in-memory copy-on-write overlay,
+/// which can later be committed back to the image or saved as a delta.
+#[derive(Clone)]
+pub struct DiskImage {
+    backend: Arc<Mutex<dyn BlockBackend>>,
+    /// The same object as `backend` in snapshot mode.
+    snapshot: Option<Arc<Mutex<SparseImage>>>,
+}
+
+impl DiskImage {
+    pub fn new(backend: impl BlockBackend + 'static) -> Self {
+        Self {
+            backend: Arc::new(Mutex::new(backend)),
+            snapshot: None,
+        }
+    }
+
+    /// A raw image file, opened for reading and writing.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::new(RawFile::open(path)?))
+    }
+
+    /// A raw image file behind a snapshot; the file is only written by
+    /// `commit`. Unlike `open`, a missing image is an error rather than an
+    /// empty disk.
+    pub fn snapshot(path: impl AsRef<Path>) -> io::Result<Self> {
+        let base: Box<dyn BlockBackend> = match RawFile::open_existing(path.as_ref()) {
+            Ok(file) => Box::new(file),
+            // the guest can still run, only committing will fail
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+                Box::new(RawFile::open_read_only(path)?)
+            }
+            Err(e) => return Err(e),
+        };
+        Ok(Self::snapshot_of(base))
+    }
+
+    pub fn snapshot_of(base: Box<dyn BlockBackend>) -> Self {
+        let snapshot = Arc::new(Mutex::new(SparseImage::overlay(base)));
+        Self {
+            backend: snapshot.clone(),
+            snapshot: Some(snapshot),
+        }
+    }
+
+    /// Size in bytes, including anything the overlay has appended.
+    pub fn len(&self) -> u64 {
+        self.backend.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.backend.lock().unwrap().is_read_only()
+    }
+
+    pub fn is_snapshot(&self) -> bool {
+        self.snapshot.is_some()
+    }
+
+    /// Number of sectors held in the overlay.
+    pub fn dirty_sectors(&self) -> usize {
+        self.snapshot
+            .as_ref()
+            .map_or(0, |snapshot| snapshot.lock().unwrap().dirty_sectors())
+    }
+
+    /// See `BlockBackend::read_at`.
+    pub fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        self.backend.lock().unwrap().read_at(offset, buf)
+    }
+
+    pub fn write_at(&self, offset: u64, data: &[u8]) -> io::Result<()> {
+        self.backend.lock().unwrap().write_at(offset, data)
+    }
+
+    pub fn flush(&self) -> io::Result<()> {
+        self.backend.lock().unwrap().flush()
+    }
+
+    /// Writes the overlay back to the image and starts a new, empty one.
+    /// Without a snapshot there is nothing to commit.
+    pub fn commit(&self) -> io::Result<usize> {
+        match &self.snapshot {
+            Some(snapshot) => snapshot.lock().unwrap().commit(),
+            None => Ok(0),
+        }
+    }
+
+    /// Saves the overlay to `path`, leaving it in place. Returns the number
+    /// of sectors written.
+    pub fn save_delta(&self, path: impl AsRef<Path>) -> io::Result<usize> {
+        match &self.snapshot {
+            Some(snapshot) => snapshot.lock().unwrap().save(path),
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "disk image is not a snapshot",
+            )),
+        }
+    }
+
+    /// Replays a delta written by `save_delta`: into the overlay for a
+    /// snapshot, otherwise straight into the image.
+    pub fn apply_delta(&self, path: impl AsRef<Path>) -> io::Result<usize> {
+        let delta = SparseImage::load(path)?;
+        delta.copy_into(&mut *self.backend.lock().unwrap())
+    }
+}
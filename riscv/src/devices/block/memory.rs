@@ -0,0 +1,56 @@
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use crate::devices::block::BlockBackend;
+
+/// An image that lives in memory only, e.g. to run a guest against a
+/// prepared buffer and inspect it afterwards. Clones share the same data,
+/// so a test can keep one handle while a disk owns another.
+#[derive(Clone, Default)]
+pub struct MemoryImage {
+    data: Arc<Mutex<Vec<u8>>>,
+}
+
+impl MemoryImage {
+    /// A zeroed image of `len` bytes.
+    pub fn new(len: usize) -> Self {
+        Self::from_bytes(vec![0; len])
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> Self {
+        Self {
+            data: Arc::new(Mutex::new(data)),
+        }
+    }
+
+    /// A copy of the current contents.
+    pub fn data(&self) -> Vec<u8> {
+        self.data.lock().unwrap().clone()
+    }
+}
+
+impl BlockBackend for MemoryImage {
+    fn len(&self) -> u64 {
+        self.data.lock().unwrap().len() as u64
+    }
+
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        let image = self.data.lock().unwrap();
+        buf.fill(0);
+        let start = offset.min(image.len() as u64) as usize;
+        let len = (image.len() - start).min(buf.len());
+        buf[..len].copy_from_slice(&image[start..start + len]);
+        Ok(len)
+    }
+
+    fn write_at(&mut self, offset: u64, data: &[u8]) -> io::Result<()> {
+        let mut image = self.data.lock().unwrap();
+        let start = usize::try_from(offset).map_err(|_| io::ErrorKind::InvalidInput)?;
+        let end = start + data.len();
+        if end > image.len() {
+            image.resize(end, 0);
+        }
+        image[start..end].copy_from_slice(data);
+        Ok(())
+    }
+}
@@ -0,0 +1,62 @@
+//! Storage behind the disk controllers. A backend is a flat byte array;
+//! the controllers deal with sectors and share one through a `DiskImage`.
+
+pub mod image;
+pub mod memory;
+pub mod raw;
+pub mod sparse;
+
+pub use image::*;
+pub use memory::*;
+pub use raw::*;
+pub use sparse::*;
+
+use std::io;
+
+pub const SECTOR_SIZE: usize = 512;
+
+pub trait BlockBackend: Send {
+    /// Size in bytes; a trailing partial sector is allowed.
+    fn len(&self) -> u64;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Fills `buf` from `offset` and returns how many bytes the image holds
+    /// there; the rest of `buf` is zeroed.
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> io::Result<usize>;
+
+    /// Writing past the end grows the image.
+    fn write_at(&mut self, offset: u64, data: &[u8]) -> io::Result<()>;
+
+    /// Makes written data durable.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn is_read_only(&self) -> bool {
+        false
+    }
+}
+
+/// Rejects all writes to the wrapped backend.
+pub struct ReadOnly<B>(pub B);
+
+impl<B: BlockBackend> BlockBackend for ReadOnly<B> {
+    fn len(&self) -> u64 {
+        self.0.len()
+    }
+
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read_at(offset, buf)
+    }
+
+    fn write_at(&mut self, _offset: u64, _data: &[u8]) -> io::Result<()> {
+        Err(io::ErrorKind::ReadOnlyFilesystem.into())
+    }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
+}
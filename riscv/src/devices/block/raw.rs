@@ -0,0 +1,91 @@
+use std::fs::File;
+use std::io;
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+
+use crate::devices::block::BlockBackend;
+
+/// A raw image file, sector 0 at offset 0.
+pub struct RawFile {
+    file: File,
+    len: u64,
+    read_only: bool,
+}
+
+impl RawFile {
+    /// Opens `path` for reading and writing, creating an empty image if it
+    /// doesn't exist.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        Self::with_file(file, false)
+    }
+
+    /// Like `open`, but fails if the image doesn't exist.
+    pub fn open_existing(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::options().read(true).write(true).open(path)?;
+        Self::with_file(file, false)
+    }
+
+    pub fn open_read_only(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::with_file(File::open(path)?, true)
+    }
+
+    fn with_file(file: File, read_only: bool) -> io::Result<Self> {
+        let len = file.metadata()?.len();
+        Ok(Self {
+            file,
+            len,
+            read_only,
+        })
+    }
+}
+
+impl BlockBackend for RawFile {
+    fn len(&self) -> u64 {
+        self.len
+    }
+
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        buf.fill(0);
+        let available = self.len.saturating_sub(offset).min(buf.len() as u64) as usize;
+
+        let mut len = 0;
+        while len < available {
+            match self
+                .file
+                .read_at(&mut buf[len..available], offset + len as u64)
+            {
+                Ok(0) => break,
+                Ok(n) => len += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(len)
+    }
+
+    fn write_at(&mut self, offset: u64, data: &[u8]) -> io::Result<()> {
+        if self.read_only {
+            return Err(io::ErrorKind::ReadOnlyFilesystem.into());
+        }
+        self.file.write_all_at(data, offset)?;
+        self.len = self.len.max(offset + data.len() as u64);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.read_only {
+            true => Ok(()),
+            false => self.file.sync_data(),
+        }
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+}
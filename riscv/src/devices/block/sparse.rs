@@ -0,0 +1,188 @@
+use std::collections::BTreeMap;
+use std::collections::btree_map::Entry;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::devices::block::{BlockBackend, SECTOR_SIZE};
+
+const SPARSE_MAGIC: &[u8; 8] = b"RVDELTA1";
+
+type Sector = Box<[u8; SECTOR_SIZE]>;
+
+/// Only stores the sectors written to it. Unwritten sectors read as zero,
+/// or from `base` when used as a copy-on-write overlay.
+///
+/// Saved files hold the length and the written sectors, so a saved overlay
+/// doubles as a delta against its base.
+pub struct SparseImage {
+    len: u64,
+    sectors: BTreeMap<u64, Sector>,
+    base: Option<Box<dyn BlockBackend>>,
+}
+
+impl SparseImage {
+    /// An all-zero image of `len` bytes.
+    pub fn new(len: u64) -> Self {
+        Self {
+            len,
+            sectors: BTreeMap::new(),
+            base: None,
+        }
+    }
+
+    /// A copy-on-write overlay; `base` is only read until `commit`.
+    pub fn overlay(base: Box<dyn BlockBackend>) -> Self {
+        Self {
+            len: base.len(),
+            sectors: BTreeMap::new(),
+            base: Some(base),
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let invalid = |msg| io::Error::new(io::ErrorKind::InvalidData, msg);
+        let mut input = BufReader::new(File::open(path)?);
+
+        let mut magic = [0; 8];
+        input.read_exact(&mut magic)?;
+        if &magic != SPARSE_MAGIC {
+            return Err(invalid("not a sparse disk image"));
+        }
+        if read_u32(&mut input)? as usize != SECTOR_SIZE {
+            return Err(invalid("unsupported sector size in sparse image"));
+        }
+        let mut image = Self::new(read_u64(&mut input)?);
+        let count = read_u64(&mut input)?;
+
+        for _ in 0..count {
+            let sector = read_u64(&mut input)?;
+            let mut data = Box::new([0; SECTOR_SIZE]);
+            input.read_exact(&mut data[..])?;
+            image.sectors.insert(sector, data);
+        }
+        Ok(image)
+    }
+
+    /// Writes the length and all written sectors to `path`; returns the
+    /// number of sectors saved.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<usize> {
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(SPARSE_MAGIC)?;
+        out.write_all(&(SECTOR_SIZE as u32).to_le_bytes())?;
+        out.write_all(&self.len.to_le_bytes())?;
+        out.write_all(&(self.sectors.len() as u64).to_le_bytes())?;
+        for (sector, data) in &self.sectors {
+            out.write_all(&sector.to_le_bytes())?;
+            out.write_all(&data[..])?;
+        }
+        out.flush()?;
+        Ok(self.sectors.len())
+    }
+
+    /// Number of sectors that have been written.
+    pub fn dirty_sectors(&self) -> usize {
+        self.sectors.len()
+    }
+
+    /// Writes the written sectors into `target`; returns how many.
+    pub fn copy_into(&self, target: &mut dyn BlockBackend) -> io::Result<usize> {
+        copy_sectors(&self.sectors, self.len, target)
+    }
+
+    /// Writes the overlay back to the base and starts over with an empty
+    /// one. Without a base there is nothing to commit to.
+    pub fn commit(&mut self) -> io::Result<usize> {
+        let Some(base) = &mut self.base else {
+            return Ok(0);
+        };
+
+        let count = copy_sectors(&self.sectors, self.len, base.as_mut())?;
+        base.flush()?;
+        self.sectors.clear();
+        Ok(count)
+    }
+}
+
+impl BlockBackend for SparseImage {
+    fn len(&self) -> u64 {
+        self.len
+    }
+
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        buf.fill(0);
+        let available = self.len.saturating_sub(offset).min(buf.len() as u64) as usize;
+
+        let mut pos = 0;
+        while pos < available {
+            let at = offset + pos as u64;
+            let sector = at / SECTOR_SIZE as u64;
+            let start = (at % SECTOR_SIZE as u64) as usize;
+            let count = (SECTOR_SIZE - start).min(available - pos);
+
+            let out = &mut buf[pos..pos + count];
+            match self.sectors.get(&sector) {
+                Some(data) => out.copy_from_slice(&data[start..start + count]),
+                None => {
+                    if let Some(base) = &mut self.base {
+                        base.read_at(at, out)?;
+                    }
+                }
+            }
+            pos += count;
+        }
+        Ok(available)
+    }
+
+    fn write_at(&mut self, offset: u64, data: &[u8]) -> io::Result<()> {
+        let mut pos = 0;
+        while pos < data.len() {
+            let at = offset + pos as u64;
+            let sector = at / SECTOR_SIZE as u64;
+            let start = (at % SECTOR_SIZE as u64) as usize;
+            let count = (SECTOR_SIZE - start).min(data.len() - pos);
+
+            let slot = match self.sectors.entry(sector) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    // a partial write needs the rest of the sector from the base
+                    let mut copy = Box::new([0; SECTOR_SIZE]);
+                    if let Some(base) = &mut self.base {
+                        base.read_at(sector * SECTOR_SIZE as u64, &mut copy[..])?;
+                    }
+                    entry.insert(copy)
+                }
+            };
+            slot[start..start + count].copy_from_slice(&data[pos..pos + count]);
+            pos += count;
+        }
+        self.len = self.len.max(offset + data.len() as u64);
+        Ok(())
+    }
+}
+
+fn copy_sectors(
+    sectors: &BTreeMap<u64, Sector>,
+    len: u64,
+    target: &mut dyn BlockBackend,
+) -> io::Result<usize> {
+    for (sector, data) in sectors {
+        let offset = sector * SECTOR_SIZE as u64;
+        // the last sector is only partially part of the image
+        let count = len.saturating_sub(offset).min(SECTOR_SIZE as u64) as usize;
+        target.write_at(offset, &data[..count])?;
+    }
+    Ok(sectors.len())
+}
+
+fn read_u32(input: &mut impl Read) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    input.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64(input: &mut impl Read) -> io::Result<u64> {
+    let mut buf = [0; 8];
+    input.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
@@ -0,0 +1,50 @@
+use crate::isa::Xlen;
+
+/// Where the boot code keeps the kernel entry point, as a 64-bit value.
+const ENTRY_OFFSET: usize = 24;
+
+/// Where the device tree blob goes; FDTs must be 8-byte aligned.
+const DTB_OFFSET: usize = 0x40;
+
+const REG_T0: u32 = 5;
+const REG_A0: u32 = 10;
+const REG_A1: u32 = 11;
+
+const CSR_MHARTID: u32 = 0xf14;
+
+fn i_type(opcode: u32, funct3: u32, rd: u32, rs1: u32, imm: u32) -> u32 {
+    (imm & 0xfff) << 20 | rs1 << 15 | funct3 << 12 | rd << 7 | opcode
+}
+
+/// Contents of a boot ROM in the style of QEMU's virt machine: the hart
+/// enters the kernel at `entry` with a0 = mhartid and a1 = the address of
+/// `dtb`, or 0 without one. The code is position independent, the ROM
+/// can be mapped anywhere.
+pub fn boot_rom(xlen: Xlen, entry: u64, dtb: Option<&[u8]>) -> Vec<u8> {
+    let load = match xlen {
+        Xlen::Rv32 => 2, // lw
+        Xlen::Rv64 => 3, // ld
+    };
+    // a1: the DTB address, or 0 without one
+    let dtb_addr = match dtb {
+        Some(_) => i_type(0x13, 0, REG_A1, REG_T0, DTB_OFFSET as u32), // addi a1, t0, DTB_OFFSET
+        None => i_type(0x13, 0, REG_A1, 0, 0),                         // li a1, 0
+    };
+
+    let code = [
+        REG_T0 << 7 | 0x17,                      // auipc t0, 0
+        i_type(0x73, 2, REG_A0, 0, CSR_MHARTID), // csrr a0, mhartid
+        dtb_addr,
+        i_type(0x03, load, REG_T0, REG_T0, ENTRY_OFFSET as u32), // l[wd] t0, ENTRY_OFFSET(t0)
+        i_type(0x67, 0, 0, REG_T0, 0),                           // jr t0
+    ];
+
+    let mut rom: Vec<u8> = code.iter().flat_map(|word| word.to_le_bytes()).collect();
+    rom.resize(ENTRY_OFFSET, 0);
+    rom.extend_from_slice(&entry.to_le_bytes());
+    if let Some(dtb) = dtb {
+        rom.resize(DTB_OFFSET, 0);
+        rom.extend_from_slice(dtb);
+    }
+    rom
+}
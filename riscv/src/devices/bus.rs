@@ -1,7 +1,11 @@
+use std::io;
+
+use crate::devices::FdtNode;
+
 #[derive(Debug)]
 pub enum BusError {
-    LoadAccessFault(u32),
-    StoreAccessFault(u32),
+    LoadAccessFault(u64),
+    StoreAccessFault(u64),
 }
 
 pub trait Device {
@@ -18,14 +22,96 @@ pub trait Device {
         Ok(())
     }
 
+    fn read_bytes(&mut self, addr: u32, buf: &mut [u8]) -> Result<(), BusError> {
+        for (i, byte) in buf.iter_mut().enumerate() {
+            *byte = self.load(addr + (i as u32), 1)? as u8;
+        }
+        Ok(())
+    }
+
     fn size(&self) -> u32;
+
+    /// Level of the device's interrupt line, polled before every instruction.
+    fn irq_pending(&mut self) -> bool {
+        false
+    }
+
+    /// Called once per cycle; bus masters reach the other devices through
+    /// `dma`.
+    fn tick(&mut self, _now: u64, _dma: &mut DmaView) {}
+
+    /// Returns the device to its power-on state, as on a machine reset.
+    /// Memory contents are kept.
+    fn reset(&mut self) {}
+
+    /// Writes state the guest left in host resources back to them, before
+    /// the emulator exits.
+    fn sync(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// How the device appears in the generated device tree; `None` leaves
+    /// it out.
+    fn fdt_node(&self) -> Option<FdtNode> {
+        None
+    }
 }
 
 struct MappedDevice {
-    base_addr: u32,
+    base_addr: u64,
     device: Box<dyn Device>,
 }
 
+fn find_mapping(mappings: &mut [MappedDevice], addr: u64) -> Option<(&mut MappedDevice, u32)> {
+    mappings.iter_mut().find_map(|mapping| {
+        let size = mapping.device.size() as u64;
+        if addr >= mapping.base_addr && addr < mapping.base_addr + size {
+            let offset = (addr - mapping.base_addr) as u32;
+            Some((mapping, offset))
+        } else {
+            None
+        }
+    })
+}
+
+/// The bus as seen by a device doing DMA: every mapping except the device
+/// itself. A transfer must stay within a single device.
+pub struct DmaView<'a> {
+    before: &'a mut [MappedDevice],
+    after: &'a mut [MappedDevice],
+}
+
+impl DmaView<'_> {
+    fn probe(&mut self, addr: u64, len: usize) -> Option<(&mut MappedDevice, u32)> {
+        let (mapping, offset) = match find_mapping(self.before, addr) {
+            Some(found) => found,
+            None => find_mapping(self.after, addr)?,
+        };
+        let fits = offset as u64 + len as u64 <= mapping.device.size() as u64;
+        fits.then_some((mapping, offset))
+    }
+
+    pub fn read(&mut self, addr: u64, buf: &mut [u8]) -> Result<(), BusError> {
+        match self.probe(addr, buf.len()) {
+            Some((mapping, offset)) => mapping
+                .device
+                .read_bytes(offset, buf)
+                .map_err(|_| BusError::LoadAccessFault(addr)),
+            None => Err(BusError::LoadAccessFault(addr)),
+        }
+    }
+
+    pub fn write(&mut self, addr: u64, data: &[u8]) -> Result<(), BusError> {
+        match self.probe(addr, data.len()) {
+            Some((mapping, offset)) => mapping
+                .device
+                .write_bytes(offset, data)
+                .map_err(|_| BusError::StoreAccessFault(addr)),
+            None => Err(BusError::StoreAccessFault(addr)),
+        }
+    }
+}
+
 pub struct Bus {
     mappings: Vec<MappedDevice>,
 }
@@ -37,43 +123,107 @@ impl Bus {
         }
     }
 
-    pub fn map_to(&mut self, base_addr: u32, device: Box<dyn Device>) {
+    pub fn map_to(&mut self, base_addr: u64, device: Box<dyn Device>) {
         self.mappings.push(MappedDevice { base_addr, device });
     }
 
-    pub fn load(&mut self, addr: u32, size: u8) -> Result<u32, BusError> {
+    /// Loads `size` bytes. Devices are at most 32 bits wide, so doubleword
+    /// accesses are split into two little-endian word accesses.
+    pub fn load(&mut self, addr: u64, size: u8) -> Result<u64, BusError> {
+        if size == 8 {
+            let lo = self.load(addr, 4)?;
+            let hi = self.load(addr.wrapping_add(4), 4)?;
+            return Ok((hi << 32) | lo);
+        }
+
         match self.probe(addr) {
-            Ok(mapping) => mapping.device.load(addr - mapping.base_addr, size),
+            Ok((mapping, offset)) => mapping
+                .device
+                .load(offset, size)
+                .map(|v| v as u64)
+                .map_err(|_| BusError::LoadAccessFault(addr)),
             Err(()) => Err(BusError::LoadAccessFault(addr)),
         }
     }
 
-    pub fn store(&mut self, addr: u32, size: u8, val: u32) -> Result<(), BusError> {
+    pub fn store(&mut self, addr: u64, size: u8, val: u64) -> Result<(), BusError> {
+        if size == 8 {
+            self.store(addr, 4, val & 0xffff_ffff)?;
+            return self.store(addr.wrapping_add(4), 4, val >> 32);
+        }
+
         match self.probe(addr) {
-            Ok(mapping) => mapping.device.store(addr - mapping.base_addr, size, val),
+            Ok((mapping, offset)) => mapping
+                .device
+                .store(offset, size, val as u32)
+                .map_err(|_| BusError::StoreAccessFault(addr)),
             Err(()) => Err(BusError::StoreAccessFault(addr)),
         }
     }
 
-    pub fn write_bytes(&mut self, addr: u32, data: &[u8]) -> Result<(), BusError> {
+    pub fn reset(&mut self) {
+        for mapping in &mut self.mappings {
+            mapping.device.reset();
+        }
+    }
+
+    /// Syncs every device, reporting the first failure; the rest are
+    /// still synced.
+    pub fn sync(&mut self) -> io::Result<()> {
+        let mut result = Ok(());
+        for mapping in &mut self.mappings {
+            result = result.and(mapping.device.sync());
+        }
+        result
+    }
+
+    /// Base address, size and description of every device that has one.
+    pub fn fdt_nodes(&self) -> impl Iterator<Item = (u64, u32, FdtNode)> + '_ {
+        self.mappings.iter().filter_map(|mapping| {
+            let node = mapping.device.fdt_node()?;
+            Some((mapping.base_addr, mapping.device.size(), node))
+        })
+    }
+
+    pub fn tick(&mut self, now: u64) {
+        for i in 0..self.mappings.len() {
+            let (before, rest) = self.mappings.split_at_mut(i);
+            let (current, after) = rest.split_first_mut().unwrap();
+            current.device.tick(now, &mut DmaView { before, after });
+        }
+    }
+
+    /// Wired-OR of all device interrupt lines; there is no interrupt
+    /// controller, so this drives the machine external interrupt directly.
+    pub fn irq_pending(&mut self) -> bool {
+        self.mappings.iter_mut().fold(false, |pending, mapping| {
+            mapping.device.irq_pending() | pending
+        })
+    }
+
+    pub fn write_bytes(&mut self, addr: u64, data: &[u8]) -> Result<(), BusError> {
         match self.probe(addr) {
-            Ok(mapping) => mapping.device.write_bytes(addr - mapping.base_addr, data),
+            Ok((mapping, offset)) => mapping
+                .device
+                .write_bytes(offset, data)
+                .map_err(|_| BusError::StoreAccessFault(addr)),
             Err(()) => Err(BusError::StoreAccessFault(addr)),
         }
     }
 
-    pub fn dump(&mut self, addr: u32, len: u32) -> Result<Vec<u8>, BusError> {
+    pub fn dump(&mut self, addr: u64, len: u64) -> Result<Vec<u8>, BusError> {
         (0..len)
             .map(|off| self.load(addr.wrapping_add(off), 1).map(|v| v as u8))
             .collect()
     }
 
-    fn probe(&mut self, addr: u32) -> Result<&mut MappedDevice, ()> {
-        for mapping in &mut self.mappings {
-            if addr >= mapping.base_addr && addr < mapping.base_addr + mapping.device.size() {
-                return Ok(mapping);
-            }
-        }
-        Err(())
+    fn probe(&mut self, addr: u64) -> Result<(&mut MappedDevice, u32), ()> {
+        find_mapping(&mut self.mappings, addr).ok_or(())
+    }
+}
+
+impl Default for Bus {
+    fn default() -> Self {
+        Self::new()
     }
 }
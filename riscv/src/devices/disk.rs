@@ -1,51 +1,203 @@
-use crate::devices::{BusError, Device};
-use std::fs::File;
-use std::io::{Read, Seek, SeekFrom, Write};
-
-const SECTOR_SIZE: usize = 512;
+use crate::devices::{BusError, Device, DiskImage, DmaView, FdtNode, SECTOR_SIZE};
+use std::io;
 
 const REG_CTRL: u32 = 0x00; // Write: 1 = load sector into buffer, 2 = flush buffer to disk
 const REG_SECTOR: u32 = 0x04; // R/W: LBA sector index to operate on
 const REG_DATA: u32 = 0x08; // R/W: sequential data port (auto-advances each access)
-const REG_STATUS: u32 = 0x0C; // Read: 0 = ready (extend for busy/error flags later)
+const REG_STATUS: u32 = 0x0C; // Read: STATUS_* bits of the last command
+const REG_ERROR: u32 = 0x10; // Read: ERR_* code of the last command
+const REG_SECTOR_COUNT: u32 = 0x14; // Read: disk capacity in sectors
+const REG_DMA_ADDR_LO: u32 = 0x18; // R/W: physical buffer address for DMA, low word
+const REG_DMA_ADDR_HI: u32 = 0x1C; // R/W: physical buffer address for DMA, high word
+const REG_DMA_COUNT: u32 = 0x20; // R/W: number of sectors to transfer, starting at REG_SECTOR
+const REG_INT_ENABLE: u32 = 0x24; // R/W: 1 = raise an interrupt on DMA completion
+const REG_INT_STATUS: u32 = 0x28; // Read: 1 = DMA completed, Write: 1 to acknowledge
+
+const CMD_READ: u32 = 1;
+const CMD_WRITE: u32 = 2;
+const CMD_DMA_READ: u32 = 3; // disk -> memory
+const CMD_DMA_WRITE: u32 = 4; // memory -> disk
+
+pub const STATUS_BUSY: u32 = 1 << 0; // command in progress
+pub const STATUS_ERROR: u32 = 1 << 1; // last command failed, see REG_ERROR
+pub const STATUS_OUT_OF_RANGE: u32 = 1 << 2; // sector >= sector count
+pub const STATUS_SHORT_READ: u32 = 1 << 3; // sector extends past end of file, rest is zero
+
+pub const ERR_NONE: u32 = 0;
+pub const ERR_IO: u32 = 1; // host read/write/seek failed
+pub const ERR_OUT_OF_RANGE: u32 = 2;
+pub const ERR_SHORT_READ: u32 = 3;
+pub const ERR_BAD_COMMAND: u32 = 4;
+pub const ERR_DMA: u32 = 5; // buffer not backed by a device
+pub const ERR_READ_ONLY: u32 = 6; // write to a read-only image
+
+const INT_COMPLETE: u32 = 1 << 0;
+
+struct DmaRequest {
+    to_memory: bool,
+    addr: u64,
+    sector: u32,
+    count: u32,
+    /// Sectors transferred so far.
+    done: u32,
+    /// Cycle at which the next sector moves, fixed on the first tick.
+    due: Option<u64>,
+}
 
 pub struct Disk {
-    file: File,
+    image: DiskImage,
     sector: u32,
+    sector_count: u32,
     buffer: [u8; SECTOR_SIZE],
     data_ptr: usize,
+    status: u32,
+    error: u32,
+    dma_addr: u64,
+    dma_count: u32,
+    dma_latency: u64,
+    dma: Option<DmaRequest>,
+    int_enable: u32,
+    int_status: u32,
 }
 
 impl Disk {
     pub fn new(path: &str) -> std::io::Result<Self> {
-        let file = File::options()
-            .read(true)
-            .write(true)
-            .create(true)
-            .open(path)?;
-
-        Ok(Self {
-            file,
+        Ok(Self::with_image(DiskImage::open(path)?))
+    }
+
+    /// A disk on an image that may be shared with other devices or be a
+    /// snapshot.
+    pub fn with_image(image: DiskImage) -> Self {
+        // a trailing partial sector is addressable and reads short
+        let sector_count = image
+            .len()
+            .div_ceil(SECTOR_SIZE as u64)
+            .min(u32::MAX as u64) as u32;
+
+        Self {
+            image,
             sector: 0,
+            sector_count,
             buffer: [0; SECTOR_SIZE],
             data_ptr: 0,
-        })
+            status: 0,
+            error: ERR_NONE,
+            dma_addr: 0,
+            dma_count: 0,
+            dma_latency: 0,
+            dma: None,
+            int_enable: 0,
+            int_status: 0,
+        }
+    }
+
+    /// Spends `cycles` on each sector of a DMA transfer, so the guest sees
+    /// it busy for a while and the buffer fill up a sector at a time.
+    /// Without a latency the whole transfer happens on one tick.
+    pub fn with_dma_latency(mut self, cycles: u64) -> Self {
+        self.dma_latency = cycles;
+        self
+    }
+
+    fn fail(&mut self, status: u32, error: u32) {
+        self.status |= STATUS_ERROR | status;
+        self.error = error;
+    }
+
+    /// Checks the sector and resets the status for a new command.
+    fn begin_command(&mut self) -> bool {
+        self.status = 0;
+        self.error = ERR_NONE;
+        self.data_ptr = 0;
+
+        if self.sector >= self.sector_count {
+            self.fail(STATUS_OUT_OF_RANGE, ERR_OUT_OF_RANGE);
+            return false;
+        }
+        true
     }
 
     fn read(&mut self) {
-        let offset = self.sector as u64 * SECTOR_SIZE as u64;
-        if self.file.seek(SeekFrom::Start(offset)).is_ok() {
-            let _ = self.file.read(&mut self.buffer);
+        // never hand out a previous sector's data
+        self.buffer.fill(0);
+        if !self.begin_command() {
+            return;
+        }
+
+        self.fill_buffer(self.sector);
+    }
+
+    fn fill_buffer(&mut self, sector: u32) {
+        match self.read_sector(sector) {
+            Ok(len) if len < SECTOR_SIZE => self.fail(STATUS_SHORT_READ, ERR_SHORT_READ),
+            Ok(_) => {}
+            Err(_) => self.fail(0, ERR_IO),
         }
-        self.data_ptr = 0;
+    }
+
+    /// Reads as much of `sector` as the file holds.
+    fn read_sector(&mut self, sector: u32) -> io::Result<usize> {
+        let offset = sector as u64 * SECTOR_SIZE as u64;
+        self.image.read_at(offset, &mut self.buffer)
     }
 
     fn write(&mut self) {
-        let offset = self.sector as u64 * SECTOR_SIZE as u64;
-        if self.file.seek(SeekFrom::Start(offset)).is_ok() {
-            let _ = self.file.write_all(&self.buffer);
+        if !self.begin_command() {
+            return;
         }
-        self.data_ptr = 0;
+
+        self.flush_buffer(self.sector);
+    }
+
+    fn flush_buffer(&mut self, sector: u32) {
+        let offset = sector as u64 * SECTOR_SIZE as u64;
+        match self.image.write_at(offset, &self.buffer) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::ReadOnlyFilesystem => self.fail(0, ERR_READ_ONLY),
+            Err(_) => self.fail(0, ERR_IO),
+        }
+    }
+
+    fn start_dma(&mut self, to_memory: bool) {
+        if !self.begin_command() {
+            return;
+        }
+        if self.sector as u64 + self.dma_count as u64 > self.sector_count as u64 {
+            self.fail(STATUS_OUT_OF_RANGE, ERR_OUT_OF_RANGE);
+            return;
+        }
+
+        self.status = STATUS_BUSY;
+        self.dma = Some(DmaRequest {
+            to_memory,
+            addr: self.dma_addr,
+            sector: self.sector,
+            count: self.dma_count,
+            done: 0,
+            due: None,
+        });
+    }
+
+    /// Moves sector `i` of the transfer. Returns false if the transfer has
+    /// to stop.
+    fn transfer_sector(&mut self, req: &DmaRequest, i: u32, dma: &mut DmaView) -> bool {
+        let sector = req.sector + i;
+        let addr = req.addr + i as u64 * SECTOR_SIZE as u64;
+
+        if req.to_memory {
+            self.buffer.fill(0);
+            self.fill_buffer(sector);
+            if dma.write(addr, &self.buffer).is_err() {
+                self.fail(0, ERR_DMA);
+            }
+        } else if dma.read(addr, &mut self.buffer).is_ok() {
+            self.flush_buffer(sector);
+        } else {
+            self.fail(0, ERR_DMA);
+        }
+
+        // a short read only matters for the last sector, so keep going
+        self.error == ERR_NONE || self.error == ERR_SHORT_READ
     }
 }
 
@@ -54,10 +206,21 @@ impl Device for Disk {
         "Disk"
     }
 
+    fn fdt_node(&self) -> Option<FdtNode> {
+        Some(FdtNode::new("disk", &["osv,disk"]).interrupts())
+    }
+
     fn load(&mut self, addr: u32, _size: u8) -> Result<u32, BusError> {
         match addr {
             REG_SECTOR => Ok(self.sector),
-            REG_STATUS => Ok(0), // always ready for simplicity,
+            REG_STATUS => Ok(self.status),
+            REG_ERROR => Ok(self.error),
+            REG_SECTOR_COUNT => Ok(self.sector_count),
+            REG_DMA_ADDR_LO => Ok(self.dma_addr as u32),
+            REG_DMA_ADDR_HI => Ok((self.dma_addr >> 32) as u32),
+            REG_DMA_COUNT => Ok(self.dma_count),
+            REG_INT_ENABLE => Ok(self.int_enable),
+            REG_INT_STATUS => Ok(self.int_status),
             REG_DATA => {
                 if self.data_ptr < SECTOR_SIZE {
                     let val = self.buffer[self.data_ptr] as u32;
@@ -67,17 +230,24 @@ impl Device for Disk {
                     Ok(0)
                 }
             }
-            _ => Err(BusError::LoadAccessFault(addr)),
+            _ => Err(BusError::LoadAccessFault(addr as u64)),
         }
     }
 
     fn store(&mut self, addr: u32, _size: u8, val: u32) -> Result<(), BusError> {
         match addr {
+            // commands are ignored while a transfer is in flight
+            REG_CTRL if self.dma.is_some() => Ok(()),
             REG_CTRL => {
                 match val {
-                    1 => self.read(),
-                    2 => self.write(),
-                    _ => {}
+                    CMD_READ => self.read(),
+                    CMD_WRITE => self.write(),
+                    CMD_DMA_READ => self.start_dma(true),
+                    CMD_DMA_WRITE => self.start_dma(false),
+                    _ => {
+                        self.status = 0;
+                        self.fail(0, ERR_BAD_COMMAND);
+                    }
                 }
                 Ok(())
             }
@@ -86,6 +256,26 @@ impl Device for Disk {
                 self.data_ptr = 0;
                 Ok(())
             }
+            REG_DMA_ADDR_LO => {
+                self.dma_addr = (self.dma_addr & !0xffff_ffff) | val as u64;
+                Ok(())
+            }
+            REG_DMA_ADDR_HI => {
+                self.dma_addr = (self.dma_addr & 0xffff_ffff) | (val as u64) << 32;
+                Ok(())
+            }
+            REG_DMA_COUNT => {
+                self.dma_count = val;
+                Ok(())
+            }
+            REG_INT_ENABLE => {
+                self.int_enable = val & INT_COMPLETE;
+                Ok(())
+            }
+            REG_INT_STATUS => {
+                self.int_status &= !val;
+                Ok(())
+            }
             REG_DATA => {
                 if self.data_ptr < SECTOR_SIZE {
                     self.buffer[self.data_ptr] = val as u8;
@@ -93,11 +283,53 @@ impl Device for Disk {
                 }
                 Ok(())
             }
-            _ => Err(BusError::StoreAccessFault(addr)),
+            _ => Err(BusError::StoreAccessFault(addr as u64)),
         }
     }
 
     fn size(&self) -> u32 {
         0x1000
     }
+
+    fn irq_pending(&mut self) -> bool {
+        self.int_enable & self.int_status != 0
+    }
+
+    /// Cancels a transfer in flight; the image is left as it is.
+    fn reset(&mut self) {
+        self.sector = 0;
+        self.buffer.fill(0);
+        self.data_ptr = 0;
+        self.status = 0;
+        self.error = ERR_NONE;
+        self.dma_addr = 0;
+        self.dma_count = 0;
+        self.dma = None;
+        self.int_enable = 0;
+        self.int_status = 0;
+    }
+
+    fn tick(&mut self, now: u64, dma: &mut DmaView) {
+        let Some(mut req) = self.dma.take() else {
+            return;
+        };
+
+        let mut due = *req.due.get_or_insert(now + self.dma_latency);
+        while now >= due && req.done < req.count {
+            if !self.transfer_sector(&req, req.done, dma) {
+                req.done = req.count;
+                break;
+            }
+            req.done += 1;
+            due = now + self.dma_latency;
+        }
+
+        if req.done < req.count {
+            req.due = Some(due);
+            self.dma = Some(req);
+        } else {
+            self.status &= !STATUS_BUSY;
+            self.int_status |= INT_COMPLETE;
+        }
+    }
 }
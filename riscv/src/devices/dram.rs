@@ -1,13 +1,168 @@
-use crate::devices::{BusError, Device};
+use std::fs::File;
+use std::io;
+use std::ops::{Deref, DerefMut};
+use std::os::fd::AsRawFd;
+use std::path::Path;
+use std::ptr::NonNull;
+
+use crate::devices::{BusError, Device, FdtNode};
+
+/// A shared mapping of a whole file.
+struct Mmap {
+    ptr: NonNull<u8>,
+    len: usize,
+    _file: File,
+}
+
+impl Mmap {
+    fn new(file: File, len: usize, writable: bool) -> io::Result<Self> {
+        if len == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "cannot map an empty file",
+            ));
+        }
+
+        let prot = match writable {
+            true => libc::PROT_READ | libc::PROT_WRITE,
+            false => libc::PROT_READ,
+        };
+        // SAFETY: a fresh mapping at an address of the kernel's choosing,
+        // checked for failure before use.
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                prot,
+                libc::MAP_SHARED,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Self {
+            ptr: NonNull::new(ptr as *mut u8).unwrap(),
+            len,
+            _file: file,
+        })
+    }
+
+    fn sync(&self) -> io::Result<()> {
+        // SAFETY: the range is exactly the mapping created in `new`
+        if unsafe { libc::msync(self.ptr.as_ptr().cast(), self.len, libc::MS_SYNC) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+impl Deref for Mmap {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // SAFETY: the mapping is valid for `len` bytes until dropped. Other
+        // processes writing the file race with the guest like a second bus
+        // master would; that is accepted.
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl DerefMut for Mmap {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        // SAFETY: see `deref`; read-only mappings are never written because
+        // `Dram` rejects stores to them.
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        // SAFETY: unmaps what `new` mapped; no slices outlive `self`
+        unsafe {
+            libc::munmap(self.ptr.as_ptr().cast(), self.len);
+        }
+    }
+}
+
+enum Memory {
+    Heap(Vec<u8>),
+    Mapped(Mmap),
+}
+
+impl Deref for Memory {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Memory::Heap(mem) => mem,
+            Memory::Mapped(mem) => mem,
+        }
+    }
+}
+
+impl DerefMut for Memory {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        match self {
+            Memory::Heap(mem) => mem,
+            Memory::Mapped(mem) => mem,
+        }
+    }
+}
 
 pub struct Dram {
-    mem: Vec<u8>,
+    mem: Memory,
+    read_only: bool,
 }
 
 impl Dram {
     pub fn new(size: u32) -> Self {
         Self {
-            mem: vec![0; size as usize], // TODO: btreemap
+            mem: Memory::Heap(vec![0; size as usize]),
+            read_only: false,
+        }
+    }
+
+    /// RAM backed by `path` through a shared mapping, so its contents
+    /// survive the emulator and can be inspected afterwards. The file is
+    /// created or grown to `size` bytes.
+    pub fn mapped(path: impl AsRef<Path>, size: u32) -> io::Result<Self> {
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        if file.metadata()?.len() < size as u64 {
+            file.set_len(size as u64)?;
+        }
+
+        Ok(Self {
+            mem: Memory::Mapped(Mmap::new(file, size as usize, true)?),
+            read_only: false,
+        })
+    }
+
+    /// A ROM with the contents of `path`. Guest stores fault; the file is
+    /// never written.
+    pub fn rom(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let len = u32::try_from(file.metadata()?.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "ROM image too large"))?;
+
+        Ok(Self {
+            mem: Memory::Mapped(Mmap::new(file, len as usize, false)?),
+            read_only: true,
+        })
+    }
+
+    /// A ROM holding `data`, e.g. generated boot code.
+    pub fn rom_from_bytes(data: Vec<u8>) -> Self {
+        Self {
+            mem: Memory::Heap(data),
+            read_only: true,
         }
     }
 
@@ -15,7 +170,7 @@ impl Dram {
         let start = addr as usize;
         let end = start + data.len();
 
-        if end > self.mem.len() {
+        if end > self.mem.len() || self.read_only {
             return Err(());
         }
 
@@ -26,7 +181,15 @@ impl Dram {
 
 impl Device for Dram {
     fn name(&self) -> &str {
-        "DRAM"
+        match self.read_only {
+            true => "ROM",
+            false => "DRAM",
+        }
+    }
+
+    /// The ROM only holds boot code, there is nothing for the guest to find.
+    fn fdt_node(&self) -> Option<FdtNode> {
+        (!self.read_only).then(FdtNode::memory)
     }
 
     fn load(&mut self, addr: u32, size: u8) -> Result<u32, BusError> {
@@ -34,7 +197,7 @@ impl Device for Dram {
         let len = size as usize;
 
         if start + len > self.mem.len() {
-            return Err(BusError::LoadAccessFault(addr));
+            return Err(BusError::LoadAccessFault(addr as u64));
         }
 
         let slice = &self.mem[start..start + len];
@@ -43,7 +206,7 @@ impl Device for Dram {
             1 => slice[0] as u32,
             2 => u16::from_le_bytes(slice.try_into().unwrap()) as u32,
             4 => u32::from_le_bytes(slice.try_into().unwrap()),
-            _ => return Err(BusError::LoadAccessFault(addr)),
+            _ => return Err(BusError::LoadAccessFault(addr as u64)),
         };
 
         Ok(val)
@@ -53,8 +216,8 @@ impl Device for Dram {
         let start = addr as usize;
         let len = size as usize;
 
-        if start + len > self.mem.len() {
-            return Err(BusError::StoreAccessFault(addr));
+        if start + len > self.mem.len() || self.read_only {
+            return Err(BusError::StoreAccessFault(addr as u64));
         }
 
         let bytes = val.to_le_bytes();
@@ -64,7 +227,36 @@ impl Device for Dram {
         Ok(())
     }
 
+    fn write_bytes(&mut self, addr: u32, data: &[u8]) -> Result<(), BusError> {
+        let start = addr as usize;
+        if self.read_only {
+            return Err(BusError::StoreAccessFault(addr as u64));
+        }
+        let Some(dest) = self.mem.get_mut(start..start + data.len()) else {
+            return Err(BusError::StoreAccessFault(addr as u64));
+        };
+        dest.copy_from_slice(data);
+        Ok(())
+    }
+
+    fn read_bytes(&mut self, addr: u32, buf: &mut [u8]) -> Result<(), BusError> {
+        let start = addr as usize;
+        let Some(src) = self.mem.get(start..start + buf.len()) else {
+            return Err(BusError::LoadAccessFault(addr as u64));
+        };
+        buf.copy_from_slice(src);
+        Ok(())
+    }
+
     fn size(&self) -> u32 {
         self.mem.len() as u32
     }
+
+    /// Writes a mapped RAM back to its file; a no-op for heap memory.
+    fn sync(&mut self) -> io::Result<()> {
+        match &self.mem {
+            Memory::Mapped(mem) if !self.read_only => mem.sync(),
+            _ => Ok(()),
+        }
+    }
 }
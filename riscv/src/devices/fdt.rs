@@ -0,0 +1,323 @@
+//! Flattened device tree (DTB) generation, so the guest can discover the
+//! machine instead of assuming a layout.
+
+use std::collections::HashMap;
+
+use crate::devices::Bus;
+
+const FDT_MAGIC: u32 = 0xd00d_feed;
+const FDT_VERSION: u32 = 17;
+const FDT_LAST_COMPATIBLE_VERSION: u32 = 16;
+const FDT_HEADER_SIZE: usize = 40;
+/// An empty memory reservation map: just the terminating entry.
+const FDT_RESERVE_MAP_SIZE: usize = 16;
+
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_END: u32 = 9;
+
+/// Writes the structure and strings blocks of a DTB node by node.
+#[derive(Default)]
+pub struct FdtWriter {
+    structure: Vec<u8>,
+    strings: Vec<u8>,
+    string_offsets: HashMap<String, u32>,
+    depth: usize,
+}
+
+impl FdtWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn push_u32(&mut self, val: u32) {
+        self.structure.extend_from_slice(&val.to_be_bytes());
+    }
+
+    /// Tokens are 32-bit aligned.
+    fn align(&mut self) {
+        let len = self.structure.len().next_multiple_of(4);
+        self.structure.resize(len, 0);
+    }
+
+    fn string_offset(&mut self, name: &str) -> u32 {
+        if let Some(&offset) = self.string_offsets.get(name) {
+            return offset;
+        }
+        let offset = self.strings.len() as u32;
+        self.strings.extend_from_slice(name.as_bytes());
+        self.strings.push(0);
+        self.string_offsets.insert(name.to_string(), offset);
+        offset
+    }
+
+    /// The root node has an empty name.
+    pub fn begin_node(&mut self, name: &str) {
+        self.push_u32(FDT_BEGIN_NODE);
+        self.structure.extend_from_slice(name.as_bytes());
+        self.structure.push(0);
+        self.align();
+        self.depth += 1;
+    }
+
+    pub fn end_node(&mut self) {
+        assert!(self.depth > 0, "unbalanced device tree node");
+        self.push_u32(FDT_END_NODE);
+        self.depth -= 1;
+    }
+
+    pub fn property(&mut self, name: &str, value: &[u8]) {
+        let name = self.string_offset(name);
+        self.push_u32(FDT_PROP);
+        self.push_u32(value.len() as u32);
+        self.push_u32(name);
+        self.structure.extend_from_slice(value);
+        self.align();
+    }
+
+    pub fn property_empty(&mut self, name: &str) {
+        self.property(name, &[]);
+    }
+
+    pub fn property_u32(&mut self, name: &str, val: u32) {
+        self.property(name, &val.to_be_bytes());
+    }
+
+    pub fn property_cells(&mut self, name: &str, cells: &[u32]) {
+        let value: Vec<u8> = cells.iter().flat_map(|cell| cell.to_be_bytes()).collect();
+        self.property(name, &value);
+    }
+
+    pub fn property_string(&mut self, name: &str, val: &str) {
+        self.property_strings(name, &[val]);
+    }
+
+    /// A string list, e.g. for `compatible`.
+    pub fn property_strings(&mut self, name: &str, vals: &[&str]) {
+        let mut value = Vec::new();
+        for val in vals {
+            value.extend_from_slice(val.as_bytes());
+            value.push(0);
+        }
+        self.property(name, &value);
+    }
+
+    /// A 64-bit address and size, for nodes under #address-cells = 2 and
+    /// #size-cells = 2.
+    pub fn property_reg(&mut self, addr: u64, size: u64) {
+        let cells = [
+            (addr >> 32) as u32,
+            addr as u32,
+            (size >> 32) as u32,
+            size as u32,
+        ];
+        self.property_cells("reg", &cells);
+    }
+
+    /// Assembles the blob.
+    pub fn finish(mut self) -> Vec<u8> {
+        assert_eq!(self.depth, 0, "unbalanced device tree node");
+        self.push_u32(FDT_END);
+
+        let off_mem_rsvmap = FDT_HEADER_SIZE;
+        let off_dt_struct = off_mem_rsvmap + FDT_RESERVE_MAP_SIZE;
+        let off_dt_strings = off_dt_struct + self.structure.len();
+        let total_size = off_dt_strings + self.strings.len();
+
+        let header = [
+            FDT_MAGIC,
+            total_size as u32,
+            off_dt_struct as u32,
+            off_dt_strings as u32,
+            off_mem_rsvmap as u32,
+            FDT_VERSION,
+            FDT_LAST_COMPATIBLE_VERSION,
+            0, // boot_cpuid_phys
+            self.strings.len() as u32,
+            self.structure.len() as u32,
+        ];
+
+        let mut blob = Vec::with_capacity(total_size);
+        blob.extend(header.iter().flat_map(|word| word.to_be_bytes()));
+        blob.resize(off_dt_struct, 0);
+        blob.extend_from_slice(&self.structure);
+        blob.extend_from_slice(&self.strings);
+        blob
+    }
+}
+
+/// Phandle of the hart's local interrupt controller.
+const INTC_PHANDLE: u32 = 1;
+
+/// Machine external interrupt: the bus drives it directly, there is no
+/// PLIC in between.
+const IRQ_MACHINE_EXTERNAL: u32 = 11;
+
+pub enum FdtValue {
+    U32(u32),
+    String(String),
+}
+
+/// How a device shows up in the device tree.
+pub struct FdtNode {
+    name: &'static str,
+    compatible: Vec<&'static str>,
+    device_type: Option<&'static str>,
+    /// Offset and length within the mapping; the whole mapping by default.
+    reg: Option<(u32, u32)>,
+    interrupts: bool,
+    props: Vec<(&'static str, FdtValue)>,
+}
+
+impl FdtNode {
+    pub fn new(name: &'static str, compatible: &[&'static str]) -> Self {
+        Self {
+            name,
+            compatible: compatible.to_vec(),
+            device_type: None,
+            reg: None,
+            interrupts: false,
+            props: Vec::new(),
+        }
+    }
+
+    /// A `memory` node, which sits at the root instead of under `/soc`.
+    pub fn memory() -> Self {
+        let mut node = Self::new("memory", &[]);
+        node.device_type = Some("memory");
+        node
+    }
+
+    pub fn device_type(mut self, device_type: &'static str) -> Self {
+        self.device_type = Some(device_type);
+        self
+    }
+
+    pub fn reg(mut self, offset: u32, len: u32) -> Self {
+        self.reg = Some((offset, len));
+        self
+    }
+
+    /// The device raises the machine external interrupt.
+    pub fn interrupts(mut self) -> Self {
+        self.interrupts = true;
+        self
+    }
+
+    pub fn u32(mut self, name: &'static str, val: u32) -> Self {
+        self.props.push((name, FdtValue::U32(val)));
+        self
+    }
+
+    pub fn string(mut self, name: &'static str, val: impl Into<String>) -> Self {
+        self.props.push((name, FdtValue::String(val.into())));
+        self
+    }
+
+    fn write(&self, fdt: &mut FdtWriter, base: u64, size: u32) {
+        let (offset, len) = self.reg.unwrap_or((0, size));
+        let addr = base + offset as u64;
+        fdt.begin_node(&format!("{}@{:x}", self.name, addr));
+        if let Some(device_type) = self.device_type {
+            fdt.property_string("device_type", device_type);
+        }
+        if !self.compatible.is_empty() {
+            fdt.property_strings("compatible", &self.compatible);
+        }
+        fdt.property_reg(addr, len as u64);
+        if self.interrupts {
+            fdt.property_cells("interrupts-extended", &[INTC_PHANDLE, IRQ_MACHINE_EXTERNAL]);
+        }
+        for (name, val) in &self.props {
+            match val {
+                FdtValue::U32(val) => fdt.property_u32(name, *val),
+                FdtValue::String(val) => fdt.property_string(name, val),
+            }
+        }
+        fdt.end_node();
+    }
+}
+
+/// What the device tree says about the hart.
+pub struct FdtCpu<'a> {
+    /// `riscv,isa`, e.g. `rv64i_zicsr_zve64x`.
+    pub isa: &'a str,
+    /// `mmu-type`, e.g. `riscv,sv39`.
+    pub mmu_type: &'a str,
+    /// Frequency of `time`, in Hz.
+    pub timebase_frequency: u32,
+}
+
+/// Builds a DTB for a single hart and whatever devices are on `bus` that
+/// describe themselves. Memory goes at the root, everything else under a
+/// `simple-bus` with identity `ranges`. `/chosen` points stdout at the
+/// first serial port.
+///
+/// There are no CLINT or PLIC nodes because the machine has neither: no
+/// device raises timer or software interrupts, and device interrupt lines
+/// are wired-OR onto the machine external interrupt. Each device's
+/// `interrupts-extended` therefore points straight at the cpu-intc. Adding
+/// nodes without the hardware behind them would send the kernel after
+/// registers that fault.
+pub fn machine_fdt(bus: &Bus, cpu: &FdtCpu) -> Vec<u8> {
+    let mut nodes: Vec<_> = bus.fdt_nodes().collect();
+    nodes.sort_by_key(|(base, _, node)| base + node.reg.map_or(0, |(offset, _)| offset as u64));
+
+    let mut fdt = FdtWriter::new();
+    fdt.begin_node("");
+    fdt.property_u32("#address-cells", 2);
+    fdt.property_u32("#size-cells", 2);
+    fdt.property_string("compatible", "osv,riscv");
+    fdt.property_string("model", "osv RISC-V emulator");
+
+    let stdout = nodes
+        .iter()
+        .find(|(_, _, node)| node.device_type == Some("serial"))
+        .map(|(base, _, node)| format!("/soc/{}@{:x}", node.name, base));
+    fdt.begin_node("chosen");
+    if let Some(stdout) = &stdout {
+        fdt.property_string("stdout-path", stdout);
+    }
+    fdt.end_node();
+
+    fdt.begin_node("cpus");
+    fdt.property_u32("#address-cells", 1);
+    fdt.property_u32("#size-cells", 0);
+    fdt.property_u32("timebase-frequency", cpu.timebase_frequency);
+    fdt.begin_node("cpu@0");
+    fdt.property_string("device_type", "cpu");
+    fdt.property_u32("reg", 0);
+    fdt.property_string("status", "okay");
+    fdt.property_string("compatible", "riscv");
+    fdt.property_string("riscv,isa", cpu.isa);
+    fdt.property_string("mmu-type", cpu.mmu_type);
+    fdt.begin_node("interrupt-controller");
+    fdt.property_u32("#interrupt-cells", 1);
+    fdt.property_empty("interrupt-controller");
+    fdt.property_string("compatible", "riscv,cpu-intc");
+    fdt.property_u32("phandle", INTC_PHANDLE);
+    fdt.end_node();
+    fdt.end_node();
+    fdt.end_node();
+
+    let (memory, devices): (Vec<_>, Vec<_>) = nodes
+        .iter()
+        .partition(|(_, _, node)| node.device_type == Some("memory"));
+    for (base, size, node) in memory {
+        node.write(&mut fdt, *base, *size);
+    }
+
+    fdt.begin_node("soc");
+    fdt.property_u32("#address-cells", 2);
+    fdt.property_u32("#size-cells", 2);
+    fdt.property_string("compatible", "simple-bus");
+    fdt.property_empty("ranges");
+    for (base, size, node) in devices {
+        node.write(&mut fdt, *base, *size);
+    }
+    fdt.end_node();
+
+    fdt.end_node();
+    fdt.finish()
+}
@@ -0,0 +1,269 @@
+//! Linear framebuffer for headless graphics. The guest draws into pixel
+//! memory and presents frames; the host writes them out as PNG or PPM.
+
+pub mod encode;
+
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use crate::devices::{BusError, Device, FdtNode};
+
+const REG_WIDTH: u32 = 0x00; // Read: visible width in pixels
+const REG_HEIGHT: u32 = 0x04; // Read: visible height in pixels
+const REG_STRIDE: u32 = 0x08; // Read: bytes per row
+const REG_FORMAT: u32 = 0x0C; // Read: PixelFormat code
+const REG_FRAME: u32 = 0x10; // Read: frames presented so far
+const REG_CTRL: u32 = 0x14; // Write: CMD_* command
+
+const CMD_PRESENT: u32 = 1; // the current frame is complete
+const CMD_DUMP: u32 = 2; // write the current frame to a numbered file
+
+/// Pixel memory starts one page in, after the registers.
+pub const PIXEL_OFFSET: u32 = 0x1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelFormat {
+    /// 32 bits per pixel, little endian, top byte unused.
+    Xrgb8888,
+    /// 16 bits per pixel, little endian.
+    Rgb565,
+}
+
+impl PixelFormat {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "xrgb8888" => Some(Self::Xrgb8888),
+            "rgb565" => Some(Self::Rgb565),
+            _ => None,
+        }
+    }
+
+    pub fn bytes_per_pixel(self) -> u32 {
+        match self {
+            Self::Xrgb8888 => 4,
+            Self::Rgb565 => 2,
+        }
+    }
+
+    fn code(self) -> u32 {
+        match self {
+            Self::Xrgb8888 => 0,
+            Self::Rgb565 => 1,
+        }
+    }
+
+    fn to_rgb(self, pixel: &[u8]) -> [u8; 3] {
+        match self {
+            Self::Xrgb8888 => [pixel[2], pixel[1], pixel[0]],
+            Self::Rgb565 => {
+                let v = u16::from_le_bytes([pixel[0], pixel[1]]);
+                let (r, g, b) = ((v >> 11) as u8, (v >> 5) as u8 & 0x3f, v as u8 & 0x1f);
+                [r << 3 | r >> 2, g << 2 | g >> 4, b << 3 | b >> 2]
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mode {
+    pub width: u32,
+    pub height: u32,
+    pub format: PixelFormat,
+}
+
+impl Mode {
+    pub fn stride(&self) -> u32 {
+        self.width * self.format.bytes_per_pixel()
+    }
+
+    pub fn len(&self) -> u32 {
+        self.stride() * self.height
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// The pixels, shared between the device and the host side that dumps
+/// them. Clones share the same pixels.
+#[derive(Clone)]
+pub struct Screen {
+    mode: Mode,
+    pixels: Arc<Mutex<Vec<u8>>>,
+}
+
+impl Screen {
+    pub fn new(mode: Mode) -> Self {
+        Self {
+            mode,
+            pixels: Arc::new(Mutex::new(vec![0; mode.len() as usize])),
+        }
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// The picture as packed 8-bit RGB rows.
+    pub fn to_rgb(&self) -> Vec<u8> {
+        let pixels = self.pixels.lock().unwrap();
+        let bpp = self.mode.format.bytes_per_pixel() as usize;
+        pixels
+            .chunks(bpp)
+            .flat_map(|pixel| self.mode.format.to_rgb(pixel))
+            .collect()
+    }
+
+    /// Writes the picture to `path`, as PPM if it ends in `.ppm` and as PNG
+    /// otherwise.
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        let rgb = self.to_rgb();
+        let Mode { width, height, .. } = self.mode;
+        let mut out = BufWriter::new(File::create(path)?);
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("ppm") => encode::write_ppm(&mut out, width, height, &rgb)?,
+            _ => encode::write_png(&mut out, width, height, &rgb)?,
+        }
+        io::Write::flush(&mut out)
+    }
+}
+
+/// Where dumps go: `path` itself for the final picture, and `path` with
+/// the frame number inserted before the extension for the others.
+pub struct DumpConfig {
+    pub path: PathBuf,
+    /// Dump every this many presented frames.
+    pub every: Option<u64>,
+}
+
+impl DumpConfig {
+    pub fn numbered(&self, frame: u64) -> PathBuf {
+        let stem = self.path.file_stem().unwrap_or_default().to_string_lossy();
+        let name = match self.path.extension() {
+            Some(ext) => format!("{}-{:06}.{}", stem, frame, ext.to_string_lossy()),
+            None => format!("{}-{:06}", stem, frame),
+        };
+        self.path.with_file_name(name)
+    }
+}
+
+pub struct Framebuffer {
+    screen: Screen,
+    frame: u64,
+    dumps: Option<DumpConfig>,
+}
+
+impl Framebuffer {
+    pub fn new(screen: Screen) -> Self {
+        Self {
+            screen,
+            frame: 0,
+            dumps: None,
+        }
+    }
+
+    pub fn with_dumps(mut self, dumps: DumpConfig) -> Self {
+        self.dumps = Some(dumps);
+        self
+    }
+
+    fn dump(&self) {
+        let Some(dumps) = &self.dumps else { return };
+        let path = dumps.numbered(self.frame);
+        if let Err(e) = self.screen.save(&path) {
+            eprintln!("Failed to dump framebuffer to {}: {}", path.display(), e);
+        }
+    }
+
+    fn present(&mut self) {
+        self.frame += 1;
+        let every = self.dumps.as_ref().and_then(|dumps| dumps.every);
+        if let Some(every) = every
+            && every != 0
+            && self.frame.is_multiple_of(every)
+        {
+            self.dump();
+        }
+    }
+}
+
+impl Device for Framebuffer {
+    fn name(&self) -> &str {
+        "Framebuffer"
+    }
+
+    /// Only the pixels: `simple-framebuffer` has no registers.
+    fn fdt_node(&self) -> Option<FdtNode> {
+        let mode = self.screen.mode;
+        let format = match mode.format {
+            PixelFormat::Xrgb8888 => "x8r8g8b8",
+            PixelFormat::Rgb565 => "r5g6b5",
+        };
+        Some(
+            FdtNode::new("framebuffer", &["simple-framebuffer"])
+                .reg(PIXEL_OFFSET, mode.len())
+                .u32("width", mode.width)
+                .u32("height", mode.height)
+                .u32("stride", mode.stride())
+                .string("format", format),
+        )
+    }
+
+    fn load(&mut self, addr: u32, size: u8) -> Result<u32, BusError> {
+        let mode = self.screen.mode;
+        match addr {
+            REG_WIDTH => Ok(mode.width),
+            REG_HEIGHT => Ok(mode.height),
+            REG_STRIDE => Ok(mode.stride()),
+            REG_FORMAT => Ok(mode.format.code()),
+            REG_FRAME => Ok(self.frame as u32),
+            PIXEL_OFFSET.. => {
+                let start = (addr - PIXEL_OFFSET) as usize;
+                let pixels = self.screen.pixels.lock().unwrap();
+                let bytes = pixels
+                    .get(start..start + size as usize)
+                    .ok_or(BusError::LoadAccessFault(addr as u64))?;
+                let mut val = [0; 4];
+                val[..bytes.len()].copy_from_slice(bytes);
+                Ok(u32::from_le_bytes(val))
+            }
+            _ => Err(BusError::LoadAccessFault(addr as u64)),
+        }
+    }
+
+    fn store(&mut self, addr: u32, size: u8, val: u32) -> Result<(), BusError> {
+        match addr {
+            REG_CTRL => {
+                match val {
+                    CMD_PRESENT => self.present(),
+                    CMD_DUMP => self.dump(),
+                    _ => {}
+                }
+                Ok(())
+            }
+            PIXEL_OFFSET.. => {
+                let start = (addr - PIXEL_OFFSET) as usize;
+                let mut pixels = self.screen.pixels.lock().unwrap();
+                let bytes = pixels
+                    .get_mut(start..start + size as usize)
+                    .ok_or(BusError::StoreAccessFault(addr as u64))?;
+                bytes.copy_from_slice(&val.to_le_bytes()[..size as usize]);
+                Ok(())
+            }
+            _ => Err(BusError::StoreAccessFault(addr as u64)),
+        }
+    }
+
+    /// Pixels are memory and keep their contents.
+    fn reset(&mut self) {
+        self.frame = 0;
+    }
+
+    fn size(&self) -> u32 {
+        PIXEL_OFFSET + self.screen.mode.len().next_multiple_of(0x1000)
+    }
+}
@@ -0,0 +1,141 @@
+use std::io::{self, Write};
+
+use crate::devices::{Bus, BusError, PowerControl, PowerRequest};
+
+const DEV_SYSCALL: u64 = 0;
+const DEV_CONSOLE: u64 = 1;
+
+const CONSOLE_PUTCHAR: u64 = 1;
+
+const SYS_WRITE: u64 = 64;
+const SYS_EXIT: u64 = 93;
+
+const EFAULT: i64 = 14;
+const EINVAL: i64 = 22;
+const ENOSYS: i64 = 38;
+
+/// Longest write the proxy accepts, so a bad length from the guest can't
+/// make the host copy all of memory.
+const MAX_WRITE: u64 = 64 * 1024;
+
+/// Words in the `magic_mem` block a proxied syscall points at: the syscall
+/// number followed by its arguments, all 64 bits wide.
+const SYSCALL_ARGS: usize = 8;
+
+/// Berkeley host-target interface, as used by riscv-tests and the proxy
+/// kernel. `tohost` and `fromhost` are plain RAM words found through the
+/// ELF symbol table; the guest posts a command to `tohost` and the host
+/// answers through `fromhost`.
+///
+/// Commands are `device << 56 | cmd << 48 | payload`. Device 0 is the
+/// syscall proxy: an odd payload exits with `payload >> 1`, an even one
+/// points at a `magic_mem` block holding a write or exit syscall. Device 1
+/// is the console, of which only putchar is supported.
+pub struct Htif {
+    tohost: u64,
+    fromhost: Option<u64>,
+    console: Box<dyn Write + Send>,
+    power: PowerControl,
+}
+
+impl Htif {
+    pub fn new(
+        tohost: u64,
+        fromhost: Option<u64>,
+        console: Box<dyn Write + Send>,
+        power: PowerControl,
+    ) -> Self {
+        Self {
+            tohost,
+            fromhost,
+            console,
+            power,
+        }
+    }
+
+    /// Handles a pending command, if any. An exit is passed on as a power
+    /// off request.
+    pub fn poll(&mut self, bus: &mut Bus) -> Result<(), BusError> {
+        let val = bus.load(self.tohost, 8)?;
+        if val == 0 {
+            return Ok(());
+        }
+        bus.store(self.tohost, 8, 0)?;
+
+        let device = val >> 56;
+        let cmd = (val >> 48) & 0xff;
+        let payload = val & 0xffff_ffff_ffff;
+
+        match (device, cmd) {
+            (DEV_SYSCALL, 0) if payload & 1 == 1 => self.exit(payload >> 1),
+            (DEV_SYSCALL, 0) => {
+                self.syscall(bus, payload)?;
+                self.respond(bus, device, cmd, 1)?;
+            }
+            (DEV_CONSOLE, CONSOLE_PUTCHAR) => {
+                // console output is best effort, like the UART's
+                let _ = self.console.write_all(&[payload as u8]);
+                let _ = self.console.flush();
+                self.respond(bus, device, cmd, 0)?;
+            }
+            // getchar and unknown devices never answer
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn exit(&mut self, code: u64) {
+        if code != 0 {
+            eprintln!("*** FAILED *** (tohost = {})", code);
+        }
+        self.power.request(PowerRequest::Off { code: code as i32 });
+    }
+
+    fn syscall(&mut self, bus: &mut Bus, magic_mem: u64) -> Result<(), BusError> {
+        let mut args = [0; SYSCALL_ARGS];
+        for (i, arg) in args.iter_mut().enumerate() {
+            *arg = bus.load(magic_mem + i as u64 * 8, 8)?;
+        }
+
+        let ret = match args[0] {
+            SYS_WRITE if args[3] > MAX_WRITE => -EINVAL as u64,
+            SYS_WRITE => match bus.dump(args[2], args[3]) {
+                Ok(data) => match self.write(args[1], &data) {
+                    Ok(()) => args[3],
+                    Err(_) => -1i64 as u64,
+                },
+                Err(_) => -EFAULT as u64,
+            },
+            SYS_EXIT => {
+                self.exit(args[1]);
+                0
+            }
+            _ => -ENOSYS as u64,
+        };
+        bus.store(magic_mem, 8, ret)
+    }
+
+    fn write(&mut self, fd: u64, data: &[u8]) -> io::Result<()> {
+        match fd {
+            1 => self
+                .console
+                .write_all(data)
+                .and_then(|_| self.console.flush()),
+            2 => io::stderr().write_all(data),
+            _ => Err(io::ErrorKind::InvalidInput.into()),
+        }
+    }
+
+    fn respond(
+        &mut self,
+        bus: &mut Bus,
+        device: u64,
+        cmd: u64,
+        payload: u64,
+    ) -> Result<(), BusError> {
+        match self.fromhost {
+            Some(fromhost) => bus.store(fromhost, 8, device << 56 | cmd << 48 | payload),
+            None => Ok(()),
+        }
+    }
+}
@@ -1,9 +1,29 @@
+pub mod block;
+pub mod boot_rom;
 pub mod bus;
 pub mod disk;
 pub mod dram;
+pub mod fdt;
+pub mod framebuffer;
+pub mod htif;
+pub mod rtc;
+pub mod sparse_dram;
+pub mod syscon;
 pub mod uart;
+pub mod virtio;
+pub mod watchdog;
 
+pub use block::*;
+pub use boot_rom::*;
 pub use bus::*;
 pub use disk::*;
 pub use dram::*;
+pub use fdt::*;
+pub use framebuffer::*;
+pub use htif::*;
+pub use rtc::*;
+pub use sparse_dram::*;
+pub use syscon::*;
 pub use uart::*;
+pub use virtio::*;
+pub use watchdog::*;
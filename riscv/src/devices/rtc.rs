@@ -0,0 +1,168 @@
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::devices::{BusError, Device, DmaView, FdtNode};
+
+const REG_TIME_LOW: u32 = 0x00; // Read: low word of the time, latches the high word
+const REG_TIME_HIGH: u32 = 0x04; // Read: latched high word of the time
+const REG_ALARM_LOW: u32 = 0x08; // Write: arms the alarm with ALARM_HIGH as the high word
+const REG_ALARM_HIGH: u32 = 0x0C; // Write: high word for the next ALARM_LOW write
+const REG_IRQ_ENABLED: u32 = 0x10; // R/W: 1 = the alarm raises an interrupt
+const REG_CLEAR_ALARM: u32 = 0x14; // Write: disarms the alarm
+const REG_ALARM_STATUS: u32 = 0x18; // Read: 1 = alarm armed
+const REG_CLEAR_INTERRUPT: u32 = 0x1C; // Write: acknowledges the alarm interrupt
+
+/// Cycle length of the deterministic clock, as if the hart ran at 10 MHz.
+pub const DEFAULT_NS_PER_CYCLE: u64 = 100;
+
+enum Clock {
+    Host,
+    /// Starts at a given time and advances with the host's monotonic clock.
+    Fixed {
+        start: u64,
+        since: Instant,
+    },
+    /// Advances by a fixed step per cycle, independent of the host.
+    Cycles {
+        start: u64,
+        ns_per_cycle: u64,
+    },
+}
+
+/// Goldfish RTC: nanoseconds since the Unix epoch, with a one-shot alarm.
+pub struct GoldfishRtc {
+    clock: Clock,
+    /// Added to the clock; set when the guest writes the time.
+    offset: u64,
+    /// Current cycle, from the last tick.
+    now: u64,
+    time_high: u32,
+    alarm_high: u32,
+    alarm: Option<u64>,
+    irq_enabled: bool,
+    irq_status: bool,
+}
+
+impl GoldfishRtc {
+    /// Follows the host's wall clock.
+    pub fn host() -> Self {
+        Self::with_clock(Clock::Host)
+    }
+
+    /// Starts at `start` nanoseconds since the epoch and runs in real time.
+    pub fn starting_at(start: u64) -> Self {
+        Self::with_clock(Clock::Fixed {
+            start,
+            since: Instant::now(),
+        })
+    }
+
+    /// Starts at `start` and advances `ns_per_cycle` every cycle, so the
+    /// same run always sees the same times.
+    pub fn deterministic(start: u64, ns_per_cycle: u64) -> Self {
+        Self::with_clock(Clock::Cycles {
+            start,
+            ns_per_cycle,
+        })
+    }
+
+    fn with_clock(clock: Clock) -> Self {
+        Self {
+            clock,
+            offset: 0,
+            now: 0,
+            time_high: 0,
+            alarm_high: 0,
+            alarm: None,
+            irq_enabled: false,
+            irq_status: false,
+        }
+    }
+
+    fn clock_time(&self) -> u64 {
+        match self.clock {
+            Clock::Host => SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_nanos() as u64),
+            Clock::Fixed { start, since } => start + since.elapsed().as_nanos() as u64,
+            Clock::Cycles {
+                start,
+                ns_per_cycle,
+            } => start + self.now * ns_per_cycle,
+        }
+    }
+
+    fn time(&self) -> u64 {
+        self.clock_time().wrapping_add(self.offset)
+    }
+
+    fn set_time(&mut self, time: u64) {
+        self.offset = time.wrapping_sub(self.clock_time());
+    }
+}
+
+impl Device for GoldfishRtc {
+    fn name(&self) -> &str {
+        "RTC"
+    }
+
+    fn fdt_node(&self) -> Option<FdtNode> {
+        Some(FdtNode::new("rtc", &["google,goldfish-rtc"]).interrupts())
+    }
+
+    fn load(&mut self, addr: u32, _size: u8) -> Result<u32, BusError> {
+        match addr {
+            REG_TIME_LOW => {
+                let time = self.time();
+                self.time_high = (time >> 32) as u32;
+                Ok(time as u32)
+            }
+            REG_TIME_HIGH => Ok(self.time_high),
+            REG_IRQ_ENABLED => Ok(self.irq_enabled as u32),
+            REG_ALARM_STATUS => Ok(self.alarm.is_some() as u32),
+            _ => Err(BusError::LoadAccessFault(addr as u64)),
+        }
+    }
+
+    fn store(&mut self, addr: u32, _size: u8, val: u32) -> Result<(), BusError> {
+        match addr {
+            // the guest sets the time by writing the high word, then the low
+            REG_TIME_LOW => self.set_time((self.time_high as u64) << 32 | val as u64),
+            REG_TIME_HIGH => self.time_high = val,
+            REG_ALARM_LOW => self.alarm = Some((self.alarm_high as u64) << 32 | val as u64),
+            REG_ALARM_HIGH => self.alarm_high = val,
+            REG_IRQ_ENABLED => self.irq_enabled = val & 1 != 0,
+            REG_CLEAR_ALARM => self.alarm = None,
+            REG_CLEAR_INTERRUPT => self.irq_status = false,
+            _ => return Err(BusError::StoreAccessFault(addr as u64)),
+        }
+        Ok(())
+    }
+
+    fn size(&self) -> u32 {
+        0x1000
+    }
+
+    fn irq_pending(&mut self) -> bool {
+        self.irq_enabled && self.irq_status
+    }
+
+    /// The time keeps running across a reset, like a battery-backed RTC.
+    fn reset(&mut self) {
+        self.time_high = 0;
+        self.alarm_high = 0;
+        self.alarm = None;
+        self.irq_enabled = false;
+        self.irq_status = false;
+    }
+
+    fn tick(&mut self, now: u64, _dma: &mut DmaView) {
+        self.now = now;
+        // an alarm in the past fires right away
+        if let Some(alarm) = self.alarm
+            && self.time() >= alarm
+        {
+            self.alarm = None;
+            self.irq_status = true;
+        }
+    }
+}
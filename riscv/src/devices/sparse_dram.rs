@@ -0,0 +1,140 @@
+use crate::devices::{BusError, Device, FdtNode};
+
+const PAGE_SIZE: usize = 4096;
+const PAGE_SHIFT: u32 = 12;
+
+type Page = Box<[u8; PAGE_SIZE]>;
+
+/// RAM that only takes host memory for pages the guest has written; all
+/// other pages read as zero. Large guest memories cost nothing until used,
+/// beyond one pointer per page for the table.
+pub struct SparseDram {
+    size: u32,
+    /// Indexed by page number; `None` until the page is first written.
+    pages: Vec<Option<Page>>,
+    allocated: usize,
+}
+
+impl SparseDram {
+    pub fn new(size: u32) -> Self {
+        let count = (size as usize).div_ceil(PAGE_SIZE);
+        Self {
+            size,
+            pages: std::iter::repeat_with(|| None).take(count).collect(),
+            allocated: 0,
+        }
+    }
+
+    /// Host memory in use, in pages.
+    pub fn allocated_pages(&self) -> usize {
+        self.allocated
+    }
+
+    fn page(&self, page: u32) -> Option<&[u8; PAGE_SIZE]> {
+        self.pages[page as usize].as_deref()
+    }
+
+    fn page_mut(&mut self, page: u32) -> &mut [u8; PAGE_SIZE] {
+        let allocated = &mut self.allocated;
+        self.pages[page as usize].get_or_insert_with(|| {
+            *allocated += 1;
+            Box::new([0; PAGE_SIZE])
+        })
+    }
+
+    fn in_range(&self, addr: u32, len: usize) -> bool {
+        addr as u64 + len as u64 <= self.size as u64
+    }
+}
+
+/// Splits `addr..addr + len` at page boundaries into (page, offset, chunk
+/// start, chunk length).
+fn chunks(addr: u32, len: usize) -> impl Iterator<Item = (u32, usize, usize, usize)> {
+    let mut pos = 0;
+    std::iter::from_fn(move || {
+        if pos == len {
+            return None;
+        }
+        let at = addr as usize + pos;
+        let offset = at % PAGE_SIZE;
+        let count = (PAGE_SIZE - offset).min(len - pos);
+        let chunk = ((at >> PAGE_SHIFT) as u32, offset, pos, count);
+        pos += count;
+        Some(chunk)
+    })
+}
+
+impl Device for SparseDram {
+    fn name(&self) -> &str {
+        "DRAM"
+    }
+
+    fn fdt_node(&self) -> Option<FdtNode> {
+        Some(FdtNode::memory())
+    }
+
+    fn load(&mut self, addr: u32, size: u8) -> Result<u32, BusError> {
+        let len = size as usize;
+        if !matches!(len, 1 | 2 | 4) || !self.in_range(addr, len) {
+            return Err(BusError::LoadAccessFault(addr as u64));
+        }
+
+        let mut bytes = [0; 4];
+        let offset = addr as usize % PAGE_SIZE;
+        if offset + len <= PAGE_SIZE {
+            // fast path: the access stays within one page
+            if let Some(page) = self.page(addr >> PAGE_SHIFT) {
+                bytes[..len].copy_from_slice(&page[offset..offset + len]);
+            }
+        } else {
+            self.read_bytes(addr, &mut bytes[..len])?;
+        }
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    fn store(&mut self, addr: u32, size: u8, val: u32) -> Result<(), BusError> {
+        let len = size as usize;
+        if !matches!(len, 1 | 2 | 4) || !self.in_range(addr, len) {
+            return Err(BusError::StoreAccessFault(addr as u64));
+        }
+
+        let bytes = val.to_le_bytes();
+        let offset = addr as usize % PAGE_SIZE;
+        if offset + len <= PAGE_SIZE {
+            let page = self.page_mut(addr >> PAGE_SHIFT);
+            page[offset..offset + len].copy_from_slice(&bytes[..len]);
+            Ok(())
+        } else {
+            self.write_bytes(addr, &bytes[..len])
+        }
+    }
+
+    fn write_bytes(&mut self, addr: u32, data: &[u8]) -> Result<(), BusError> {
+        if !self.in_range(addr, data.len()) {
+            return Err(BusError::StoreAccessFault(addr as u64));
+        }
+        for (page, offset, pos, count) in chunks(addr, data.len()) {
+            let page = self.page_mut(page);
+            page[offset..offset + count].copy_from_slice(&data[pos..pos + count]);
+        }
+        Ok(())
+    }
+
+    fn read_bytes(&mut self, addr: u32, buf: &mut [u8]) -> Result<(), BusError> {
+        if !self.in_range(addr, buf.len()) {
+            return Err(BusError::LoadAccessFault(addr as u64));
+        }
+        for (page, offset, pos, count) in chunks(addr, buf.len()) {
+            let dest = &mut buf[pos..pos + count];
+            match self.page(page) {
+                Some(page) => dest.copy_from_slice(&page[offset..offset + count]),
+                None => dest.fill(0),
+            }
+        }
+        Ok(())
+    }
+
+    fn size(&self) -> u32 {
+        self.size
+    }
+}
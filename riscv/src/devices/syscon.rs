@@ -0,0 +1,104 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::devices::{BusError, Device, FdtNode};
+
+/// Low half of the word written to the finisher; a failure code goes in
+/// the high half.
+pub const FINISHER_FAIL: u32 = 0x3333;
+pub const FINISHER_PASS: u32 = 0x5555;
+pub const FINISHER_RESET: u32 = 0x7777;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerRequest {
+    /// Stop the machine; the emulator exits with `code`.
+    Off {
+        code: i32,
+    },
+    Reset,
+    /// Stop the machine and dump the hart's state for post-mortem.
+    Halt,
+}
+
+/// Carries power requests from the guest to whoever runs the machine.
+/// Clones share the same request.
+#[derive(Clone, Default)]
+pub struct PowerControl {
+    request: Arc<Mutex<Option<PowerRequest>>>,
+    /// Set along with `request`, so the hart can check it every step
+    /// without taking the lock.
+    pending: Arc<AtomicBool>,
+}
+
+impl PowerControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn request(&self, request: PowerRequest) {
+        *self.request.lock().unwrap() = Some(request);
+        self.pending.store(true, Ordering::Release);
+    }
+
+    pub fn pending(&self) -> bool {
+        self.pending.load(Ordering::Acquire)
+    }
+
+    /// The pending request, if any; clears it.
+    pub fn take(&self) -> Option<PowerRequest> {
+        let mut request = self.request.lock().unwrap();
+        self.pending.store(false, Ordering::Release);
+        request.take()
+    }
+}
+
+/// SiFive test finisher, as used for syscon-poweroff/syscon-reboot: write
+/// FINISHER_PASS, FINISHER_FAIL | code << 16 or FINISHER_RESET to offset 0.
+pub struct TestFinisher {
+    control: PowerControl,
+}
+
+impl TestFinisher {
+    pub fn new(control: PowerControl) -> Self {
+        Self { control }
+    }
+}
+
+impl Device for TestFinisher {
+    fn name(&self) -> &str {
+        "TestFinisher"
+    }
+
+    fn fdt_node(&self) -> Option<FdtNode> {
+        Some(FdtNode::new(
+            "test",
+            &["sifive,test1", "sifive,test0", "syscon"],
+        ))
+    }
+
+    fn load(&mut self, _addr: u32, _size: u8) -> Result<u32, BusError> {
+        Ok(0)
+    }
+
+    fn store(&mut self, addr: u32, size: u8, val: u32) -> Result<(), BusError> {
+        if addr != 0 || size != 4 {
+            return Err(BusError::StoreAccessFault(addr as u64));
+        }
+
+        let request = match val & 0xffff {
+            FINISHER_PASS => PowerRequest::Off { code: 0 },
+            FINISHER_FAIL => PowerRequest::Off {
+                code: (val >> 16) as i32,
+            },
+            FINISHER_RESET => PowerRequest::Reset,
+            // unknown commands are ignored, like on the real device
+            _ => return Ok(()),
+        };
+        self.control.request(request);
+        Ok(())
+    }
+
+    fn size(&self) -> u32 {
+        0x1000
+    }
+}
@@ -0,0 +1,429 @@
+use std::collections::VecDeque;
+use std::ffi::CStr;
+use std::fs::File;
+use std::io::{self, BufReader, ErrorKind, LineWriter, Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Host side of a serial line. `read` must never block: it returns `None`
+/// when no input is available right now.
+pub trait UartBackend {
+    fn write(&mut self, byte: u8);
+
+    fn read(&mut self) -> Option<u8>;
+}
+
+/// Takes a byte from a reader thread's channel without blocking; drops the
+/// channel once the thread is gone.
+fn try_recv(rx: &mut Option<Receiver<u8>>) -> Option<u8> {
+    match rx.as_ref()?.try_recv() {
+        Ok(byte) => Some(byte),
+        Err(TryRecvError::Empty) => None,
+        Err(TryRecvError::Disconnected) => {
+            *rx = None;
+            None
+        }
+    }
+}
+
+/// Ctrl-A, then `x`, quits the emulator (stdin is in raw mode, so Ctrl-C
+/// goes to the guest).
+const ESCAPE_CHAR: u8 = 0x01;
+
+/// Runs on the stdin thread when Ctrl-A is followed by its key.
+pub type EscapeAction = Box<dyn FnMut() + Send>;
+
+/// Transmits to stdout and receives from stdin, which is switched to raw
+/// mode if it is a terminal.
+pub struct StdioBackend {
+    rx: Option<Receiver<u8>>,
+    _raw_mode: Option<RawMode>,
+}
+
+impl StdioBackend {
+    pub fn new() -> Self {
+        Self::with_escapes(Vec::new())
+    }
+
+    /// Like `new`, with emulator commands bound to Ctrl-A sequences. A
+    /// binding for `x` replaces the default of exiting on the spot.
+    pub fn with_escapes(mut escapes: Vec<(u8, EscapeAction)>) -> Self {
+        let raw_mode = RawMode::enable(libc::STDIN_FILENO);
+        let (tx, rx) = mpsc::channel();
+        let restore = raw_mode.as_ref().map(|mode| mode.saved);
+
+        // stdin has no portable non-blocking read, so a reader thread feeds
+        // a channel that is polled from the cpu thread
+        thread::spawn(move || {
+            let mut escape = false;
+            for byte in io::stdin().lock().bytes() {
+                let Ok(byte) = byte else { break };
+                if escape {
+                    escape = false;
+                    if let Some((_, action)) = escapes.iter_mut().find(|(key, _)| *key == byte) {
+                        action();
+                        continue;
+                    }
+                    if byte == b'x' {
+                        if let Some(termios) = restore {
+                            RawMode::restore(libc::STDIN_FILENO, &termios);
+                        }
+                        std::process::exit(0);
+                    }
+                } else if byte == ESCAPE_CHAR {
+                    escape = true;
+                    continue;
+                }
+                if tx.send(byte).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            rx: Some(rx),
+            _raw_mode: raw_mode,
+        }
+    }
+}
+
+impl Default for StdioBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl UartBackend for StdioBackend {
+    fn write(&mut self, byte: u8) {
+        let mut stdout = io::stdout();
+        let _ = stdout.write_all(&[byte]);
+        let _ = stdout.flush();
+    }
+
+    fn read(&mut self) -> Option<u8> {
+        try_recv(&mut self.rx)
+    }
+}
+
+/// Appends all output to a log file; there is no input.
+pub struct FileBackend {
+    file: LineWriter<File>,
+}
+
+impl FileBackend {
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self {
+            file: LineWriter::new(File::create(path)?),
+        })
+    }
+}
+
+impl UartBackend for FileBackend {
+    fn write(&mut self, byte: u8) {
+        let _ = self.file.write_all(&[byte]);
+    }
+
+    fn read(&mut self) -> Option<u8> {
+        None
+    }
+}
+
+#[derive(Default)]
+struct MemoryBuffers {
+    input: VecDeque<u8>,
+    output: Vec<u8>,
+}
+
+/// In-memory serial line. Clones share the same buffers, so a test can keep
+/// one handle while the UART owns another.
+#[derive(Clone, Default)]
+pub struct MemoryBackend {
+    buffers: Arc<Mutex<MemoryBuffers>>,
+}
+
+impl MemoryBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues bytes for the guest to receive.
+    pub fn push_input(&self, data: &[u8]) {
+        self.buffers.lock().unwrap().input.extend(data);
+    }
+
+    /// Everything the guest has transmitted so far.
+    pub fn output(&self) -> Vec<u8> {
+        self.buffers.lock().unwrap().output.clone()
+    }
+
+    /// Like `output`, but clears the buffer.
+    pub fn take_output(&self) -> Vec<u8> {
+        std::mem::take(&mut self.buffers.lock().unwrap().output)
+    }
+}
+
+impl UartBackend for MemoryBackend {
+    fn write(&mut self, byte: u8) {
+        self.buffers.lock().unwrap().output.push(byte);
+    }
+
+    fn read(&mut self) -> Option<u8> {
+        self.buffers.lock().unwrap().input.pop_front()
+    }
+}
+
+/// Listens on a Unix domain socket and connects the line to one client at a
+/// time. Output is dropped while nobody is connected.
+pub struct UnixSocketBackend {
+    path: PathBuf,
+    rx: Option<Receiver<u8>>,
+    /// The connected client, shared with the thread that accepts and reads.
+    client: Arc<Mutex<Option<UnixStream>>>,
+}
+
+impl UnixSocketBackend {
+    pub fn bind(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        // a stale socket from a previous run would make bind fail
+        if path.exists() {
+            std::fs::remove_file(&path)?;
+        }
+
+        let listener = UnixListener::bind(&path)?;
+        eprintln!("UART: listening on {}", path.display());
+
+        let (tx, rx) = mpsc::channel();
+        let client = Arc::new(Mutex::new(None));
+        let shared = client.clone();
+        // accepting and reading block, so both happen off the cpu thread
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(stream) = stream else { continue };
+                let Ok(writer) = stream.try_clone() else {
+                    continue;
+                };
+                *shared.lock().unwrap() = Some(writer);
+                let connected = forward_input(stream, &tx);
+                *shared.lock().unwrap() = None;
+                if !connected {
+                    break;
+                }
+            }
+        });
+
+        Ok(Self {
+            path,
+            rx: Some(rx),
+            client,
+        })
+    }
+}
+
+/// Sends everything read from `input` to `tx` until end of file. Returns
+/// false once the receiving side is gone.
+fn forward_input(input: impl Read, tx: &Sender<u8>) -> bool {
+    for byte in BufReader::new(input).bytes() {
+        let Ok(byte) = byte else { break };
+        if tx.send(byte).is_err() {
+            return false;
+        }
+    }
+    true
+}
+
+impl UartBackend for UnixSocketBackend {
+    fn write(&mut self, byte: u8) {
+        let mut client = self.client.lock().unwrap();
+        let Some(stream) = client.as_ref() else {
+            return;
+        };
+        // SAFETY: the descriptor belongs to `stream`, which is borrowed for
+        // the call, and the buffer is a live one-byte array.
+        let sent = unsafe {
+            libc::send(
+                stream.as_raw_fd(),
+                [byte].as_ptr().cast(),
+                1,
+                libc::MSG_DONTWAIT | libc::MSG_NOSIGNAL,
+            )
+        };
+        if sent < 0 {
+            match io::Error::last_os_error().kind() {
+                // the client isn't keeping up, drop the byte like a real line would
+                ErrorKind::WouldBlock => {}
+                _ => *client = None,
+            }
+        }
+    }
+
+    fn read(&mut self) -> Option<u8> {
+        try_recv(&mut self.rx)
+    }
+}
+
+impl Drop for UnixSocketBackend {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// How long the pty reader waits before looking for a slave again.
+const PTY_IDLE_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Allocates a host pseudo-terminal; attach to the printed slave device with
+/// e.g. `screen` or `picocom`.
+pub struct PtyBackend {
+    master: File,
+    slave_path: PathBuf,
+    rx: Option<Receiver<u8>>,
+}
+
+impl PtyBackend {
+    pub fn open() -> io::Result<Self> {
+        // SAFETY: the fd returned by posix_openpt is checked before use and
+        // owned by the File afterwards; ptsname's buffer is copied right away.
+        let (master, slave_path) = unsafe {
+            let fd = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY | libc::O_NONBLOCK);
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let master = File::from_raw_fd(fd);
+
+            if libc::grantpt(fd) != 0 || libc::unlockpt(fd) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let name = libc::ptsname(fd);
+            if name.is_null() {
+                return Err(io::Error::last_os_error());
+            }
+            let slave_path = PathBuf::from(CStr::from_ptr(name).to_string_lossy().into_owned());
+
+            // the guest does its own line discipline
+            let mut termios: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(fd, &mut termios) == 0 {
+                libc::cfmakeraw(&mut termios);
+                libc::tcsetattr(fd, libc::TCSANOW, &termios);
+            }
+
+            (master, slave_path)
+        };
+
+        eprintln!("UART: attached to {}", slave_path.display());
+
+        let (tx, rx) = mpsc::channel();
+        let reader = master.try_clone()?;
+        thread::spawn(move || Self::read_input(reader, tx));
+
+        Ok(Self {
+            master,
+            slave_path,
+            rx: Some(rx),
+        })
+    }
+
+    /// Reader thread. The master is non-blocking for the sake of `write`,
+    /// so this waits in poll instead; while no slave is open the master
+    /// reports a hangup, and the thread backs off rather than spin.
+    fn read_input(mut master: File, tx: Sender<u8>) {
+        let mut buf = [0; 256];
+        loop {
+            let mut pollfd = libc::pollfd {
+                fd: master.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            // SAFETY: pollfd is a valid array of one entry for the call
+            unsafe { libc::poll(&mut pollfd, 1, -1) };
+
+            match master.read(&mut buf) {
+                Ok(n) if n > 0 => {
+                    if buf[..n].iter().any(|&byte| tx.send(byte).is_err()) {
+                        return;
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {}
+                // EIO or eof while no slave is open
+                _ => thread::sleep(PTY_IDLE_BACKOFF),
+            }
+        }
+    }
+
+    pub fn slave_path(&self) -> &Path {
+        &self.slave_path
+    }
+}
+
+impl UartBackend for PtyBackend {
+    fn write(&mut self, byte: u8) {
+        // fails with EAGAIN once the buffer is full and nobody reads; drop
+        let _ = self.master.write_all(&[byte]);
+    }
+
+    fn read(&mut self) -> Option<u8> {
+        try_recv(&mut self.rx)
+    }
+}
+
+/// Puts a terminal into raw mode and restores the previous settings when
+/// dropped.
+struct RawMode {
+    fd: i32,
+    saved: libc::termios,
+}
+
+impl RawMode {
+    fn enable(fd: i32) -> Option<Self> {
+        // SAFETY: termios is plain data and only handed to tcgetattr/tcsetattr
+        // on a descriptor that outlives the process.
+        unsafe {
+            if libc::isatty(fd) == 0 {
+                return None;
+            }
+
+            let mut saved: libc::termios = std::mem::zeroed();
+            if libc::tcgetattr(fd, &mut saved) != 0 {
+                return None;
+            }
+
+            // like cfmakeraw, but keep output processing so "\n" still
+            // returns the carriage
+            let mut raw = saved;
+            raw.c_iflag &= !(libc::IGNBRK
+                | libc::BRKINT
+                | libc::PARMRK
+                | libc::ISTRIP
+                | libc::INLCR
+                | libc::IGNCR
+                | libc::ICRNL
+                | libc::IXON);
+            raw.c_lflag &= !(libc::ECHO | libc::ECHONL | libc::ICANON | libc::ISIG | libc::IEXTEN);
+            raw.c_cc[libc::VMIN] = 1;
+            raw.c_cc[libc::VTIME] = 0;
+
+            if libc::tcsetattr(fd, libc::TCSANOW, &raw) != 0 {
+                return None;
+            }
+
+            Some(Self { fd, saved })
+        }
+    }
+
+    fn restore(fd: i32, termios: &libc::termios) {
+        // SAFETY: see `enable`
+        unsafe {
+            libc::tcsetattr(fd, libc::TCSANOW, termios);
+        }
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        Self::restore(self.fd, &self.saved);
+    }
+}
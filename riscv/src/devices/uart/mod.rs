@@ -0,0 +1,225 @@
+mod backend;
+
+pub use backend::*;
+
+use std::collections::VecDeque;
+
+use crate::devices::{BusError, Device, DmaView, FdtNode};
+
+// NS16550A register map (byte registers, no shift)
+const REG_RBR_THR: u32 = 0x0; // Read: receive buffer, Write: transmit holding (DLL when DLAB = 1)
+const REG_IER: u32 = 0x1; // R/W: interrupt enable (DLM when DLAB = 1)
+const REG_IIR_FCR: u32 = 0x2; // Read: interrupt identification, Write: FIFO control
+const REG_LCR: u32 = 0x3; // R/W: line control
+const REG_MCR: u32 = 0x4; // R/W: modem control
+const REG_LSR: u32 = 0x5; // Read: line status
+const REG_MSR: u32 = 0x6; // Read: modem status
+const REG_SCR: u32 = 0x7; // R/W: scratch
+
+const IER_ERBFI: u8 = 1 << 0; // received data available
+const IER_ETBEI: u8 = 1 << 1; // transmit holding register empty
+
+const IIR_NO_INT: u8 = 0x01;
+const IIR_THR_EMPTY: u8 = 0x02;
+const IIR_RX_AVAIL: u8 = 0x04;
+const IIR_FIFO_ENABLED: u8 = 0xc0;
+
+const FCR_ENABLE: u8 = 1 << 0;
+const FCR_CLEAR_RX: u8 = 1 << 1;
+
+const LCR_DLAB: u8 = 1 << 7;
+
+const LSR_DR: u8 = 1 << 0; // data ready
+const LSR_THRE: u8 = 1 << 5; // transmit holding register empty
+const LSR_TEMT: u8 = 1 << 6; // transmitter empty
+
+// DCD | DSR | CTS: a permanently connected line
+const MSR_CONNECTED: u8 = 0xb0;
+
+const RX_FIFO_SIZE: usize = 16;
+
+/// Cycles between polls of the backend for input while the FIFO is empty.
+const RX_POLL_INTERVAL: u64 = 1024;
+
+/// Input clock advertised to the guest; the divisor latch has no effect.
+const UART_CLOCK: u32 = 3_686_400;
+
+pub struct Uart {
+    backend: Box<dyn UartBackend>,
+    rx_fifo: VecDeque<u8>,
+    ier: u8,
+    fcr: u8,
+    lcr: u8,
+    mcr: u8,
+    scr: u8,
+    dll: u8,
+    dlm: u8,
+    thre_pending: bool,
+    /// Cycle at which the backend is next asked for input.
+    next_poll: u64,
+}
+
+impl Uart {
+    pub fn new(backend: Box<dyn UartBackend>) -> Self {
+        Self {
+            backend,
+            rx_fifo: VecDeque::with_capacity(RX_FIFO_SIZE),
+            ier: 0,
+            fcr: 0,
+            lcr: 0,
+            mcr: 0,
+            scr: 0,
+            dll: 0,
+            dlm: 0,
+            thre_pending: false,
+            next_poll: 0,
+        }
+    }
+
+    fn fill_rx_fifo(&mut self) {
+        while self.rx_fifo.len() < RX_FIFO_SIZE {
+            match self.backend.read() {
+                Some(byte) => self.rx_fifo.push_back(byte),
+                None => break,
+            }
+        }
+    }
+
+    fn dlab(&self) -> bool {
+        self.lcr & LCR_DLAB != 0
+    }
+
+    fn rx_interrupt(&self) -> bool {
+        self.ier & IER_ERBFI != 0 && !self.rx_fifo.is_empty()
+    }
+
+    fn thre_interrupt(&self) -> bool {
+        self.ier & IER_ETBEI != 0 && self.thre_pending
+    }
+
+    fn iir(&mut self) -> u8 {
+        let fifo = if self.fcr & FCR_ENABLE != 0 {
+            IIR_FIFO_ENABLED
+        } else {
+            0
+        };
+
+        if self.rx_interrupt() {
+            fifo | IIR_RX_AVAIL
+        } else if self.thre_interrupt() {
+            // reading IIR acknowledges a THR empty interrupt
+            self.thre_pending = false;
+            fifo | IIR_THR_EMPTY
+        } else {
+            fifo | IIR_NO_INT
+        }
+    }
+
+    fn transmit(&mut self, byte: u8) {
+        self.backend.write(byte);
+        // the byte leaves immediately, so the holding register is empty again
+        self.thre_pending = true;
+    }
+}
+
+impl Default for Uart {
+    fn default() -> Self {
+        Self::new(Box::new(StdioBackend::new()))
+    }
+}
+
+impl Device for Uart {
+    fn name(&self) -> &str {
+        "UART"
+    }
+
+    fn fdt_node(&self) -> Option<FdtNode> {
+        Some(
+            FdtNode::new("serial", &["ns16550a"])
+                .device_type("serial")
+                .interrupts()
+                .u32("clock-frequency", UART_CLOCK),
+        )
+    }
+
+    fn load(&mut self, addr: u32, _size: u8) -> Result<u32, BusError> {
+        let val = match addr {
+            REG_RBR_THR if self.dlab() => self.dll,
+            REG_RBR_THR => self.rx_fifo.pop_front().unwrap_or(0),
+            REG_IER if self.dlab() => self.dlm,
+            REG_IER => self.ier,
+            REG_IIR_FCR => self.iir(),
+            REG_LCR => self.lcr,
+            REG_MCR => self.mcr,
+            REG_LSR => {
+                let dr = if self.rx_fifo.is_empty() { 0 } else { LSR_DR };
+                dr | LSR_THRE | LSR_TEMT
+            }
+            REG_MSR => MSR_CONNECTED,
+            REG_SCR => self.scr,
+            _ => 0,
+        };
+
+        Ok(val as u32)
+    }
+
+    fn store(&mut self, addr: u32, _size: u8, val: u32) -> Result<(), BusError> {
+        let val = val as u8;
+
+        match addr {
+            REG_RBR_THR if self.dlab() => self.dll = val,
+            REG_RBR_THR => self.transmit(val),
+            REG_IER if self.dlab() => self.dlm = val,
+            REG_IER => {
+                // enabling the THR empty interrupt raises it right away
+                if val & IER_ETBEI != 0 && self.ier & IER_ETBEI == 0 {
+                    self.thre_pending = true;
+                }
+                self.ier = val & 0x0f;
+            }
+            REG_IIR_FCR => {
+                if val & FCR_CLEAR_RX != 0 {
+                    self.rx_fifo.clear();
+                }
+                self.fcr = val;
+            }
+            REG_LCR => self.lcr = val,
+            REG_MCR => self.mcr = val & 0x1f,
+            REG_SCR => self.scr = val,
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn size(&self) -> u32 {
+        0x1000
+    }
+
+    fn irq_pending(&mut self) -> bool {
+        self.rx_interrupt() || self.thre_interrupt()
+    }
+
+    /// Input still waiting in the backend survives, the FIFO does not.
+    fn reset(&mut self) {
+        self.rx_fifo.clear();
+        self.ier = 0;
+        self.fcr = 0;
+        self.lcr = 0;
+        self.mcr = 0;
+        self.scr = 0;
+        self.dll = 0;
+        self.dlm = 0;
+        self.thre_pending = false;
+        self.next_poll = 0;
+    }
+
+    /// The backend is only polled once the FIFO has drained, and then at
+    /// most every `RX_POLL_INTERVAL` cycles: a poll can be a syscall.
+    fn tick(&mut self, now: u64, _dma: &mut DmaView) {
+        if self.rx_fifo.is_empty() && now >= self.next_poll {
+            self.next_poll = now + RX_POLL_INTERVAL;
+            self.fill_rx_fifo();
+        }
+    }
+}
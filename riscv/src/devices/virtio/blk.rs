@@ -0,0 +1,171 @@
+use std::io;
+
+use crate::devices::virtio::{
+    DescriptorChain, MAX_CHAIN_BYTES, VIRTIO_ID_BLOCK, VirtioDevice, Virtqueue,
+};
+use crate::devices::{BusError, DiskImage, DmaView, SECTOR_SIZE};
+
+const VIRTIO_BLK_F_RO: u64 = 1 << 5;
+const VIRTIO_BLK_F_FLUSH: u64 = 1 << 9;
+
+const VIRTIO_BLK_T_IN: u32 = 0;
+const VIRTIO_BLK_T_OUT: u32 = 1;
+const VIRTIO_BLK_T_FLUSH: u32 = 4;
+const VIRTIO_BLK_T_GET_ID: u32 = 8;
+
+const VIRTIO_BLK_S_OK: u8 = 0;
+const VIRTIO_BLK_S_IOERR: u8 = 1;
+const VIRTIO_BLK_S_UNSUPP: u8 = 2;
+
+/// type, reserved, sector
+const REQUEST_HEADER_SIZE: usize = 16;
+
+const ID_BYTES: usize = 20;
+
+/// virtio-blk on top of a block backend.
+pub struct VirtioBlk {
+    image: DiskImage,
+    capacity: u64,
+}
+
+impl VirtioBlk {
+    pub fn new(path: &str) -> io::Result<Self> {
+        Ok(Self::with_image(DiskImage::open(path)?))
+    }
+
+    pub fn with_image(image: DiskImage) -> Self {
+        let capacity = image.len() / SECTOR_SIZE as u64;
+        Self { image, capacity }
+    }
+
+    fn execute(&mut self, chain: &DescriptorChain, mem: &mut DmaView) -> Result<u32, BusError> {
+        // the lengths come from the driver; refuse before allocating for them
+        if chain.readable_len() > MAX_CHAIN_BYTES || chain.writable_len() > MAX_CHAIN_BYTES {
+            return self.complete(chain, mem, &[], VIRTIO_BLK_S_IOERR);
+        }
+
+        let request = chain.read_all(mem)?;
+        let Some(header) = request.get(..REQUEST_HEADER_SIZE) else {
+            return self.complete(chain, mem, &[], VIRTIO_BLK_S_IOERR);
+        };
+        let kind = u32::from_le_bytes(header[0..4].try_into().unwrap());
+        let sector = u64::from_le_bytes(header[8..16].try_into().unwrap());
+
+        // the last writable byte is the status, everything before it data
+        let data_len = chain.writable_len().saturating_sub(1);
+
+        match kind {
+            VIRTIO_BLK_T_IN => match self.read_at(sector, data_len) {
+                Ok(data) => self.complete(chain, mem, &data, VIRTIO_BLK_S_OK),
+                Err(_) => self.complete(chain, mem, &[], VIRTIO_BLK_S_IOERR),
+            },
+            VIRTIO_BLK_T_OUT => {
+                let data = &request[REQUEST_HEADER_SIZE..];
+                let status = io_status(self.write_at(sector, data));
+                self.complete(chain, mem, &[], status)
+            }
+            VIRTIO_BLK_T_FLUSH => {
+                let status = io_status(self.image.flush());
+                self.complete(chain, mem, &[], status)
+            }
+            VIRTIO_BLK_T_GET_ID => {
+                let mut id = [0; ID_BYTES];
+                let name = b"riscv-virtio-blk";
+                id[..name.len()].copy_from_slice(name);
+                let len = data_len.min(ID_BYTES);
+                self.complete(chain, mem, &id[..len], VIRTIO_BLK_S_OK)
+            }
+            _ => self.complete(chain, mem, &[], VIRTIO_BLK_S_UNSUPP),
+        }
+    }
+
+    fn check_range(&self, sector: u64, len: usize) -> io::Result<()> {
+        let sectors = (len as u64).div_ceil(SECTOR_SIZE as u64);
+        if sector
+            .checked_add(sectors)
+            .is_none_or(|end| end > self.capacity)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "sector out of range",
+            ));
+        }
+        Ok(())
+    }
+
+    fn read_at(&mut self, sector: u64, len: usize) -> io::Result<Vec<u8>> {
+        self.check_range(sector, len)?;
+        let mut data = vec![0; len];
+        if self.image.read_at(sector * SECTOR_SIZE as u64, &mut data)? < len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(data)
+    }
+
+    fn write_at(&mut self, sector: u64, data: &[u8]) -> io::Result<()> {
+        self.check_range(sector, data.len())?;
+        self.image.write_at(sector * SECTOR_SIZE as u64, data)
+    }
+
+    /// Writes `data`, and the status into the last writable byte; returns
+    /// the used length.
+    fn complete(
+        &mut self,
+        chain: &DescriptorChain,
+        mem: &mut DmaView,
+        data: &[u8],
+        status: u8,
+    ) -> Result<u32, BusError> {
+        let Some(status_at) = chain.writable_len().checked_sub(1) else {
+            return Ok(0);
+        };
+        chain.write_all(mem, &data[..data.len().min(status_at)])?;
+        chain.write_at(mem, status_at, &[status])?;
+        Ok(u32::try_from(status_at + 1).unwrap_or(u32::MAX))
+    }
+}
+
+fn io_status(res: io::Result<()>) -> u8 {
+    match res {
+        Ok(()) => VIRTIO_BLK_S_OK,
+        Err(_) => VIRTIO_BLK_S_IOERR,
+    }
+}
+
+impl VirtioDevice for VirtioBlk {
+    fn device_id(&self) -> u32 {
+        VIRTIO_ID_BLOCK
+    }
+
+    fn features(&self) -> u64 {
+        match self.image.is_read_only() {
+            true => VIRTIO_BLK_F_FLUSH | VIRTIO_BLK_F_RO,
+            false => VIRTIO_BLK_F_FLUSH,
+        }
+    }
+
+    fn queue_count(&self) -> usize {
+        1
+    }
+
+    fn config(&self) -> Vec<u8> {
+        // only the capacity field; the optional fields are not offered
+        self.capacity.to_le_bytes().to_vec()
+    }
+
+    fn notify(
+        &mut self,
+        queue: usize,
+        queues: &mut [Virtqueue],
+        mem: &mut DmaView,
+    ) -> Result<bool, BusError> {
+        let queue = &mut queues[queue];
+        let mut used = false;
+        while let Some(chain) = queue.pop(mem)? {
+            let len = self.execute(&chain, mem)?;
+            queue.push_used(mem, chain.head, len)?;
+            used = true;
+        }
+        Ok(used)
+    }
+}
@@ -0,0 +1,250 @@
+use std::collections::VecDeque;
+
+use crate::devices::virtio::{VIRTIO_ID_CONSOLE, VirtioDevice, Virtqueue};
+use crate::devices::{BusError, DmaView, UartBackend};
+
+const VIRTIO_CONSOLE_F_MULTIPORT: u64 = 1 << 1;
+
+// control queue events
+const VIRTIO_CONSOLE_DEVICE_READY: u16 = 0;
+const VIRTIO_CONSOLE_DEVICE_ADD: u16 = 1;
+const VIRTIO_CONSOLE_PORT_READY: u16 = 3;
+const VIRTIO_CONSOLE_CONSOLE_PORT: u16 = 4;
+const VIRTIO_CONSOLE_PORT_OPEN: u16 = 6;
+const VIRTIO_CONSOLE_PORT_NAME: u16 = 7;
+
+const CONTROL_RX_QUEUE: usize = 2;
+const CONTROL_TX_QUEUE: usize = 3;
+
+/// Input bytes buffered per port while the driver has no receive buffers.
+const RX_BUFFER_LIMIT: usize = 4096;
+
+pub struct ConsolePort {
+    name: Option<String>,
+    backend: Box<dyn UartBackend>,
+    pending_rx: VecDeque<u8>,
+}
+
+impl ConsolePort {
+    /// Named ports show up as /dev/vport*p* with a name in sysfs; port 0 is
+    /// always the console.
+    pub fn new(name: Option<&str>, backend: Box<dyn UartBackend>) -> Self {
+        Self {
+            name: name.map(str::to_string),
+            backend,
+            pending_rx: VecDeque::new(),
+        }
+    }
+}
+
+/// virtio-console with one port per backend. Ports beyond the first need
+/// the driver to negotiate VIRTIO_CONSOLE_F_MULTIPORT.
+pub struct VirtioConsole {
+    ports: Vec<ConsolePort>,
+    multiport: bool,
+    /// Device-to-driver control messages waiting for a buffer.
+    control_out: VecDeque<Vec<u8>>,
+}
+
+impl VirtioConsole {
+    pub fn new(ports: Vec<ConsolePort>) -> Self {
+        assert!(!ports.is_empty(), "virtio-console needs at least one port");
+        Self {
+            ports,
+            multiport: false,
+            control_out: VecDeque::new(),
+        }
+    }
+
+    fn rx_queue(port: usize) -> usize {
+        if port == 0 { 0 } else { 2 * (port + 1) }
+    }
+
+    fn port_of_tx_queue(queue: usize) -> Option<usize> {
+        match queue {
+            1 => Some(0),
+            q if q >= 5 && q % 2 == 1 => Some((q - 1) / 2 - 1),
+            _ => None,
+        }
+    }
+
+    fn send_control(&mut self, id: u32, event: u16, value: u16, extra: &[u8]) {
+        let mut msg = Vec::with_capacity(8 + extra.len());
+        msg.extend_from_slice(&id.to_le_bytes());
+        msg.extend_from_slice(&event.to_le_bytes());
+        msg.extend_from_slice(&value.to_le_bytes());
+        msg.extend_from_slice(extra);
+        self.control_out.push_back(msg);
+    }
+
+    fn handle_control(&mut self, msg: &[u8]) {
+        if msg.len() < 8 {
+            return;
+        }
+        let id = u32::from_le_bytes(msg[0..4].try_into().unwrap());
+        let event = u16::from_le_bytes(msg[4..6].try_into().unwrap());
+
+        match event {
+            VIRTIO_CONSOLE_DEVICE_READY => {
+                for port in 0..self.ports.len() {
+                    self.send_control(port as u32, VIRTIO_CONSOLE_DEVICE_ADD, 0, &[]);
+                }
+            }
+            VIRTIO_CONSOLE_PORT_READY => {
+                let Some(port) = self.ports.get(id as usize) else {
+                    return;
+                };
+                let name = port.name.clone();
+                if id == 0 {
+                    self.send_control(id, VIRTIO_CONSOLE_CONSOLE_PORT, 1, &[]);
+                }
+                if let Some(name) = name {
+                    self.send_control(id, VIRTIO_CONSOLE_PORT_NAME, 1, name.as_bytes());
+                }
+                self.send_control(id, VIRTIO_CONSOLE_PORT_OPEN, 1, &[]);
+            }
+            // PORT_OPEN from the guest and the rest need no reaction
+            _ => {}
+        }
+    }
+
+    fn transmit(
+        &mut self,
+        port: usize,
+        queue: &mut Virtqueue,
+        mem: &mut DmaView,
+    ) -> Result<bool, BusError> {
+        let mut used = false;
+        while let Some(chain) = queue.pop(mem)? {
+            for byte in chain.read_all(mem)? {
+                self.ports[port].backend.write(byte);
+            }
+            queue.push_used(mem, chain.head, 0)?;
+            used = true;
+        }
+        Ok(used)
+    }
+
+    fn receive(
+        &mut self,
+        port: usize,
+        queue: &mut Virtqueue,
+        mem: &mut DmaView,
+    ) -> Result<bool, BusError> {
+        let port = &mut self.ports[port];
+        while port.pending_rx.len() < RX_BUFFER_LIMIT {
+            match port.backend.read() {
+                Some(byte) => port.pending_rx.push_back(byte),
+                None => break,
+            }
+        }
+
+        let mut used = false;
+        while !port.pending_rx.is_empty() {
+            let Some(chain) = queue.pop(mem)? else { break };
+            let data: Vec<u8> = port.pending_rx.iter().copied().collect();
+            let len = chain.write_all(mem, &data)?;
+            port.pending_rx.drain(..len);
+            queue.push_used(mem, chain.head, len as u32)?;
+            used = true;
+        }
+        Ok(used)
+    }
+
+    fn flush_control(
+        &mut self,
+        queue: &mut Virtqueue,
+        mem: &mut DmaView,
+    ) -> Result<bool, BusError> {
+        let mut used = false;
+        while !self.control_out.is_empty() {
+            let Some(chain) = queue.pop(mem)? else { break };
+            let msg = self.control_out.pop_front().unwrap();
+            let len = chain.write_all(mem, &msg)?;
+            queue.push_used(mem, chain.head, len as u32)?;
+            used = true;
+        }
+        Ok(used)
+    }
+}
+
+impl VirtioDevice for VirtioConsole {
+    fn device_id(&self) -> u32 {
+        VIRTIO_ID_CONSOLE
+    }
+
+    fn features(&self) -> u64 {
+        VIRTIO_CONSOLE_F_MULTIPORT
+    }
+
+    fn queue_count(&self) -> usize {
+        // port 0 rx/tx, control rx/tx, then rx/tx for each further port
+        2 * (self.ports.len() + 1)
+    }
+
+    fn config(&self) -> Vec<u8> {
+        let mut config = Vec::with_capacity(12);
+        config.extend_from_slice(&0u16.to_le_bytes()); // cols
+        config.extend_from_slice(&0u16.to_le_bytes()); // rows
+        config.extend_from_slice(&(self.ports.len() as u32).to_le_bytes()); // max_nr_ports
+        config.extend_from_slice(&0u32.to_le_bytes()); // emerg_wr
+        config
+    }
+
+    fn write_config(&mut self, offset: u32, data: &[u8]) {
+        // emerg_wr: early output to port 0 before the queues are up
+        if offset == 8
+            && let Some(&byte) = data.first()
+        {
+            self.ports[0].backend.write(byte);
+        }
+    }
+
+    fn activate(&mut self, features: u64) {
+        self.multiport = features & VIRTIO_CONSOLE_F_MULTIPORT != 0;
+    }
+
+    fn reset(&mut self) {
+        self.multiport = false;
+        self.control_out.clear();
+    }
+
+    fn notify(
+        &mut self,
+        queue: usize,
+        queues: &mut [Virtqueue],
+        mem: &mut DmaView,
+    ) -> Result<bool, BusError> {
+        if queue == CONTROL_TX_QUEUE && self.multiport {
+            let mut used = false;
+            while let Some(chain) = queues[queue].pop(mem)? {
+                let msg = chain.read_all(mem)?;
+                self.handle_control(&msg);
+                queues[queue].push_used(mem, chain.head, 0)?;
+                used = true;
+            }
+            return Ok(used | self.flush_control(&mut queues[CONTROL_RX_QUEUE], mem)?);
+        }
+
+        match Self::port_of_tx_queue(queue) {
+            Some(port) if port < self.ports.len() && (port == 0 || self.multiport) => {
+                self.transmit(port, &mut queues[queue], mem)
+            }
+            // receive queues are filled from poll
+            _ => Ok(false),
+        }
+    }
+
+    fn poll(&mut self, queues: &mut [Virtqueue], mem: &mut DmaView) -> Result<bool, BusError> {
+        let mut used = false;
+        if self.multiport {
+            used |= self.flush_control(&mut queues[CONTROL_RX_QUEUE], mem)?;
+        }
+
+        let ports = if self.multiport { self.ports.len() } else { 1 };
+        for port in 0..ports {
+            used |= self.receive(port, &mut queues[Self::rx_queue(port)], mem)?;
+        }
+        Ok(used)
+    }
+}
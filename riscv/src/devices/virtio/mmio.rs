@@ -0,0 +1,272 @@
+use crate::devices::virtio::{VIRTIO_F_VERSION_1, VirtioDevice, Virtqueue};
+use crate::devices::{BusError, Device, DmaView, FdtNode};
+
+// virtio-mmio version 2 register map
+const REG_MAGIC_VALUE: u32 = 0x000; // Read: "virt"
+const REG_VERSION: u32 = 0x004; // Read: 2
+const REG_DEVICE_ID: u32 = 0x008; // Read: VIRTIO_ID_*
+const REG_VENDOR_ID: u32 = 0x00c; // Read
+const REG_DEVICE_FEATURES: u32 = 0x010; // Read: 32 feature bits selected by DEVICE_FEATURES_SEL
+const REG_DEVICE_FEATURES_SEL: u32 = 0x014; // Write
+const REG_DRIVER_FEATURES: u32 = 0x020; // Write: 32 feature bits selected by DRIVER_FEATURES_SEL
+const REG_DRIVER_FEATURES_SEL: u32 = 0x024; // Write
+const REG_QUEUE_SEL: u32 = 0x030; // Write: queue the QUEUE_* registers refer to
+const REG_QUEUE_NUM_MAX: u32 = 0x034; // Read
+const REG_QUEUE_NUM: u32 = 0x038; // Write: queue size
+const REG_QUEUE_READY: u32 = 0x044; // R/W
+const REG_QUEUE_NOTIFY: u32 = 0x050; // Write: queue index with new buffers
+const REG_INTERRUPT_STATUS: u32 = 0x060; // Read
+const REG_INTERRUPT_ACK: u32 = 0x064; // Write: bits to clear in INTERRUPT_STATUS
+const REG_STATUS: u32 = 0x070; // R/W: STATUS_* bits, 0 resets the device
+const REG_QUEUE_DESC_LOW: u32 = 0x080; // Write
+const REG_QUEUE_DESC_HIGH: u32 = 0x084; // Write
+const REG_QUEUE_DRIVER_LOW: u32 = 0x090; // Write: available ring
+const REG_QUEUE_DRIVER_HIGH: u32 = 0x094; // Write
+const REG_QUEUE_DEVICE_LOW: u32 = 0x0a0; // Write: used ring
+const REG_QUEUE_DEVICE_HIGH: u32 = 0x0a4; // Write
+const REG_CONFIG_GENERATION: u32 = 0x0fc; // Read
+const REG_CONFIG: u32 = 0x100; // R/W: device-specific configuration space
+
+const MAGIC_VALUE: u32 = 0x7472_6976;
+const VERSION: u32 = 2;
+const VENDOR_ID: u32 = 0x554d_4551; // "QEMU", which is what drivers usually expect
+
+const QUEUE_NUM_MAX: u16 = 256;
+
+const STATUS_DRIVER_OK: u32 = 4;
+const STATUS_FEATURES_OK: u32 = 8;
+const STATUS_DEVICE_NEEDS_RESET: u32 = 64;
+
+const INTERRUPT_USED_BUFFER: u32 = 1 << 0;
+const INTERRUPT_CONFIG_CHANGE: u32 = 1 << 1;
+
+/// Cycles between two calls of `VirtioDevice::poll`.
+const POLL_INTERVAL: u64 = 1024;
+
+/// virtio-mmio transport: exposes a `VirtioDevice` as a memory-mapped
+/// device. Queue notifications are handled on the next tick, when guest
+/// memory is reachable.
+pub struct VirtioMmio {
+    device: Box<dyn VirtioDevice>,
+    device_features_sel: u32,
+    driver_features: u64,
+    driver_features_sel: u32,
+    queue_sel: u32,
+    queues: Vec<Virtqueue>,
+    status: u32,
+    interrupt_status: u32,
+    config_generation: u32,
+    /// Queues notified since the last tick, each listed once. Devices can
+    /// have more queues than fit in a bitmask.
+    pending_notify: Vec<usize>,
+    /// Cycle at which the device is next polled.
+    next_poll: u64,
+}
+
+impl VirtioMmio {
+    pub fn new(device: Box<dyn VirtioDevice>) -> Self {
+        let queues = vec![Virtqueue::default(); device.queue_count()];
+        Self {
+            device,
+            device_features_sel: 0,
+            driver_features: 0,
+            driver_features_sel: 0,
+            queue_sel: 0,
+            queues,
+            status: 0,
+            interrupt_status: 0,
+            config_generation: 0,
+            pending_notify: Vec::new(),
+            next_poll: 0,
+        }
+    }
+
+    fn device_features(&self) -> u64 {
+        self.device.features() | VIRTIO_F_VERSION_1
+    }
+
+    fn set_status(&mut self, val: u32) {
+        if val == 0 {
+            self.reset();
+            return;
+        }
+
+        if val & STATUS_FEATURES_OK != 0 && self.status & STATUS_FEATURES_OK == 0 {
+            // the driver may only accept what was offered, and must speak 1.0
+            let offered = self.device_features();
+            let accepted = self.driver_features & !offered == 0
+                && self.driver_features & VIRTIO_F_VERSION_1 != 0;
+            if !accepted {
+                self.status = val & !STATUS_FEATURES_OK;
+                return;
+            }
+            self.device.activate(self.driver_features);
+        }
+
+        self.status = val;
+    }
+
+    fn queue(&mut self) -> Option<&mut Virtqueue> {
+        self.queues.get_mut(self.queue_sel as usize)
+    }
+
+    fn set_queue_addr(&mut self, val: u32, high: bool, field: fn(&mut Virtqueue) -> &mut u64) {
+        if let Some(queue) = self.queue() {
+            let addr = field(queue);
+            *addr = if high {
+                (*addr & 0xffff_ffff) | (val as u64) << 32
+            } else {
+                (*addr & !0xffff_ffff) | val as u64
+            };
+        }
+    }
+
+    fn fail(&mut self) {
+        self.status |= STATUS_DEVICE_NEEDS_RESET;
+        self.interrupt_status |= INTERRUPT_CONFIG_CHANGE;
+    }
+}
+
+impl Device for VirtioMmio {
+    fn name(&self) -> &str {
+        "VirtIO"
+    }
+
+    fn fdt_node(&self) -> Option<FdtNode> {
+        Some(FdtNode::new("virtio_mmio", &["virtio,mmio"]).interrupts())
+    }
+
+    fn load(&mut self, addr: u32, size: u8) -> Result<u32, BusError> {
+        if addr >= REG_CONFIG {
+            let config = self.device.config();
+            let start = (addr - REG_CONFIG) as usize;
+            let mut bytes = [0; 4];
+            for (i, byte) in bytes.iter_mut().take(size as usize).enumerate() {
+                *byte = config.get(start + i).copied().unwrap_or(0);
+            }
+            return Ok(u32::from_le_bytes(bytes));
+        }
+
+        let val = match addr {
+            REG_MAGIC_VALUE => MAGIC_VALUE,
+            REG_VERSION => VERSION,
+            REG_DEVICE_ID => self.device.device_id(),
+            REG_VENDOR_ID => VENDOR_ID,
+            REG_DEVICE_FEATURES => match self.device_features_sel {
+                0 => self.device_features() as u32,
+                1 => (self.device_features() >> 32) as u32,
+                _ => 0,
+            },
+            REG_QUEUE_NUM_MAX => match self.queue() {
+                Some(_) => QUEUE_NUM_MAX as u32,
+                None => 0,
+            },
+            REG_QUEUE_READY => self.queue().is_some_and(|queue| queue.ready) as u32,
+            REG_INTERRUPT_STATUS => self.interrupt_status,
+            REG_STATUS => self.status,
+            REG_CONFIG_GENERATION => self.config_generation,
+            _ => 0,
+        };
+
+        Ok(val)
+    }
+
+    fn store(&mut self, addr: u32, size: u8, val: u32) -> Result<(), BusError> {
+        if addr >= REG_CONFIG {
+            let bytes = val.to_le_bytes();
+            self.device
+                .write_config(addr - REG_CONFIG, &bytes[..size as usize]);
+            return Ok(());
+        }
+
+        match addr {
+            REG_DEVICE_FEATURES_SEL => self.device_features_sel = val,
+            REG_DRIVER_FEATURES => match self.driver_features_sel {
+                0 => self.driver_features = (self.driver_features & !0xffff_ffff) | val as u64,
+                1 => {
+                    self.driver_features = (self.driver_features & 0xffff_ffff) | (val as u64) << 32
+                }
+                _ => {}
+            },
+            REG_DRIVER_FEATURES_SEL => self.driver_features_sel = val,
+            REG_QUEUE_SEL => self.queue_sel = val,
+            REG_QUEUE_NUM => {
+                if let Some(queue) = self.queue() {
+                    queue.size = (val as u16).min(QUEUE_NUM_MAX);
+                }
+            }
+            REG_QUEUE_READY => {
+                if let Some(queue) = self.queue() {
+                    queue.ready = val & 1 != 0;
+                }
+            }
+            REG_QUEUE_NOTIFY
+                if (val as usize) < self.queues.len()
+                    && !self.pending_notify.contains(&(val as usize)) =>
+            {
+                self.pending_notify.push(val as usize);
+            }
+            REG_INTERRUPT_ACK => self.interrupt_status &= !val,
+            REG_STATUS => self.set_status(val),
+            REG_QUEUE_DESC_LOW => self.set_queue_addr(val, false, |q| &mut q.desc_addr),
+            REG_QUEUE_DESC_HIGH => self.set_queue_addr(val, true, |q| &mut q.desc_addr),
+            REG_QUEUE_DRIVER_LOW => self.set_queue_addr(val, false, |q| &mut q.avail_addr),
+            REG_QUEUE_DRIVER_HIGH => self.set_queue_addr(val, true, |q| &mut q.avail_addr),
+            REG_QUEUE_DEVICE_LOW => self.set_queue_addr(val, false, |q| &mut q.used_addr),
+            REG_QUEUE_DEVICE_HIGH => self.set_queue_addr(val, true, |q| &mut q.used_addr),
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn size(&self) -> u32 {
+        0x1000
+    }
+
+    fn irq_pending(&mut self) -> bool {
+        self.interrupt_status != 0
+    }
+
+    /// Notified queues are served on the next tick, but the device is only
+    /// polled every `POLL_INTERVAL` cycles: a poll can be a syscall.
+    fn tick(&mut self, now: u64, dma: &mut DmaView) {
+        if self.status & STATUS_DRIVER_OK == 0 || self.status & STATUS_DEVICE_NEEDS_RESET != 0 {
+            return;
+        }
+
+        let mut used = false;
+        for queue in std::mem::take(&mut self.pending_notify) {
+            match self.device.notify(queue, &mut self.queues, dma) {
+                Ok(any) => used |= any,
+                Err(_) => return self.fail(),
+            }
+        }
+
+        if now >= self.next_poll {
+            self.next_poll = now + POLL_INTERVAL;
+            match self.device.poll(&mut self.queues, dma) {
+                Ok(any) => used |= any,
+                Err(_) => return self.fail(),
+            }
+        }
+
+        if used {
+            self.interrupt_status |= INTERRUPT_USED_BUFFER;
+        }
+    }
+
+    /// Also what the driver gets by writing 0 to the status register.
+    fn reset(&mut self) {
+        self.device.reset();
+        self.queues.iter_mut().for_each(Virtqueue::reset);
+        self.device_features_sel = 0;
+        self.driver_features = 0;
+        self.driver_features_sel = 0;
+        self.queue_sel = 0;
+        self.status = 0;
+        self.interrupt_status = 0;
+        self.pending_notify.clear();
+        self.next_poll = 0;
+    }
+}
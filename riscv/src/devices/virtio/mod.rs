@@ -0,0 +1,63 @@
+pub mod blk;
+pub mod console;
+pub mod mmio;
+pub mod net;
+pub mod p9;
+pub mod queue;
+pub mod rng;
+
+pub use blk::*;
+pub use console::*;
+pub use mmio::*;
+pub use net::*;
+pub use p9::Virtio9p;
+pub use queue::*;
+pub use rng::*;
+
+use crate::devices::{BusError, DmaView};
+
+pub const VIRTIO_ID_NET: u32 = 1;
+pub const VIRTIO_ID_BLOCK: u32 = 2;
+pub const VIRTIO_ID_CONSOLE: u32 = 3;
+pub const VIRTIO_ID_RNG: u32 = 4;
+pub const VIRTIO_ID_9P: u32 = 9;
+
+pub const VIRTIO_F_VERSION_1: u64 = 1 << 32;
+
+/// A virtio device model, independent of the transport it is attached
+/// through.
+pub trait VirtioDevice {
+    fn device_id(&self) -> u32;
+
+    /// Device-specific feature bits; the transport adds VIRTIO_F_VERSION_1.
+    fn features(&self) -> u64;
+
+    fn queue_count(&self) -> usize;
+
+    /// Contents of the device configuration space.
+    fn config(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    fn write_config(&mut self, _offset: u32, _data: &[u8]) {}
+
+    /// Called once the driver has accepted `features`.
+    fn activate(&mut self, _features: u64) {}
+
+    fn reset(&mut self) {}
+
+    /// Processes the buffers the driver made available on `queues[queue]`.
+    /// Returns whether any were used, which raises the interrupt.
+    fn notify(
+        &mut self,
+        queue: usize,
+        queues: &mut [Virtqueue],
+        mem: &mut DmaView,
+    ) -> Result<bool, BusError>;
+
+    /// Called every 1024 cycles or so while the driver is up, for devices
+    /// that produce data on their own (e.g. console input).
+    fn poll(&mut self, _queues: &mut [Virtqueue], _mem: &mut DmaView) -> Result<bool, BusError> {
+        Ok(false)
+    }
+}
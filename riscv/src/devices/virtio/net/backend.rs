@@ -0,0 +1,230 @@
+use std::collections::VecDeque;
+use std::io::{self, ErrorKind, Read, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+
+use crate::devices::virtio::net::pcap::{PcapWriter, read_pcap};
+
+/// Host side of a network link, carrying whole Ethernet frames. `recv`
+/// must never block: it returns `None` when nothing has arrived.
+pub trait NetBackend {
+    fn send(&mut self, frame: &[u8]);
+
+    fn recv(&mut self) -> Option<Vec<u8>>;
+}
+
+/// Hands every transmitted frame straight back to the guest.
+#[derive(Default)]
+pub struct Loopback {
+    frames: VecDeque<Vec<u8>>,
+}
+
+impl Loopback {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl NetBackend for Loopback {
+    fn send(&mut self, frame: &[u8]) {
+        self.frames.push_back(frame.to_vec());
+    }
+
+    fn recv(&mut self) -> Option<Vec<u8>> {
+        self.frames.pop_front()
+    }
+}
+
+/// Bytes of unsent frames kept while the peer isn't reading; more are
+/// dropped, like on a congested link.
+const CABLE_TX_LIMIT: usize = 1 << 20;
+
+const CABLE_FRAME_LIMIT: usize = 65535;
+
+/// A virtual cable between two emulators over a Unix domain socket. Each
+/// frame is sent as a 32-bit big-endian length followed by the frame, the
+/// same framing as QEMU's stream socket netdev. One side listens, the
+/// other connects; frames are dropped while the cable is unplugged.
+pub struct CableBackend {
+    listener: Option<(UnixListener, PathBuf)>,
+    stream: Option<UnixStream>,
+    tx: Vec<u8>,
+    rx: Vec<u8>,
+}
+
+impl CableBackend {
+    pub fn listen(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        // a stale socket from a previous run would make bind fail
+        if path.exists() {
+            std::fs::remove_file(&path)?;
+        }
+
+        let listener = UnixListener::bind(&path)?;
+        listener.set_nonblocking(true)?;
+        eprintln!("NET: listening on {}", path.display());
+
+        Ok(Self {
+            listener: Some((listener, path)),
+            stream: None,
+            tx: Vec::new(),
+            rx: Vec::new(),
+        })
+    }
+
+    pub fn connect(path: impl AsRef<Path>) -> io::Result<Self> {
+        let stream = UnixStream::connect(path)?;
+        stream.set_nonblocking(true)?;
+
+        Ok(Self {
+            listener: None,
+            stream: Some(stream),
+            tx: Vec::new(),
+            rx: Vec::new(),
+        })
+    }
+
+    fn stream(&mut self) -> Option<&mut UnixStream> {
+        if self.stream.is_none()
+            && let Some((listener, _)) = &self.listener
+            && let Ok((stream, _)) = listener.accept()
+            && stream.set_nonblocking(true).is_ok()
+        {
+            self.stream = Some(stream);
+        }
+        self.stream.as_mut()
+    }
+
+    fn unplug(&mut self) {
+        self.stream = None;
+        self.tx.clear();
+        self.rx.clear();
+    }
+
+    /// Writes as much of the pending output as the socket takes.
+    fn flush(&mut self) {
+        if self.stream().is_none() {
+            return;
+        }
+        while let Some(stream) = &mut self.stream
+            && !self.tx.is_empty()
+        {
+            match stream.write(&self.tx) {
+                Ok(0) => return self.unplug(),
+                Ok(n) => {
+                    self.tx.drain(..n);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(_) => return self.unplug(),
+            }
+        }
+    }
+
+    fn fill(&mut self) {
+        let mut buf = [0; 4096];
+        loop {
+            let Some(stream) = self.stream() else { return };
+            match stream.read(&mut buf) {
+                // eof: the peer hung up
+                Ok(0) => return self.unplug(),
+                Ok(n) => self.rx.extend_from_slice(&buf[..n]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(_) => return self.unplug(),
+            }
+        }
+    }
+}
+
+impl NetBackend for CableBackend {
+    fn send(&mut self, frame: &[u8]) {
+        if self.stream().is_none() || self.tx.len() + frame.len() > CABLE_TX_LIMIT {
+            return;
+        }
+        self.tx
+            .extend_from_slice(&(frame.len() as u32).to_be_bytes());
+        self.tx.extend_from_slice(frame);
+        self.flush();
+    }
+
+    fn recv(&mut self) -> Option<Vec<u8>> {
+        self.flush();
+        if self.rx.len() < 4 {
+            self.fill();
+        }
+
+        let len = u32::from_be_bytes(self.rx.get(..4)?.try_into().unwrap()) as usize;
+        if len > CABLE_FRAME_LIMIT {
+            // the stream is out of sync and can't be trusted any more
+            self.unplug();
+            return None;
+        }
+        if self.rx.len() < 4 + len {
+            self.fill();
+        }
+        let frame = self.rx.get(4..4 + len)?.to_vec();
+        self.rx.drain(..4 + len);
+        Some(frame)
+    }
+}
+
+impl Drop for CableBackend {
+    fn drop(&mut self) {
+        if let Some((_, path)) = &self.listener {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// Feeds the frames of a pcap file to the guest, in order and as fast as
+/// the driver takes them; timestamps are ignored, so runs are repeatable.
+/// Transmitted frames go nowhere.
+pub struct PcapReplay {
+    frames: VecDeque<Vec<u8>>,
+}
+
+impl PcapReplay {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self {
+            frames: read_pcap(path)?.into(),
+        })
+    }
+}
+
+impl NetBackend for PcapReplay {
+    fn send(&mut self, _frame: &[u8]) {}
+
+    fn recv(&mut self) -> Option<Vec<u8>> {
+        self.frames.pop_front()
+    }
+}
+
+/// Captures the traffic of another backend in both directions.
+pub struct PcapRecorder {
+    inner: Box<dyn NetBackend>,
+    pcap: PcapWriter,
+}
+
+impl PcapRecorder {
+    pub fn new(inner: Box<dyn NetBackend>, path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self {
+            inner,
+            pcap: PcapWriter::create(path)?,
+        })
+    }
+}
+
+impl NetBackend for PcapRecorder {
+    fn send(&mut self, frame: &[u8]) {
+        // a failed capture must not take the link down
+        let _ = self.pcap.write_frame(frame);
+        self.inner.send(frame);
+    }
+
+    fn recv(&mut self) -> Option<Vec<u8>> {
+        let frame = self.inner.recv()?;
+        let _ = self.pcap.write_frame(&frame);
+        Some(frame)
+    }
+}
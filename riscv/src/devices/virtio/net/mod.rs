@@ -0,0 +1,127 @@
+//! virtio-net with host-local backends: no host network is ever touched,
+//! frames go to another emulator, a loopback or pcap files.
+
+pub mod backend;
+pub mod pcap;
+
+pub use backend::*;
+pub use pcap::*;
+
+use std::collections::VecDeque;
+
+use crate::devices::virtio::{VIRTIO_ID_NET, VirtioDevice, Virtqueue};
+use crate::devices::{BusError, DmaView};
+
+const VIRTIO_NET_F_MAC: u64 = 1 << 5;
+const VIRTIO_NET_F_STATUS: u64 = 1 << 16;
+
+const VIRTIO_NET_S_LINK_UP: u16 = 1;
+
+const RX_QUEUE: usize = 0;
+const TX_QUEUE: usize = 1;
+
+/// struct virtio_net_hdr; with VIRTIO_F_VERSION_1 it always includes
+/// num_buffers.
+const NET_HEADER_SIZE: usize = 12;
+
+/// Received frames buffered while the driver has no receive buffers; more
+/// are dropped.
+const RX_FRAME_LIMIT: usize = 256;
+
+pub const DEFAULT_MAC: [u8; 6] = [0x52, 0x54, 0x00, 0x12, 0x34, 0x56];
+
+/// virtio-net without offloads: one receive and one transmit queue.
+pub struct VirtioNet {
+    mac: [u8; 6],
+    backend: Box<dyn NetBackend>,
+    pending_rx: VecDeque<Vec<u8>>,
+}
+
+impl VirtioNet {
+    pub fn new(mac: [u8; 6], backend: Box<dyn NetBackend>) -> Self {
+        Self {
+            mac,
+            backend,
+            pending_rx: VecDeque::new(),
+        }
+    }
+
+    fn transmit(&mut self, queue: &mut Virtqueue, mem: &mut DmaView) -> Result<bool, BusError> {
+        let mut used = false;
+        while let Some(chain) = queue.pop(mem)? {
+            let packet = chain.read_all(mem)?;
+            if let Some(frame) = packet.get(NET_HEADER_SIZE..) {
+                self.backend.send(frame);
+            }
+            queue.push_used(mem, chain.head, 0)?;
+            used = true;
+        }
+        Ok(used)
+    }
+
+    fn receive(&mut self, queue: &mut Virtqueue, mem: &mut DmaView) -> Result<bool, BusError> {
+        while let Some(frame) = self.backend.recv() {
+            if self.pending_rx.len() < RX_FRAME_LIMIT {
+                self.pending_rx.push_back(frame);
+            }
+        }
+
+        let mut used = false;
+        while !self.pending_rx.is_empty() {
+            let Some(chain) = queue.pop(mem)? else { break };
+            let frame = self.pending_rx.pop_front().unwrap();
+
+            let mut packet = vec![0; NET_HEADER_SIZE];
+            packet[10..12].copy_from_slice(&1u16.to_le_bytes()); // num_buffers
+            packet.extend_from_slice(&frame);
+            // a frame that doesn't fit is truncated, the driver drops it
+            let len = chain.write_all(mem, &packet)?;
+            queue.push_used(mem, chain.head, len as u32)?;
+            used = true;
+        }
+        Ok(used)
+    }
+}
+
+impl VirtioDevice for VirtioNet {
+    fn device_id(&self) -> u32 {
+        VIRTIO_ID_NET
+    }
+
+    fn features(&self) -> u64 {
+        VIRTIO_NET_F_MAC | VIRTIO_NET_F_STATUS
+    }
+
+    fn queue_count(&self) -> usize {
+        2
+    }
+
+    fn config(&self) -> Vec<u8> {
+        let mut config = Vec::with_capacity(8);
+        config.extend_from_slice(&self.mac);
+        config.extend_from_slice(&VIRTIO_NET_S_LINK_UP.to_le_bytes());
+        config
+    }
+
+    fn reset(&mut self) {
+        self.pending_rx.clear();
+    }
+
+    fn notify(
+        &mut self,
+        queue: usize,
+        queues: &mut [Virtqueue],
+        mem: &mut DmaView,
+    ) -> Result<bool, BusError> {
+        match queue {
+            TX_QUEUE => self.transmit(&mut queues[TX_QUEUE], mem),
+            // new receive buffers, maybe for frames that were waiting
+            RX_QUEUE => self.receive(&mut queues[RX_QUEUE], mem),
+            _ => Ok(false),
+        }
+    }
+
+    fn poll(&mut self, queues: &mut [Virtqueue], mem: &mut DmaView) -> Result<bool, BusError> {
+        self.receive(&mut queues[RX_QUEUE], mem)
+    }
+}
@@ -0,0 +1,89 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const MAGIC_MICROS: u32 = 0xa1b2_c3d4;
+const MAGIC_NANOS: u32 = 0xa1b2_3c4d;
+const LINKTYPE_ETHERNET: u32 = 1;
+const SNAPLEN: u32 = 65535;
+
+const FILE_HEADER_SIZE: usize = 24;
+const RECORD_HEADER_SIZE: usize = 16;
+
+/// Appends Ethernet frames to a classic pcap file, stamped with host time.
+/// Every frame is flushed, so the capture is complete even if the emulator
+/// exits without unwinding.
+pub struct PcapWriter {
+    out: BufWriter<File>,
+}
+
+impl PcapWriter {
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(&MAGIC_MICROS.to_le_bytes())?;
+        out.write_all(&2u16.to_le_bytes())?; // version 2.4
+        out.write_all(&4u16.to_le_bytes())?;
+        out.write_all(&0i32.to_le_bytes())?; // GMT
+        out.write_all(&0u32.to_le_bytes())?; // timestamp accuracy
+        out.write_all(&SNAPLEN.to_le_bytes())?;
+        out.write_all(&LINKTYPE_ETHERNET.to_le_bytes())?;
+        out.flush()?;
+        Ok(Self { out })
+    }
+
+    pub fn write_frame(&mut self, frame: &[u8]) -> io::Result<()> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let len = frame.len() as u32;
+        let captured = len.min(SNAPLEN);
+
+        self.out.write_all(&(now.as_secs() as u32).to_le_bytes())?;
+        self.out.write_all(&now.subsec_micros().to_le_bytes())?;
+        self.out.write_all(&captured.to_le_bytes())?;
+        self.out.write_all(&len.to_le_bytes())?;
+        self.out.write_all(&frame[..captured as usize])?;
+        self.out.flush()
+    }
+}
+
+/// Reads all frames of a pcap file, in either byte order and with micro-
+/// or nanosecond timestamps. Timestamps are not kept.
+pub fn read_pcap(path: impl AsRef<Path>) -> io::Result<Vec<Vec<u8>>> {
+    let data = fs::read(path)?;
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+    let header = data
+        .get(..FILE_HEADER_SIZE)
+        .ok_or_else(|| invalid("truncated pcap header"))?;
+    let magic = u32::from_le_bytes(header[0..4].try_into().unwrap());
+    let swapped = match magic {
+        MAGIC_MICROS | MAGIC_NANOS => false,
+        _ if magic.swap_bytes() == MAGIC_MICROS || magic.swap_bytes() == MAGIC_NANOS => true,
+        _ => return Err(invalid("not a pcap file")),
+    };
+    let read_u32 = |bytes: &[u8]| {
+        let val = u32::from_le_bytes(bytes.try_into().unwrap());
+        if swapped { val.swap_bytes() } else { val }
+    };
+    if read_u32(&header[20..24]) != LINKTYPE_ETHERNET {
+        return Err(invalid("pcap link type is not Ethernet"));
+    }
+
+    let mut frames = Vec::new();
+    let mut pos = FILE_HEADER_SIZE;
+    while pos < data.len() {
+        let record = data
+            .get(pos..pos + RECORD_HEADER_SIZE)
+            .ok_or_else(|| invalid("truncated pcap record"))?;
+        let len = read_u32(&record[8..12]) as usize;
+        pos += RECORD_HEADER_SIZE;
+        let frame = data
+            .get(pos..pos + len)
+            .ok_or_else(|| invalid("truncated pcap record"))?;
+        frames.push(frame.to_vec());
+        pos += len;
+    }
+    Ok(frames)
+}
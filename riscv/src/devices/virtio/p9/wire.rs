@@ -0,0 +1,113 @@
+//! 9P message encoding: little-endian integers, strings prefixed with a
+//! 16-bit length.
+
+use crate::devices::virtio::p9::{EINVAL, Errno};
+
+pub const QID_SIZE: usize = 13;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Qid {
+    pub kind: u8,
+    pub version: u32,
+    pub path: u64,
+}
+
+pub struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Errno> {
+        let end = self.pos.checked_add(len).ok_or(EINVAL)?;
+        let bytes = self.data.get(self.pos..end).ok_or(EINVAL)?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, Errno> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> Result<u16, Errno> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, Errno> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, Errno> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8], Errno> {
+        self.take(len)
+    }
+
+    pub fn string(&mut self) -> Result<String, Errno> {
+        let len = self.u16()? as usize;
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| EINVAL)
+    }
+}
+
+#[derive(Default)]
+pub struct Writer {
+    data: Vec<u8>,
+}
+
+impl Writer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn u8(&mut self, val: u8) -> &mut Self {
+        self.data.push(val);
+        self
+    }
+
+    pub fn u16(&mut self, val: u16) -> &mut Self {
+        self.data.extend_from_slice(&val.to_le_bytes());
+        self
+    }
+
+    pub fn u32(&mut self, val: u32) -> &mut Self {
+        self.data.extend_from_slice(&val.to_le_bytes());
+        self
+    }
+
+    pub fn u64(&mut self, val: u64) -> &mut Self {
+        self.data.extend_from_slice(&val.to_le_bytes());
+        self
+    }
+
+    pub fn bytes(&mut self, val: &[u8]) -> &mut Self {
+        self.data.extend_from_slice(val);
+        self
+    }
+
+    pub fn string(&mut self, val: &str) -> &mut Self {
+        self.u16(val.len() as u16).bytes(val.as_bytes())
+    }
+
+    pub fn qid(&mut self, qid: Qid) -> &mut Self {
+        self.u8(qid.kind).u32(qid.version).u64(qid.path)
+    }
+
+    pub fn into_inner(self) -> Vec<u8> {
+        self.data
+    }
+}
@@ -0,0 +1,180 @@
+use crate::devices::{BusError, DmaView};
+
+pub const VIRTQ_DESC_F_NEXT: u16 = 1;
+pub const VIRTQ_DESC_F_WRITE: u16 = 2;
+
+const DESC_SIZE: u64 = 16;
+
+/// Most bytes a device gathers from, or prepares for, a single chain. The
+/// descriptor lengths come from the driver and could add up to terabytes.
+pub const MAX_CHAIN_BYTES: usize = 4 << 20;
+
+/// A split virtqueue living in guest memory, as configured by the driver.
+#[derive(Debug, Default, Clone)]
+pub struct Virtqueue {
+    pub size: u16,
+    pub ready: bool,
+    pub desc_addr: u64,
+    pub avail_addr: u64,
+    pub used_addr: u64,
+    last_avail_idx: u16,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Descriptor {
+    pub addr: u64,
+    pub len: u32,
+    pub flags: u16,
+    pub next: u16,
+}
+
+impl Descriptor {
+    pub fn is_writable(&self) -> bool {
+        self.flags & VIRTQ_DESC_F_WRITE != 0
+    }
+}
+
+/// The buffers of one request, device-readable ones first.
+#[derive(Debug)]
+pub struct DescriptorChain {
+    pub head: u16,
+    pub descriptors: Vec<Descriptor>,
+}
+
+impl DescriptorChain {
+    pub fn readable(&self) -> impl Iterator<Item = &Descriptor> {
+        self.descriptors.iter().filter(|desc| !desc.is_writable())
+    }
+
+    pub fn writable(&self) -> impl Iterator<Item = &Descriptor> {
+        self.descriptors.iter().filter(|desc| desc.is_writable())
+    }
+
+    pub fn readable_len(&self) -> usize {
+        self.readable().map(|desc| desc.len as usize).sum()
+    }
+
+    pub fn writable_len(&self) -> usize {
+        self.writable().map(|desc| desc.len as usize).sum()
+    }
+
+    /// Gathers all device-readable buffers. A chain holding more than
+    /// MAX_CHAIN_BYTES is refused like one with a bad address.
+    pub fn read_all(&self, mem: &mut DmaView) -> Result<Vec<u8>, BusError> {
+        if self.readable_len() > MAX_CHAIN_BYTES {
+            let addr = self.readable().next().map_or(0, |desc| desc.addr);
+            return Err(BusError::LoadAccessFault(addr));
+        }
+
+        let mut data = vec![0; self.readable_len()];
+        let mut pos = 0;
+        for desc in self.readable() {
+            let len = desc.len as usize;
+            mem.read(desc.addr, &mut data[pos..pos + len])?;
+            pos += len;
+        }
+        Ok(data)
+    }
+
+    /// Scatters `data` over the device-writable buffers and returns how many
+    /// bytes fit.
+    pub fn write_all(&self, mem: &mut DmaView, data: &[u8]) -> Result<usize, BusError> {
+        self.write_at(mem, 0, data)
+    }
+
+    /// Like `write_all`, starting `offset` bytes into the writable buffers.
+    pub fn write_at(
+        &self,
+        mem: &mut DmaView,
+        offset: usize,
+        data: &[u8],
+    ) -> Result<usize, BusError> {
+        let mut skip = offset;
+        let mut pos = 0;
+        for desc in self.writable() {
+            if pos == data.len() {
+                break;
+            }
+            let desc_len = desc.len as usize;
+            if skip >= desc_len {
+                skip -= desc_len;
+                continue;
+            }
+            let len = (desc_len - skip).min(data.len() - pos);
+            mem.write(desc.addr + skip as u64, &data[pos..pos + len])?;
+            pos += len;
+            skip = 0;
+        }
+        Ok(pos)
+    }
+}
+
+impl Virtqueue {
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// Takes the next chain the driver made available, if any.
+    pub fn pop(&mut self, mem: &mut DmaView) -> Result<Option<DescriptorChain>, BusError> {
+        if !self.ready || self.size == 0 {
+            return Ok(None);
+        }
+
+        let avail_idx = read_u16(mem, self.avail_addr + 2)?;
+        if avail_idx == self.last_avail_idx {
+            return Ok(None);
+        }
+
+        let slot = (self.last_avail_idx % self.size) as u64;
+        let head = read_u16(mem, self.avail_addr + 4 + 2 * slot)?;
+        self.last_avail_idx = self.last_avail_idx.wrapping_add(1);
+
+        self.chain(mem, head).map(Some)
+    }
+
+    fn chain(&self, mem: &mut DmaView, head: u16) -> Result<DescriptorChain, BusError> {
+        let mut descriptors = Vec::new();
+        let mut idx = head;
+
+        // a chain can't be longer than the table; stop on loops
+        for _ in 0..self.size {
+            let addr = self.desc_addr + (idx % self.size) as u64 * DESC_SIZE;
+            let mut raw = [0; DESC_SIZE as usize];
+            mem.read(addr, &mut raw)?;
+
+            let desc = Descriptor {
+                addr: u64::from_le_bytes(raw[0..8].try_into().unwrap()),
+                len: u32::from_le_bytes(raw[8..12].try_into().unwrap()),
+                flags: u16::from_le_bytes(raw[12..14].try_into().unwrap()),
+                next: u16::from_le_bytes(raw[14..16].try_into().unwrap()),
+            };
+            descriptors.push(desc);
+
+            if desc.flags & VIRTQ_DESC_F_NEXT == 0 {
+                break;
+            }
+            idx = desc.next;
+        }
+
+        Ok(DescriptorChain { head, descriptors })
+    }
+
+    /// Returns a chain to the driver with `len` bytes written to it.
+    pub fn push_used(&mut self, mem: &mut DmaView, head: u16, len: u32) -> Result<(), BusError> {
+        let used_idx = read_u16(mem, self.used_addr + 2)?;
+        let slot = (used_idx % self.size) as u64;
+
+        let mut elem = [0; 8];
+        elem[0..4].copy_from_slice(&(head as u32).to_le_bytes());
+        elem[4..8].copy_from_slice(&len.to_le_bytes());
+        mem.write(self.used_addr + 4 + 8 * slot, &elem)?;
+
+        mem.write(self.used_addr + 2, &used_idx.wrapping_add(1).to_le_bytes())
+    }
+}
+
+fn read_u16(mem: &mut DmaView, addr: u64) -> Result<u16, BusError> {
+    let mut buf = [0; 2];
+    mem.read(addr, &mut buf)?;
+    Ok(u16::from_le_bytes(buf))
+}
@@ -0,0 +1,89 @@
+use std::fs::File;
+use std::io::{self, Read};
+
+use crate::devices::virtio::{VIRTIO_ID_RNG, VirtioDevice, Virtqueue};
+use crate::devices::{BusError, DmaView};
+
+/// Most entropy handed out per chain; the driver sees the shorter used
+/// length and asks again.
+const MAX_FILL: usize = 64 * 1024;
+
+enum EntropySource {
+    Os(File),
+    /// SplitMix64, so test runs can be reproduced from a seed.
+    Seeded(u64),
+}
+
+impl EntropySource {
+    fn fill(&mut self, buf: &mut [u8]) {
+        match self {
+            EntropySource::Os(file) => {
+                if file.read_exact(buf).is_err() {
+                    buf.fill(0);
+                }
+            }
+            EntropySource::Seeded(state) => {
+                for chunk in buf.chunks_mut(8) {
+                    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+                    let mut z = *state;
+                    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+                    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+                    z ^= z >> 31;
+                    chunk.copy_from_slice(&z.to_le_bytes()[..chunk.len()]);
+                }
+            }
+        }
+    }
+}
+
+pub struct VirtioRng {
+    source: EntropySource,
+}
+
+impl VirtioRng {
+    /// Entropy from the host's /dev/urandom.
+    pub fn from_os() -> io::Result<Self> {
+        Ok(Self {
+            source: EntropySource::Os(File::open("/dev/urandom")?),
+        })
+    }
+
+    /// A deterministic stream: the same seed yields the same bytes.
+    pub fn seeded(seed: u64) -> Self {
+        Self {
+            source: EntropySource::Seeded(seed),
+        }
+    }
+}
+
+impl VirtioDevice for VirtioRng {
+    fn device_id(&self) -> u32 {
+        VIRTIO_ID_RNG
+    }
+
+    fn features(&self) -> u64 {
+        0
+    }
+
+    fn queue_count(&self) -> usize {
+        1
+    }
+
+    fn notify(
+        &mut self,
+        queue: usize,
+        queues: &mut [Virtqueue],
+        mem: &mut DmaView,
+    ) -> Result<bool, BusError> {
+        let queue = &mut queues[queue];
+        let mut used = false;
+        while let Some(chain) = queue.pop(mem)? {
+            let mut data = vec![0; chain.writable_len().min(MAX_FILL)];
+            self.source.fill(&mut data);
+            let len = chain.write_all(mem, &data)?;
+            queue.push_used(mem, chain.head, len as u32)?;
+            used = true;
+        }
+        Ok(used)
+    }
+}
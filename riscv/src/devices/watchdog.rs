@@ -0,0 +1,115 @@
+use crate::devices::{BusError, Device, DmaView, FdtNode, PowerControl, PowerRequest};
+
+const REG_CTRL: u32 = 0x00; // R/W: 1 = running, loads the counter when set
+const REG_TIMEOUT: u32 = 0x04; // R/W: cycles between refreshes
+const REG_KICK: u32 = 0x08; // Write: WATCHDOG_KICK reloads the counter
+const REG_REMAINING: u32 = 0x0C; // Read: cycles left before the watchdog bites
+
+const CTRL_ENABLE: u32 = 1 << 0;
+
+/// Value the guest writes to REG_KICK; anything else is ignored.
+pub const WATCHDOG_KICK: u32 = 0x4b49_434b; // "KICK"
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchdogAction {
+    Reset,
+    /// Stop with a dump of the hart's state.
+    Halt,
+}
+
+/// Cycle-counting watchdog: once enabled, the guest has to kick it at
+/// least every `timeout` cycles or the machine is reset or halted. It
+/// stops after biting and has to be enabled again.
+pub struct Watchdog {
+    power: PowerControl,
+    action: WatchdogAction,
+    enabled: bool,
+    timeout: u32,
+    deadline: u64,
+    /// Current cycle, counted by the watchdog itself: the guest can write
+    /// mcycle, and must not be able to wind the clock back.
+    now: u64,
+    /// Ticks seen since the watchdog was created.
+    ticks: u64,
+}
+
+impl Watchdog {
+    pub fn new(power: PowerControl, action: WatchdogAction) -> Self {
+        Self {
+            power,
+            action,
+            enabled: false,
+            timeout: 0,
+            deadline: 0,
+            now: 0,
+            ticks: 0,
+        }
+    }
+
+    fn reload(&mut self) {
+        self.deadline = self.now + self.timeout as u64;
+    }
+}
+
+impl Device for Watchdog {
+    fn name(&self) -> &str {
+        "Watchdog"
+    }
+
+    fn fdt_node(&self) -> Option<FdtNode> {
+        Some(FdtNode::new("watchdog", &["osv,watchdog"]))
+    }
+
+    fn load(&mut self, addr: u32, _size: u8) -> Result<u32, BusError> {
+        match addr {
+            REG_CTRL => Ok(self.enabled as u32),
+            REG_TIMEOUT => Ok(self.timeout),
+            REG_REMAINING if self.enabled => Ok(self.deadline.saturating_sub(self.now) as u32),
+            REG_REMAINING => Ok(0),
+            _ => Err(BusError::LoadAccessFault(addr as u64)),
+        }
+    }
+
+    fn store(&mut self, addr: u32, _size: u8, val: u32) -> Result<(), BusError> {
+        match addr {
+            REG_CTRL => {
+                let enable = val & CTRL_ENABLE != 0;
+                if enable && !self.enabled {
+                    self.reload();
+                }
+                self.enabled = enable;
+            }
+            REG_TIMEOUT => self.timeout = val,
+            REG_KICK if val == WATCHDOG_KICK => self.reload(),
+            REG_KICK => {}
+            _ => return Err(BusError::StoreAccessFault(addr as u64)),
+        }
+        Ok(())
+    }
+
+    fn size(&self) -> u32 {
+        0x1000
+    }
+
+    fn reset(&mut self) {
+        self.enabled = false;
+        self.timeout = 0;
+        self.deadline = 0;
+    }
+
+    fn tick(&mut self, now: u64, _dma: &mut DmaView) {
+        self.now = self.ticks;
+        self.ticks += 1;
+        if !self.enabled || self.now < self.deadline {
+            return;
+        }
+
+        self.enabled = false;
+        eprintln!("\nWatchdog expired at cycle {}", now);
+        let request = match self.action {
+            WatchdogAction::Reset => PowerRequest::Reset,
+            WatchdogAction::Halt => PowerRequest::Halt,
+        };
+        self.power.request(request);
+    }
+}
@@ -1,4 +1,8 @@
 pub mod privileged;
 pub mod rv32i;
 pub mod rv32m;
+pub mod rv64i;
+pub mod rvv;
+pub mod zicond;
 pub mod zicsr;
+pub mod zkn;
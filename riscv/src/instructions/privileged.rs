@@ -41,6 +41,10 @@ pub fn exec_privileged(cpu: &mut Cpu, instr: Instr) -> Result<(), Trap> {
 
             Ok(())
         }
+        0x105 => {
+            // WFI: implemented as a nop, the interrupt is taken on a later step
+            Ok(())
+        }
         _ => Err(Trap::Exception(Exception::IllegalInstruction(instr))),
     }
 }
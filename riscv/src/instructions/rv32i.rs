@@ -1,23 +1,44 @@
 use crate::{
     cpu::Cpu,
-    isa::Instr,
+    instructions::rv64i,
+    isa::{Instr, Xlen},
     trap::{Exception, Trap},
 };
 
+/// Whether an OP-IMM (or OP-IMM-32) encoding belongs to the base ISA rather
+/// than one of the bit manipulation extensions sharing its shift slots.
+pub fn is_base_op_imm(instr: Instr) -> bool {
+    match instr.funct3() {
+        0b001 => instr.funct6() == 0x00,
+        0b101 => matches!(instr.funct6(), 0x00 | 0x10),
+        _ => true,
+    }
+}
+
+/// Whether an OP (or OP-32) encoding belongs to the base ISA: funct7 is zero
+/// except for SUB and SRA.
+pub fn is_base_op_reg(instr: Instr) -> bool {
+    match instr.funct7() {
+        0x00 => true,
+        0x20 => matches!(instr.funct3(), 0b000 | 0b101),
+        _ => false,
+    }
+}
+
 pub fn exec_op_imm(cpu: &mut Cpu, instr: Instr) -> Result<(), Trap> {
     let i = instr.as_i_type();
 
     match instr.funct3() {
         0b000 => {
             // ADDI
-            let res = (cpu.reg_file.read(i.rs1()) as i32).wrapping_add(i.imm());
-            cpu.reg_file.write(i.rd(), res as u32);
+            let res = cpu.reg_file.read(i.rs1()).wrapping_add(i.imm() as u64);
+            cpu.reg_file.write(i.rd(), res);
             Ok(())
         }
         0b010 => {
             // SLTI (Set Less Than Imm)
-            let rs1_val = cpu.reg_file.read(i.rs1()) as i32;
-            let imm_val = i.imm();
+            let rs1_val = cpu.reg_file.read(i.rs1()) as i64;
+            let imm_val = i.imm() as i64;
             if rs1_val < imm_val {
                 cpu.reg_file.write(i.rd(), 1);
             } else {
@@ -28,7 +49,7 @@ pub fn exec_op_imm(cpu: &mut Cpu, instr: Instr) -> Result<(), Trap> {
         0b011 => {
             // SLTIU (Set Less Than Imm Unsigned)
             let rs1_val = cpu.reg_file.read(i.rs1());
-            let imm_val = i.imm() as u32;
+            let imm_val = i.imm() as u64;
             if rs1_val < imm_val {
                 cpu.reg_file.write(i.rd(), 1);
             } else {
@@ -38,42 +59,45 @@ pub fn exec_op_imm(cpu: &mut Cpu, instr: Instr) -> Result<(), Trap> {
         }
         0b100 => {
             // XORI
-            let res = cpu.reg_file.read(i.rs1()) ^ (i.imm() as u32);
+            let res = cpu.reg_file.read(i.rs1()) ^ (i.imm() as u64);
             cpu.reg_file.write(i.rd(), res);
             Ok(())
         }
         0b110 => {
             // ORI
-            let res = cpu.reg_file.read(i.rs1()) | (i.imm() as u32);
+            let res = cpu.reg_file.read(i.rs1()) | (i.imm() as u64);
             cpu.reg_file.write(i.rd(), res);
             Ok(())
         }
         0b111 => {
             // ANDI
-            let res = cpu.reg_file.read(i.rs1()) & (i.imm() as u32);
+            let res = cpu.reg_file.read(i.rs1()) & (i.imm() as u64);
             cpu.reg_file.write(i.rd(), res);
             Ok(())
         }
 
         0b001 => {
             // SLLI
-            let shamt = (i.imm() & 0x1f) as u32;
+            let shamt = shift_amount(cpu, instr)?;
+            if instr.funct6() != 0x00 {
+                return Err(Trap::Exception(Exception::IllegalInstruction(instr)));
+            }
             let res = cpu.reg_file.read(i.rs1()) << shamt;
             cpu.reg_file.write(i.rd(), res);
             Ok(())
         }
         0b101 => {
-            let shamt = (i.imm() & 0x1f) as u32;
-            match instr.funct7() {
+            let shamt = shift_amount(cpu, instr)?;
+            match instr.funct6() << 1 {
                 0x00 => {
                     // SRLI
-                    let res = cpu.reg_file.read(i.rs1()) >> shamt;
+                    let res = cpu.xlen.zext(cpu.reg_file.read(i.rs1())) >> shamt;
                     cpu.reg_file.write(i.rd(), res);
                 }
                 0x20 => {
                     // SRAI
-                    let rs1_val = cpu.reg_file.read(i.rs1()) as i32;
-                    let res = (rs1_val >> shamt) as u32;
+                    let rs1_val = cpu.reg_file.read(i.rs1()) as i64;
+                    let res = (rs1_val >> shamt) as u64;
                     cpu.reg_file.write(i.rd(), res);
                 }
                 _ => return Err(Trap::Exception(Exception::IllegalInstruction(instr))),
@@ -115,15 +139,15 @@ pub fn exec_op_reg(cpu: &mut Cpu, instr: Instr) -> Result<(), Trap> {
         0b001 => {
             // SLL
             let rs1_val = cpu.reg_file.read(r.rs1());
-            let rs2_val = cpu.reg_file.read(r.rs2()) & 0x1f;
+            let rs2_val = cpu.reg_file.read(r.rs2()) & cpu.xlen.shamt_mask();
             let res = rs1_val << rs2_val;
             cpu.reg_file.write(r.rd(), res);
             Ok(())
         }
         0b010 => {
             // SLT
-            let rs1_val = cpu.reg_file.read(r.rs1()) as i32;
-            let rs2_val = cpu.reg_file.read(r.rs2()) as i32;
+            let rs1_val = cpu.reg_file.read(r.rs1()) as i64;
+            let rs2_val = cpu.reg_file.read(r.rs2()) as i64;
             if rs1_val < rs2_val {
                 cpu.reg_file.write(r.rd(), 1);
             } else {
@@ -152,17 +176,17 @@ pub fn exec_op_reg(cpu: &mut Cpu, instr: Instr) -> Result<(), Trap> {
             match instr.funct7() {
                 0x00 => {
                     // SRL
-                    let rs1_val = cpu.reg_file.read(r.rs1());
-                    let rs2_val = cpu.reg_file.read(r.rs2()) & 0x1f;
+                    let rs1_val = cpu.xlen.zext(cpu.reg_file.read(r.rs1()));
+                    let rs2_val = cpu.reg_file.read(r.rs2()) & cpu.xlen.shamt_mask();
                     let res = rs1_val >> rs2_val;
                     cpu.reg_file.write(r.rd(), res);
                     Ok(())
                 }
                 0x20 => {
                     // SRA
-                    let rs1_val = cpu.reg_file.read(r.rs1()) as i32;
-                    let rs2_val = cpu.reg_file.read(r.rs2()) & 0x1f;
-                    let res = (rs1_val >> rs2_val) as u32;
+                    let rs1_val = cpu.reg_file.read(r.rs1()) as i64;
+                    let rs2_val = cpu.reg_file.read(r.rs2()) & cpu.xlen.shamt_mask();
+                    let res = (rs1_val >> rs2_val) as u64;
                     cpu.reg_file.write(r.rd(), res);
                     Ok(())
                 }
@@ -187,13 +211,13 @@ pub fn exec_op_reg(cpu: &mut Cpu, instr: Instr) -> Result<(), Trap> {
 
 pub fn exec_lui(cpu: &mut Cpu, instr: Instr) -> Result<(), Trap> {
     let u = instr.as_u_type();
-    cpu.reg_file.write(u.rd(), u.imm() as u32);
+    cpu.reg_file.write(u.rd(), u.imm() as u64);
     Ok(())
 }
 
 pub fn exec_auipc(cpu: &mut Cpu, instr: Instr) -> Result<(), Trap> {
     let u = instr.as_u_type();
-    let result = cpu.pc.wrapping_add(u.imm() as u32);
+    let result = cpu.pc.wrapping_add(u.imm() as u64);
     cpu.reg_file.write(u.rd(), result);
     Ok(())
 }
@@ -202,7 +226,7 @@ pub fn exec_jal(cpu: &mut Cpu, instr: Instr) -> Result<(), Trap> {
     let j = instr.as_j_type();
 
     let ret_addr = cpu.next_pc;
-    let target_addr = cpu.pc.wrapping_add(j.imm() as u32);
+    let target_addr = cpu.pc.wrapping_add(j.imm() as u64);
 
     cpu.reg_file.write(j.rd(), ret_addr);
     cpu.next_pc = target_addr;
@@ -215,7 +239,7 @@ pub fn exec_jalr(cpu: &mut Cpu, instr: Instr) -> Result<(), Trap> {
 
     let ret_addr = cpu.next_pc;
     let base_addr = cpu.reg_file.read(i.rs1());
-    let target_addr = base_addr.wrapping_add(i.imm() as u32) & !1;
+    let target_addr = base_addr.wrapping_add(i.imm() as u64) & !1;
 
     cpu.reg_file.write(i.rd(), ret_addr);
     cpu.next_pc = target_addr;
@@ -232,7 +256,7 @@ pub fn exec_branch(cpu: &mut Cpu, instr: Instr) -> Result<(), Trap> {
             let rs1_val = cpu.reg_file.read(b.rs1());
             let rs2_val = cpu.reg_file.read(b.rs2());
             if rs1_val == rs2_val {
-                let target_addr = cpu.pc.wrapping_add(b.imm() as u32);
+                let target_addr = cpu.pc.wrapping_add(b.imm() as u64);
                 cpu.next_pc = target_addr;
             }
             Ok(())
@@ -242,27 +266,27 @@ pub fn exec_branch(cpu: &mut Cpu, instr: Instr) -> Result<(), Trap> {
             let rs1_val = cpu.reg_file.read(b.rs1());
             let rs2_val = cpu.reg_file.read(b.rs2());
             if rs1_val != rs2_val {
-                let target_addr = cpu.pc.wrapping_add(b.imm() as u32);
+                let target_addr = cpu.pc.wrapping_add(b.imm() as u64);
                 cpu.next_pc = target_addr;
             }
             Ok(())
         }
         0b100 => {
             // BLT
-            let rs1_val = cpu.reg_file.read(b.rs1()) as i32;
-            let rs2_val = cpu.reg_file.read(b.rs2()) as i32;
+            let rs1_val = cpu.reg_file.read(b.rs1()) as i64;
+            let rs2_val = cpu.reg_file.read(b.rs2()) as i64;
             if rs1_val < rs2_val {
-                let target_addr = cpu.pc.wrapping_add(b.imm() as u32);
+                let target_addr = cpu.pc.wrapping_add(b.imm() as u64);
                 cpu.next_pc = target_addr;
             }
             Ok(())
         }
         0b101 => {
             // BGE
-            let rs1_val = cpu.reg_file.read(b.rs1()) as i32;
-            let rs2_val = cpu.reg_file.read(b.rs2()) as i32;
+            let rs1_val = cpu.reg_file.read(b.rs1()) as i64;
+            let rs2_val = cpu.reg_file.read(b.rs2()) as i64;
             if rs1_val >= rs2_val {
-                let target_addr = cpu.pc.wrapping_add(b.imm() as u32);
+                let target_addr = cpu.pc.wrapping_add(b.imm() as u64);
                 cpu.next_pc = target_addr;
             }
             Ok(())
@@ -272,7 +296,7 @@ pub fn exec_branch(cpu: &mut Cpu, instr: Instr) -> Result<(), Trap> {
             let rs1_val = cpu.reg_file.read(b.rs1());
             let rs2_val = cpu.reg_file.read(b.rs2());
             if rs1_val < rs2_val {
-                let target_addr = cpu.pc.wrapping_add(b.imm() as u32);
+                let target_addr = cpu.pc.wrapping_add(b.imm() as u64);
                 cpu.next_pc = target_addr;
             }
             Ok(())
@@ -282,7 +306,7 @@ pub fn exec_branch(cpu: &mut Cpu, instr: Instr) -> Result<(), Trap> {
             let rs1_val = cpu.reg_file.read(b.rs1());
             let rs2_val = cpu.reg_file.read(b.rs2());
             if rs1_val >= rs2_val {
-                let target_addr = cpu.pc.wrapping_add(b.imm() as u32);
+                let target_addr = cpu.pc.wrapping_add(b.imm() as u64);
                 cpu.next_pc = target_addr;
             }
             Ok(())
@@ -297,48 +321,45 @@ pub fn exec_load(cpu: &mut Cpu, instr: Instr) -> Result<(), Trap> {
     match instr.funct3() {
         0b000 => {
             // LB (Load Byte, sign-extended)
-            let addr = cpu.reg_file.read(i.rs1()).wrapping_add(i.imm() as u32);
-            let phys_addr = cpu.translate(addr)?;
-            let byte = cpu.bus.load(phys_addr, 1)? as u8;
-            let value = (byte as i8) as i32;
-            cpu.reg_file.write(i.rd(), value as u32);
+            let addr = cpu.effective_addr(cpu.reg_file.read(i.rs1()), i.imm());
+            let byte = cpu.load(addr, 1)? as u8;
+            let value = (byte as i8) as i64;
+            cpu.reg_file.write(i.rd(), value as u64);
             Ok(())
         }
         0b001 => {
             // LH (Load Half, sign-extended)
-            let addr = cpu.reg_file.read(i.rs1()).wrapping_add(i.imm() as u32);
-            let phys_addr = cpu.translate(addr)?;
-            let halfword = cpu.bus.load(phys_addr, 2)? as u16;
-            let value = (halfword as i16) as i32;
-            cpu.reg_file.write(i.rd(), value as u32);
+            let addr = cpu.effective_addr(cpu.reg_file.read(i.rs1()), i.imm());
+            let halfword = cpu.load(addr, 2)? as u16;
+            let value = (halfword as i16) as i64;
+            cpu.reg_file.write(i.rd(), value as u64);
             Ok(())
         }
         0b010 => {
-            // LW (Load Word)
-            let addr = cpu.reg_file.read(i.rs1()).wrapping_add(i.imm() as u32);
-            let phys_addr = cpu.translate(addr)?;
-            let word = cpu.bus.load(phys_addr, 4)? as u32;
-            cpu.reg_file.write(i.rd(), word);
+            // LW (Load Word, sign-extended on rv64)
+            let addr = cpu.effective_addr(cpu.reg_file.read(i.rs1()), i.imm());
+            let word = cpu.load(addr, 4)? as u32;
+            let value = (word as i32) as i64;
+            cpu.reg_file.write(i.rd(), value as u64);
             Ok(())
         }
         0b100 => {
             // LBU (Load Byte Unsigned, zero-extended)
-            let addr = cpu.reg_file.read(i.rs1()).wrapping_add(i.imm() as u32);
-            let phys_addr = cpu.translate(addr)?;
-            let byte = cpu.bus.load(phys_addr, 1)? as u8;
-            let value = byte as u32;
+            let addr = cpu.effective_addr(cpu.reg_file.read(i.rs1()), i.imm());
+            let byte = cpu.load(addr, 1)? as u8;
+            let value = byte as u64;
             cpu.reg_file.write(i.rd(), value);
             Ok(())
         }
         0b101 => {
             // LHU (Load Half Unsigned, zero-extended)
-            let addr = cpu.reg_file.read(i.rs1()).wrapping_add(i.imm() as u32);
-            let phys_addr = cpu.translate(addr)?;
-            let halfword = cpu.bus.load(phys_addr, 2)? as u16;
-            let value = halfword as u32;
+            let addr = cpu.effective_addr(cpu.reg_file.read(i.rs1()), i.imm());
+            let halfword = cpu.load(addr, 2)? as u16;
+            let value = halfword as u64;
             cpu.reg_file.write(i.rd(), value);
             Ok(())
         }
+        0b011 | 0b110 if cpu.xlen == Xlen::Rv64 => rv64i::exec_load(cpu, instr),
         _ => Err(Trap::Exception(Exception::IllegalInstruction(instr))),
     }
 }
@@ -349,28 +370,35 @@ pub fn exec_store(cpu: &mut Cpu, instr: Instr) -> Result<(), Trap> {
     match instr.funct3() {
         0b000 => {
             // SB
-            let addr = cpu.reg_file.read(s.rs1()).wrapping_add(s.imm() as u32);
-            let data = (cpu.reg_file.read(s.rs2()) & 0xff) as u8;
-            let phys_addr = cpu.translate(addr)?;
-            cpu.bus.store(phys_addr, 1, data as u32)?;
+            let addr = cpu.effective_addr(cpu.reg_file.read(s.rs1()), s.imm());
+            let data = cpu.reg_file.read(s.rs2()) & 0xff;
+            cpu.store(addr, 1, data)?;
             Ok(())
         }
         0b001 => {
             // SH
-            let addr = cpu.reg_file.read(s.rs1()).wrapping_add(s.imm() as u32);
-            let data = (cpu.reg_file.read(s.rs2()) & 0xffff) as u16;
-            let phys_addr = cpu.translate(addr)?;
-            cpu.bus.store(phys_addr, 2, data as u32)?;
+            let addr = cpu.effective_addr(cpu.reg_file.read(s.rs1()), s.imm());
+            let data = cpu.reg_file.read(s.rs2()) & 0xffff;
+            cpu.store(addr, 2, data)?;
             Ok(())
         }
         0b010 => {
             // SW
-            let addr = cpu.reg_file.read(s.rs1()).wrapping_add(s.imm() as u32);
-            let data = cpu.reg_file.read(s.rs2());
-            let phys_addr = cpu.translate(addr)?;
-            cpu.bus.store(phys_addr, 4, data)?;
+            let addr = cpu.effective_addr(cpu.reg_file.read(s.rs1()), s.imm());
+            let data = cpu.reg_file.read(s.rs2()) & 0xffff_ffff;
+            cpu.store(addr, 4, data)?;
             Ok(())
         }
+        0b011 if cpu.xlen == Xlen::Rv64 => rv64i::exec_store(cpu, instr),
         _ => Err(Trap::Exception(Exception::IllegalInstruction(instr))),
     }
 }
+
+/// Shift amount of SLLI/SRLI/SRAI. On rv32, shamt[5] must be zero.
+fn shift_amount(cpu: &Cpu, instr: Instr) -> Result<u32, Trap> {
+    let shamt = (instr.as_i_type().imm() & 0x3f) as u64;
+    if shamt & !cpu.xlen.shamt_mask() != 0 {
+        return Err(Trap::Exception(Exception::IllegalInstruction(instr)));
+    }
+    Ok(shamt as u32)
+}
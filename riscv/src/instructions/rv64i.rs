@@ -0,0 +1,118 @@
+use crate::{
+    cpu::Cpu,
+    isa::Instr,
+    trap::{Exception, Trap},
+};
+
+// *W instructions operate on the low 32 bits and sign-extend the result.
+#[inline(always)]
+fn sext_w(val: u32) -> u64 {
+    val as i32 as i64 as u64
+}
+
+pub fn exec_op_imm_32(cpu: &mut Cpu, instr: Instr) -> Result<(), Trap> {
+    let i = instr.as_i_type();
+    let rs1_val = cpu.reg_file.read(i.rs1()) as u32;
+    let shamt = (i.imm() & 0x1f) as u32;
+
+    match (instr.funct3(), instr.funct7()) {
+        (0b000, _) => {
+            // ADDIW
+            let res = rs1_val.wrapping_add(i.imm() as u32);
+            cpu.reg_file.write(i.rd(), sext_w(res));
+            Ok(())
+        }
+        (0b001, 0x00) => {
+            // SLLIW
+            cpu.reg_file.write(i.rd(), sext_w(rs1_val << shamt));
+            Ok(())
+        }
+        (0b101, 0x00) => {
+            // SRLIW
+            cpu.reg_file.write(i.rd(), sext_w(rs1_val >> shamt));
+            Ok(())
+        }
+        (0b101, 0x20) => {
+            // SRAIW
+            let res = (rs1_val as i32) >> shamt;
+            cpu.reg_file.write(i.rd(), sext_w(res as u32));
+            Ok(())
+        }
+        _ => Err(Trap::Exception(Exception::IllegalInstruction(instr))),
+    }
+}
+
+pub fn exec_op_32(cpu: &mut Cpu, instr: Instr) -> Result<(), Trap> {
+    let r = instr.as_r_type();
+    let rs1_val = cpu.reg_file.read(r.rs1()) as u32;
+    let rs2_val = cpu.reg_file.read(r.rs2()) as u32;
+    let shamt = rs2_val & 0x1f;
+
+    match (instr.funct3(), instr.funct7()) {
+        (0b000, 0x00) => {
+            // ADDW
+            let res = rs1_val.wrapping_add(rs2_val);
+            cpu.reg_file.write(r.rd(), sext_w(res));
+            Ok(())
+        }
+        (0b000, 0x20) => {
+            // SUBW
+            let res = rs1_val.wrapping_sub(rs2_val);
+            cpu.reg_file.write(r.rd(), sext_w(res));
+            Ok(())
+        }
+        (0b001, 0x00) => {
+            // SLLW
+            cpu.reg_file.write(r.rd(), sext_w(rs1_val << shamt));
+            Ok(())
+        }
+        (0b101, 0x00) => {
+            // SRLW
+            cpu.reg_file.write(r.rd(), sext_w(rs1_val >> shamt));
+            Ok(())
+        }
+        (0b101, 0x20) => {
+            // SRAW
+            let res = (rs1_val as i32) >> shamt;
+            cpu.reg_file.write(r.rd(), sext_w(res as u32));
+            Ok(())
+        }
+        _ => Err(Trap::Exception(Exception::IllegalInstruction(instr))),
+    }
+}
+
+pub fn exec_load(cpu: &mut Cpu, instr: Instr) -> Result<(), Trap> {
+    let i = instr.as_i_type();
+    let addr = cpu.effective_addr(cpu.reg_file.read(i.rs1()), i.imm());
+
+    match instr.funct3() {
+        0b011 => {
+            // LD
+            let doubleword = cpu.load(addr, 8)?;
+            cpu.reg_file.write(i.rd(), doubleword);
+            Ok(())
+        }
+        0b110 => {
+            // LWU (Load Word Unsigned, zero-extended)
+            let word = cpu.load(addr, 4)?;
+            cpu.reg_file.write(i.rd(), word);
+            Ok(())
+        }
+        _ => Err(Trap::Exception(Exception::IllegalInstruction(instr))),
+    }
+}
+
+pub fn exec_store(cpu: &mut Cpu, instr: Instr) -> Result<(), Trap> {
+    let s = instr.as_s_type();
+
+    match instr.funct3() {
+        0b011 => {
+            // SD
+            let addr = cpu.effective_addr(cpu.reg_file.read(s.rs1()), s.imm());
+            let data = cpu.reg_file.read(s.rs2());
+            cpu.store(addr, 8, data)?;
+            Ok(())
+        }
+        _ => Err(Trap::Exception(Exception::IllegalInstruction(instr))),
+    }
+}
@@ -0,0 +1,303 @@
+use super::{
+    VConfig, check_group, check_mask_overlap, illegal, is_active, retire, vconfig, vregs, vregs_ref,
+};
+use crate::{cpu::Cpu, isa::Instr, trap::Trap};
+
+const MOP_UNIT_STRIDE: u8 = 0b00;
+const MOP_INDEXED_UNORDERED: u8 = 0b01;
+const MOP_STRIDED: u8 = 0b10;
+const MOP_INDEXED_ORDERED: u8 = 0b11;
+
+const LUMOP_UNIT: u8 = 0b00000;
+const LUMOP_WHOLE_REG: u8 = 0b01000;
+const LUMOP_MASK: u8 = 0b01011;
+const LUMOP_FAULT_ONLY_FIRST: u8 = 0b10000;
+
+#[derive(Clone, Copy)]
+enum Addressing {
+    /// Consecutive segments of `nf` fields.
+    UnitStride,
+    Strided(u64),
+    /// Byte offsets taken from the index register group, of width `eew`.
+    Indexed {
+        vs2: u8,
+        eew: u32,
+    },
+}
+
+/// Fully decoded vector memory access over `evl` elements.
+struct Access {
+    base: u64,
+    addressing: Addressing,
+    /// Data element width in bits.
+    eew: u32,
+    /// Number of fields per segment.
+    nf: usize,
+    /// Registers per field.
+    field_regs: usize,
+    evl: usize,
+    fault_only_first: bool,
+}
+
+impl Access {
+    fn decode(cpu: &Cpu, instr: Instr, is_load: bool) -> Result<Access, Trap> {
+        let v = instr.as_v_type();
+
+        if v.mew() {
+            return Err(illegal(instr));
+        }
+        let width = match instr.funct3() {
+            0b000 => 8,
+            0b101 => 16,
+            0b110 => 32,
+            0b111 => 64,
+            _ => return Err(illegal(instr)), // scalar fp
+        };
+
+        let config = vconfig(cpu, instr)?;
+        let base = cpu.reg_file.read(v.rs1());
+        let nf = v.nf() as usize + 1;
+        let vl = cpu.csr_file.get_vl() as usize;
+
+        let (addressing, eew, lmul_log2) = match v.mop() {
+            MOP_UNIT_STRIDE => (Addressing::UnitStride, width, emul_log2(&config, width)),
+            MOP_STRIDED => {
+                let stride = cpu.reg_file.read(v.vs2());
+                (
+                    Addressing::Strided(stride),
+                    width,
+                    emul_log2(&config, width),
+                )
+            }
+            MOP_INDEXED_UNORDERED | MOP_INDEXED_ORDERED => {
+                let index_regs = 1 << emul_log2(&config, width).max(0);
+                check_group(instr, v.vs2(), index_regs)?;
+                let addressing = Addressing::Indexed {
+                    vs2: v.vs2(),
+                    eew: width,
+                };
+                (addressing, config.sew, config.lmul_log2)
+            }
+            _ => unreachable!(),
+        };
+
+        if !(-3..=3).contains(&lmul_log2) {
+            return Err(illegal(instr));
+        }
+
+        let field_regs = 1 << lmul_log2.max(0);
+        if nf * field_regs > 8 {
+            return Err(illegal(instr));
+        }
+        check_group(instr, v.vd(), field_regs)?;
+        if v.vd() as usize + nf * field_regs > 32 {
+            return Err(illegal(instr));
+        }
+        if is_load {
+            check_mask_overlap(instr, v)?;
+        }
+
+        Ok(Access {
+            base,
+            addressing,
+            eew,
+            nf,
+            field_regs,
+            evl: vl,
+            fault_only_first: false,
+        })
+    }
+
+    fn addr(&self, cpu: &Cpu, idx: usize, field: usize) -> u64 {
+        let width = (self.eew / 8) as u64;
+        let field_offset = field as u64 * width;
+
+        let offset = match self.addressing {
+            Addressing::UnitStride => (idx * self.nf) as u64 * width + field_offset,
+            Addressing::Strided(stride) => {
+                (idx as u64).wrapping_mul(stride).wrapping_add(field_offset)
+            }
+            Addressing::Indexed { vs2, eew } => {
+                let offset = vregs_ref(cpu).read(vs2, idx, eew);
+                offset.wrapping_add(field_offset)
+            }
+        };
+
+        cpu.xlen.zext(self.base.wrapping_add(offset))
+    }
+
+    /// First register of the group holding `field` of each segment.
+    fn field_reg(&self, vd: u8, field: usize) -> u8 {
+        vd + (field * self.field_regs) as u8
+    }
+}
+
+/// log2 of EMUL = EEW / SEW * LMUL.
+fn emul_log2(config: &VConfig, eew: u32) -> i32 {
+    config.lmul_log2 + eew.trailing_zeros() as i32 - config.sew.trailing_zeros() as i32
+}
+
+/// Vector loads in the LOAD-FP opcode space.
+pub fn exec_load(cpu: &mut Cpu, instr: Instr) -> Result<(), Trap> {
+    let v = instr.as_v_type();
+
+    if v.mop() == MOP_UNIT_STRIDE {
+        match v.vs2() {
+            LUMOP_UNIT => {}
+            LUMOP_WHOLE_REG => return exec_whole_reg(cpu, instr, true),
+            LUMOP_MASK => return exec_mask_access(cpu, instr, true),
+            LUMOP_FAULT_ONLY_FIRST => {
+                let mut access = Access::decode(cpu, instr, true)?;
+                access.fault_only_first = true;
+                return load_elements(cpu, instr, &access);
+            }
+            _ => return Err(illegal(instr)),
+        }
+    }
+
+    let access = Access::decode(cpu, instr, true)?;
+    load_elements(cpu, instr, &access)
+}
+
+/// Vector stores in the STORE-FP opcode space.
+pub fn exec_store(cpu: &mut Cpu, instr: Instr) -> Result<(), Trap> {
+    let v = instr.as_v_type();
+
+    if v.mop() == MOP_UNIT_STRIDE {
+        match v.vs2() {
+            LUMOP_UNIT => {}
+            LUMOP_WHOLE_REG => return exec_whole_reg(cpu, instr, false),
+            LUMOP_MASK => return exec_mask_access(cpu, instr, false),
+            _ => return Err(illegal(instr)),
+        }
+    }
+
+    let access = Access::decode(cpu, instr, false)?;
+    store_elements(cpu, instr, &access)
+}
+
+fn load_elements(cpu: &mut Cpu, instr: Instr, access: &Access) -> Result<(), Trap> {
+    let v = instr.as_v_type();
+    let width = (access.eew / 8) as u8;
+
+    for idx in cpu.csr_file.get_vstart() as usize..access.evl {
+        if !is_active(cpu, v, idx) {
+            continue;
+        }
+
+        for field in 0..access.nf {
+            let addr = access.addr(cpu, idx, field);
+            let val = match cpu.load(addr, width) {
+                Ok(val) => val,
+                Err(_) if access.fault_only_first && idx > 0 => {
+                    // trim vl to the elements loaded so far instead of trapping
+                    let vtype = cpu.csr_file.get_vtype();
+                    cpu.csr_file.set_vl_vtype(idx as u64, vtype);
+                    retire(cpu);
+                    return Ok(());
+                }
+                Err(trap) => {
+                    cpu.csr_file.set_vstart(idx as u64);
+                    return Err(trap);
+                }
+            };
+            let vreg = access.field_reg(v.vd(), field);
+            vregs(cpu).write(vreg, idx, access.eew, val);
+        }
+    }
+
+    retire(cpu);
+    Ok(())
+}
+
+fn store_elements(cpu: &mut Cpu, instr: Instr, access: &Access) -> Result<(), Trap> {
+    let v = instr.as_v_type();
+    let width = (access.eew / 8) as u8;
+
+    for idx in cpu.csr_file.get_vstart() as usize..access.evl {
+        if !is_active(cpu, v, idx) {
+            continue;
+        }
+
+        for field in 0..access.nf {
+            let addr = access.addr(cpu, idx, field);
+            let vreg = access.field_reg(v.vd(), field);
+            let val = vregs_ref(cpu).read(vreg, idx, access.eew);
+            if let Err(trap) = cpu.store(addr, width, val) {
+                cpu.csr_file.set_vstart(idx as u64);
+                return Err(trap);
+            }
+        }
+    }
+
+    retire(cpu);
+    Ok(())
+}
+
+/// VL<nf>RE<eew>.V / VS<nf>R.V: whole register groups, ignoring vtype and vl.
+fn exec_whole_reg(cpu: &mut Cpu, instr: Instr, is_load: bool) -> Result<(), Trap> {
+    let v = instr.as_v_type();
+
+    if !cpu.csr_file.vector_enabled() || !v.vm() || v.mew() {
+        return Err(illegal(instr));
+    }
+    let regs = v.nf() as usize + 1;
+    if !regs.is_power_of_two() {
+        return Err(illegal(instr));
+    }
+    check_group(instr, v.vd(), regs)?;
+
+    let base = cpu.reg_file.read(v.rs1());
+    let len = regs * vregs_ref(cpu).vlenb();
+
+    for byte in cpu.csr_file.get_vstart() as usize..len {
+        let addr = cpu.xlen.zext(base.wrapping_add(byte as u64));
+        let result = if is_load {
+            cpu.load(addr, 1)
+                .map(|val| vregs(cpu).regs_mut(v.vd(), regs)[byte] = val as u8)
+        } else {
+            let val = vregs_ref(cpu).regs(v.vd(), regs)[byte];
+            cpu.store(addr, 1, val as u64)
+        };
+
+        if let Err(trap) = result {
+            cpu.csr_file.set_vstart(byte as u64);
+            return Err(trap);
+        }
+    }
+
+    retire(cpu);
+    Ok(())
+}
+
+/// VLM.V / VSM.V: ceil(vl / 8) bytes of a mask register.
+fn exec_mask_access(cpu: &mut Cpu, instr: Instr, is_load: bool) -> Result<(), Trap> {
+    let v = instr.as_v_type();
+
+    vconfig(cpu, instr)?;
+    if !v.vm() || v.mew() || v.nf() != 0 || instr.funct3() != 0b000 {
+        return Err(illegal(instr));
+    }
+
+    let base = cpu.reg_file.read(v.rs1());
+    let evl = (cpu.csr_file.get_vl() as usize).div_ceil(8);
+
+    for byte in cpu.csr_file.get_vstart() as usize..evl {
+        let addr = cpu.xlen.zext(base.wrapping_add(byte as u64));
+        let result = if is_load {
+            cpu.load(addr, 1)
+                .map(|val| vregs(cpu).write(v.vd(), byte, 8, val))
+        } else {
+            let val = vregs_ref(cpu).read(v.vd(), byte, 8);
+            cpu.store(addr, 1, val)
+        };
+
+        if let Err(trap) = result {
+            cpu.csr_file.set_vstart(byte as u64);
+            return Err(trap);
+        }
+    }
+
+    retire(cpu);
+    Ok(())
+}
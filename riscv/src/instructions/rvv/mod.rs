@@ -0,0 +1,239 @@
+mod memory;
+mod opi;
+mod opm;
+
+use crate::{
+    cpu::Cpu,
+    isa::{Instr, VType, Xlen},
+    trap::{Exception, Trap},
+    vregs::VRegFile,
+};
+
+pub use memory::{exec_load, exec_store};
+
+/// Widest supported element, independent of XLEN.
+const ELEN: u32 = 64;
+
+const VTYPE_VLMUL: u64 = 0b111;
+const VTYPE_VSEW_SHIFT: u64 = 3;
+const VTYPE_VSEW: u64 = 0b111 << VTYPE_VSEW_SHIFT;
+const VTYPE_VTA: u64 = 1 << 6;
+const VTYPE_VMA: u64 = 1 << 7;
+
+const OPIVV: u8 = 0b000;
+const OPMVV: u8 = 0b010;
+const OPIVI: u8 = 0b011;
+const OPIVX: u8 = 0b100;
+const OPMVX: u8 = 0b110;
+const OPCFG: u8 = 0b111;
+
+/// Decoded, valid vtype.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct VConfig {
+    /// Selected element width in bits.
+    pub sew: u32,
+    /// log2 of the register group multiplier, negative for fractional LMUL.
+    pub lmul_log2: i32,
+    pub vlmax: usize,
+}
+
+impl VConfig {
+    fn decode(vtype: u64, vlen: usize, xlen: Xlen) -> Option<Self> {
+        let reserved = !(VTYPE_VLMUL | VTYPE_VSEW | VTYPE_VTA | VTYPE_VMA);
+        if xlen.zext(vtype) & reserved != 0 {
+            return None;
+        }
+
+        let vsew = (vtype & VTYPE_VSEW) >> VTYPE_VSEW_SHIFT;
+        if vsew > 3 {
+            return None;
+        }
+        let sew = 8 << vsew;
+
+        let lmul_log2 = match vtype & VTYPE_VLMUL {
+            0b100 => return None,
+            vlmul @ 0b000..=0b011 => vlmul as i32,
+            vlmul => vlmul as i32 - 8,
+        };
+
+        // fractional lmul must still hold at least one sew element of elen
+        if lmul_log2 < 0 && sew > ELEN >> -lmul_log2 {
+            return None;
+        }
+
+        Some(Self {
+            sew,
+            lmul_log2,
+            vlmax: scale_by_lmul(vlen / sew as usize, lmul_log2),
+        })
+    }
+
+    /// Number of registers in a group of this LMUL.
+    pub fn group_regs(&self) -> usize {
+        1 << self.lmul_log2.max(0)
+    }
+}
+
+fn scale_by_lmul(val: usize, lmul_log2: i32) -> usize {
+    if lmul_log2 >= 0 {
+        val << lmul_log2
+    } else {
+        val >> -lmul_log2
+    }
+}
+
+pub fn exec_op_v(cpu: &mut Cpu, instr: Instr) -> Result<(), Trap> {
+    match instr.funct3() {
+        OPCFG => exec_vset(cpu, instr),
+        OPIVV | OPIVI | OPIVX => opi::exec_opi(cpu, instr),
+        OPMVV | OPMVX => opm::exec_opm(cpu, instr),
+        _ => Err(illegal(instr)),
+    }
+}
+
+/// VSETVLI / VSETIVLI / VSETVL
+fn exec_vset(cpu: &mut Cpu, instr: Instr) -> Result<(), Trap> {
+    if !cpu.csr_file.vector_enabled() {
+        return Err(illegal(instr));
+    }
+
+    let v = instr.as_v_type();
+    let word = instr.word();
+    let (rd, rs1) = (v.vd(), v.rs1());
+
+    let (vtype, avl) = if word >> 31 == 0 {
+        // VSETVLI
+        ((word >> 20) as u64 & 0x7ff, None)
+    } else if word >> 30 == 0b11 {
+        // VSETIVLI
+        ((word >> 20) as u64 & 0x3ff, Some(rs1 as u64))
+    } else if word >> 25 == 0b1000000 {
+        // VSETVL
+        (cpu.reg_file.read(v.vs2()), None)
+    } else {
+        return Err(illegal(instr));
+    };
+
+    let avl = match avl {
+        Some(uimm) => uimm,
+        None if rs1 != 0 => cpu.xlen.zext(cpu.reg_file.read(rs1)),
+        None if rd != 0 => u64::MAX,
+        None => cpu.csr_file.get_vl(),
+    };
+
+    let vlen = vregs(cpu).vlenb() * 8;
+    let vtype = cpu.xlen.zext(vtype);
+    match VConfig::decode(vtype, vlen, cpu.xlen) {
+        Some(config) => {
+            let vl = avl.min(config.vlmax as u64);
+            cpu.csr_file.set_vl_vtype(vl, vtype);
+            cpu.reg_file.write(rd, vl);
+        }
+        None => {
+            cpu.csr_file.set_vl_vtype(0, cpu.xlen.sign_bit());
+            cpu.reg_file.write(rd, 0);
+        }
+    }
+
+    cpu.csr_file.set_vstart(0);
+    cpu.csr_file.set_vector_dirty();
+    Ok(())
+}
+
+/// Current vtype, or an illegal instruction trap if vector instructions
+/// can't execute (v disabled in mstatus, or vill set).
+fn vconfig(cpu: &Cpu, instr: Instr) -> Result<VConfig, Trap> {
+    if !cpu.csr_file.vector_enabled() {
+        return Err(illegal(instr));
+    }
+
+    let vlen = vregs_ref(cpu).vlenb() * 8;
+    VConfig::decode(cpu.csr_file.get_vtype(), vlen, cpu.xlen).ok_or(illegal(instr))
+}
+
+/// Marks the vector state dirty and resets vstart after an instruction
+/// has run to completion.
+fn retire(cpu: &mut Cpu) {
+    cpu.csr_file.set_vstart(0);
+    cpu.csr_file.set_vector_dirty();
+}
+
+fn vregs(cpu: &mut Cpu) -> &mut VRegFile {
+    cpu.vreg_file
+        .as_mut()
+        .expect("vector instruction executed without a vector register file")
+}
+
+fn vregs_ref(cpu: &Cpu) -> &VRegFile {
+    cpu.vreg_file
+        .as_ref()
+        .expect("vector instruction executed without a vector register file")
+}
+
+/// Register group `vreg` of `regs` registers must be aligned and in range.
+fn check_group(instr: Instr, vreg: u8, regs: usize) -> Result<(), Trap> {
+    if !(vreg as usize).is_multiple_of(regs) || vreg as usize + regs > 32 {
+        return Err(illegal(instr));
+    }
+    Ok(())
+}
+
+/// Masked instructions can't write their (non-mask) result over v0.
+fn check_mask_overlap(instr: Instr, v: VType) -> Result<(), Trap> {
+    if !v.vm() && v.vd() == 0 {
+        return Err(illegal(instr));
+    }
+    Ok(())
+}
+
+/// Writes `source(idx)` to every active body element, reading all sources
+/// before writing so overlapping groups behave as if read at once.
+fn write_elements(
+    cpu: &mut Cpu,
+    instr: Instr,
+    config: &VConfig,
+    source: impl Fn(&Cpu, usize) -> Option<u64>,
+) -> Result<(), Trap> {
+    let v = instr.as_v_type();
+    let vl = cpu.csr_file.get_vl() as usize;
+    let start = cpu.csr_file.get_vstart() as usize;
+
+    let results: Vec<Option<u64>> = (start..vl)
+        .map(|idx| {
+            if is_active(cpu, v, idx) {
+                source(cpu, idx)
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    for (idx, val) in (start..vl).zip(results) {
+        if let Some(val) = val {
+            vregs(cpu).write(v.vd(), idx, config.sew, val);
+        }
+    }
+
+    retire(cpu);
+    Ok(())
+}
+
+/// Whether element `idx` is active under the instruction's mask.
+fn is_active(cpu: &Cpu, v: VType, idx: usize) -> bool {
+    v.vm() || vregs_ref(cpu).read_mask(0, idx)
+}
+
+#[inline(always)]
+fn elem_mask(sew: u32) -> u64 {
+    u64::MAX >> (64 - sew)
+}
+
+/// Sign-extends an element of width `sew`.
+#[inline(always)]
+fn sext_elem(val: u64, sew: u32) -> i64 {
+    ((val << (64 - sew)) as i64) >> (64 - sew)
+}
+
+fn illegal(instr: Instr) -> Trap {
+    Trap::Exception(Exception::IllegalInstruction(instr))
+}
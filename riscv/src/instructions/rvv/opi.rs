@@ -0,0 +1,413 @@
+use std::cell::Cell;
+
+use super::{
+    OPIVI, OPIVV, OPIVX, VConfig, check_group, check_mask_overlap, elem_mask, illegal, is_active,
+    retire, sext_elem, vconfig, vregs, vregs_ref, write_elements,
+};
+use crate::{cpu::Cpu, isa::Instr, trap::Trap};
+
+const VADD: u8 = 0b000000;
+const VSUB: u8 = 0b000010;
+const VRSUB: u8 = 0b000011;
+const VMINU: u8 = 0b000100;
+const VMIN: u8 = 0b000101;
+const VMAXU: u8 = 0b000110;
+const VMAX: u8 = 0b000111;
+const VAND: u8 = 0b001001;
+const VOR: u8 = 0b001010;
+const VXOR: u8 = 0b001011;
+const VRGATHER: u8 = 0b001100;
+const VSLIDEUP: u8 = 0b001110;
+const VSLIDEDOWN: u8 = 0b001111;
+const VADC: u8 = 0b010000;
+const VMADC: u8 = 0b010001;
+const VSBC: u8 = 0b010010;
+const VMSBC: u8 = 0b010011;
+const VMERGE: u8 = 0b010111;
+const VMSEQ: u8 = 0b011000;
+const VMSNE: u8 = 0b011001;
+const VMSLTU: u8 = 0b011010;
+const VMSLT: u8 = 0b011011;
+const VMSLEU: u8 = 0b011100;
+const VMSLE: u8 = 0b011101;
+const VMSGTU: u8 = 0b011110;
+const VMSGT: u8 = 0b011111;
+const VSADDU: u8 = 0b100000;
+const VSADD: u8 = 0b100001;
+const VSSUBU: u8 = 0b100010;
+const VSSUB: u8 = 0b100011;
+const VSLL: u8 = 0b100101;
+const VMV_NR_R: u8 = 0b100111;
+const VSRL: u8 = 0b101000;
+const VSRA: u8 = 0b101001;
+
+/// Second operand of an OPIVV/OPIVX/OPIVI instruction.
+#[derive(Clone, Copy)]
+enum Operand {
+    Vector(u8),
+    Scalar(u64),
+}
+
+impl Operand {
+    fn get(self, cpu: &Cpu, idx: usize, sew: u32) -> u64 {
+        match self {
+            Operand::Vector(vs1) => vregs_ref(cpu).read(vs1, idx, sew),
+            Operand::Scalar(val) => val & elem_mask(sew),
+        }
+    }
+}
+
+pub fn exec_opi(cpu: &mut Cpu, instr: Instr) -> Result<(), Trap> {
+    let v = instr.as_v_type();
+    let funct3 = instr.funct3();
+    let funct6 = v.funct6();
+
+    if funct6 == VMV_NR_R && funct3 == OPIVI {
+        return exec_vmv_nr_r(cpu, instr);
+    }
+
+    let config = vconfig(cpu, instr)?;
+    let sew = config.sew;
+
+    // shifts, slides and gathers take the immediate as unsigned
+    let unsigned_imm = matches!(
+        funct6,
+        VSLL | VSRL | VSRA | VSLIDEUP | VSLIDEDOWN | VRGATHER
+    );
+    let operand = match funct3 {
+        OPIVV => Operand::Vector(v.rs1()),
+        OPIVX => Operand::Scalar(cpu.reg_file.read(v.rs1())),
+        OPIVI if unsigned_imm => Operand::Scalar(v.rs1() as u64),
+        OPIVI => Operand::Scalar(v.simm5() as i64 as u64),
+        _ => return Err(illegal(instr)),
+    };
+
+    // carry-outs and compares write a single mask register, any vd will do
+    let mask_result = matches!(funct6, VMADC | VMSBC | VMSEQ..=VMSGT);
+    let regs = config.group_regs();
+    if !mask_result {
+        check_group(instr, v.vd(), regs)?;
+    }
+    check_group(instr, v.vs2(), regs)?;
+    if let Operand::Vector(vs1) = operand {
+        check_group(instr, vs1, regs)?;
+    }
+
+    let reserved = match funct3 {
+        OPIVV => matches!(funct6, VRSUB | VSLIDEUP | VSLIDEDOWN | VMSGTU | VMSGT),
+        OPIVX => false,
+        _ => matches!(
+            funct6,
+            VSUB | VMINU | VMIN | VMAXU | VMAX | VSBC | VMSBC | VMSLTU | VMSLT | VSSUBU | VSSUB
+        ),
+    };
+    if reserved {
+        return Err(illegal(instr));
+    }
+
+    match funct6 {
+        VADD => elementwise(cpu, instr, &config, operand, |a, b| a.wrapping_add(b)),
+        VSUB => elementwise(cpu, instr, &config, operand, |a, b| a.wrapping_sub(b)),
+        VRSUB => elementwise(cpu, instr, &config, operand, |a, b| b.wrapping_sub(a)),
+        VMINU => elementwise(cpu, instr, &config, operand, |a, b| a.min(b)),
+        VMAXU => elementwise(cpu, instr, &config, operand, |a, b| a.max(b)),
+        VMIN => elementwise(cpu, instr, &config, operand, |a, b| {
+            if sext_elem(a, sew) < sext_elem(b, sew) {
+                a
+            } else {
+                b
+            }
+        }),
+        VMAX => elementwise(cpu, instr, &config, operand, |a, b| {
+            if sext_elem(a, sew) > sext_elem(b, sew) {
+                a
+            } else {
+                b
+            }
+        }),
+        VAND => elementwise(cpu, instr, &config, operand, |a, b| a & b),
+        VOR => elementwise(cpu, instr, &config, operand, |a, b| a | b),
+        VXOR => elementwise(cpu, instr, &config, operand, |a, b| a ^ b),
+        VSLL => elementwise(cpu, instr, &config, operand, |a, b| {
+            a << (b & (sew - 1) as u64)
+        }),
+        VSRL => elementwise(cpu, instr, &config, operand, |a, b| {
+            a >> (b & (sew - 1) as u64)
+        }),
+        VSRA => elementwise(cpu, instr, &config, operand, |a, b| {
+            (sext_elem(a, sew) >> (b & (sew - 1) as u64)) as u64
+        }),
+        VSADDU | VSADD | VSSUBU | VSSUB => exec_saturating(cpu, instr, &config, operand),
+        VADC | VSBC => exec_carry(cpu, instr, &config, operand),
+        VMADC | VMSBC => exec_carry_out(cpu, instr, &config, operand),
+        VMERGE => exec_merge(cpu, instr, &config, operand),
+        VMSEQ..=VMSGT => exec_compare(cpu, instr, &config, operand),
+        VSLIDEUP | VSLIDEDOWN => exec_slide(cpu, instr, &config, operand),
+        VRGATHER => exec_gather(cpu, instr, &config, operand),
+        _ => Err(illegal(instr)),
+    }
+}
+
+/// `vd[i] = op(vs2[i], operand[i])` for every active body element.
+fn elementwise(
+    cpu: &mut Cpu,
+    instr: Instr,
+    config: &VConfig,
+    operand: Operand,
+    op: impl Fn(u64, u64) -> u64,
+) -> Result<(), Trap> {
+    let v = instr.as_v_type();
+    check_mask_overlap(instr, v)?;
+
+    let sew = config.sew;
+    for idx in cpu.csr_file.get_vstart() as usize..cpu.csr_file.get_vl() as usize {
+        if !is_active(cpu, v, idx) {
+            continue;
+        }
+        let a = vregs_ref(cpu).read(v.vs2(), idx, sew);
+        let b = operand.get(cpu, idx, sew);
+        vregs(cpu).write(v.vd(), idx, sew, op(a, b) & elem_mask(sew));
+    }
+
+    retire(cpu);
+    Ok(())
+}
+
+fn exec_saturating(
+    cpu: &mut Cpu,
+    instr: Instr,
+    config: &VConfig,
+    operand: Operand,
+) -> Result<(), Trap> {
+    let sew = config.sew;
+    let umax = elem_mask(sew);
+    let (smin, smax) = (i64::MIN >> (64 - sew), i64::MAX >> (64 - sew));
+    let funct6 = instr.as_v_type().funct6();
+    let saturated = Cell::new(false);
+
+    elementwise(cpu, instr, config, operand, |a, b| {
+        let (res, sat) = match funct6 {
+            VSADDU => match a.checked_add(b).filter(|&res| res <= umax) {
+                Some(res) => (res, false),
+                None => (umax, true),
+            },
+            VSSUBU => match a.checked_sub(b) {
+                Some(res) => (res, false),
+                None => (0, true),
+            },
+            _ => {
+                let (a, b) = (sext_elem(a, sew) as i128, sext_elem(b, sew) as i128);
+                let res = if funct6 == VSADD { a + b } else { a - b };
+                let clamped = res.clamp(smin as i128, smax as i128);
+                (clamped as u64, clamped != res)
+            }
+        };
+        saturated.set(saturated.get() || sat);
+        res
+    })?;
+
+    if saturated.get() {
+        cpu.csr_file.set_vxsat();
+    }
+    Ok(())
+}
+
+/// VADC / VSBC: add or subtract with the carry/borrow taken from v0.
+fn exec_carry(cpu: &mut Cpu, instr: Instr, config: &VConfig, operand: Operand) -> Result<(), Trap> {
+    let v = instr.as_v_type();
+    if v.vm() || v.vd() == 0 {
+        return Err(illegal(instr));
+    }
+
+    let sew = config.sew;
+    for idx in cpu.csr_file.get_vstart() as usize..cpu.csr_file.get_vl() as usize {
+        let carry = vregs_ref(cpu).read_mask(0, idx) as u64;
+        let a = vregs_ref(cpu).read(v.vs2(), idx, sew);
+        let b = operand.get(cpu, idx, sew);
+        let res = if v.funct6() == VADC {
+            a.wrapping_add(b).wrapping_add(carry)
+        } else {
+            a.wrapping_sub(b).wrapping_sub(carry)
+        };
+        vregs(cpu).write(v.vd(), idx, sew, res & elem_mask(sew));
+    }
+
+    retire(cpu);
+    Ok(())
+}
+
+/// VMADC / VMSBC: carry/borrow out as a mask, with carry-in from v0 if masked.
+fn exec_carry_out(
+    cpu: &mut Cpu,
+    instr: Instr,
+    config: &VConfig,
+    operand: Operand,
+) -> Result<(), Trap> {
+    let v = instr.as_v_type();
+    let sew = config.sew;
+    let vl = cpu.csr_file.get_vl() as usize;
+    let start = cpu.csr_file.get_vstart() as usize;
+
+    let results: Vec<bool> = (start..vl)
+        .map(|idx| {
+            let carry = if v.vm() {
+                0
+            } else {
+                vregs_ref(cpu).read_mask(0, idx) as u128
+            };
+            let a = vregs_ref(cpu).read(v.vs2(), idx, sew) as u128;
+            let b = operand.get(cpu, idx, sew) as u128;
+            if v.funct6() == VMADC {
+                a + b + carry > elem_mask(sew) as u128
+            } else {
+                a < b + carry
+            }
+        })
+        .collect();
+
+    for (idx, bit) in (start..vl).zip(results) {
+        vregs(cpu).write_mask(v.vd(), idx, bit);
+    }
+
+    retire(cpu);
+    Ok(())
+}
+
+/// VMERGE (masked) and VMV.V.* (unmasked).
+fn exec_merge(cpu: &mut Cpu, instr: Instr, config: &VConfig, operand: Operand) -> Result<(), Trap> {
+    let v = instr.as_v_type();
+    if v.vm() && v.vs2() != 0 {
+        return Err(illegal(instr));
+    }
+    if !v.vm() && v.vd() == 0 {
+        return Err(illegal(instr));
+    }
+
+    let sew = config.sew;
+    for idx in cpu.csr_file.get_vstart() as usize..cpu.csr_file.get_vl() as usize {
+        let val = if v.vm() || vregs_ref(cpu).read_mask(0, idx) {
+            operand.get(cpu, idx, sew)
+        } else {
+            vregs_ref(cpu).read(v.vs2(), idx, sew)
+        };
+        vregs(cpu).write(v.vd(), idx, sew, val);
+    }
+
+    retire(cpu);
+    Ok(())
+}
+
+/// Integer compares, writing one mask bit per active element.
+fn exec_compare(
+    cpu: &mut Cpu,
+    instr: Instr,
+    config: &VConfig,
+    operand: Operand,
+) -> Result<(), Trap> {
+    let v = instr.as_v_type();
+    let sew = config.sew;
+    let vl = cpu.csr_file.get_vl() as usize;
+    let start = cpu.csr_file.get_vstart() as usize;
+
+    let results: Vec<Option<bool>> = (start..vl)
+        .map(|idx| {
+            if !is_active(cpu, v, idx) {
+                return None;
+            }
+            let a = vregs_ref(cpu).read(v.vs2(), idx, sew);
+            let b = operand.get(cpu, idx, sew);
+            let (sa, sb) = (sext_elem(a, sew), sext_elem(b, sew));
+            Some(match v.funct6() {
+                VMSEQ => a == b,
+                VMSNE => a != b,
+                VMSLTU => a < b,
+                VMSLT => sa < sb,
+                VMSLEU => a <= b,
+                VMSLE => sa <= sb,
+                VMSGTU => a > b,
+                _ => sa > sb,
+            })
+        })
+        .collect();
+
+    for (idx, bit) in (start..vl).zip(results) {
+        if let Some(bit) = bit {
+            vregs(cpu).write_mask(v.vd(), idx, bit);
+        }
+    }
+
+    retire(cpu);
+    Ok(())
+}
+
+/// VSLIDEUP / VSLIDEDOWN by a scalar or immediate offset.
+fn exec_slide(cpu: &mut Cpu, instr: Instr, config: &VConfig, operand: Operand) -> Result<(), Trap> {
+    let v = instr.as_v_type();
+    check_mask_overlap(instr, v)?;
+
+    let Operand::Scalar(offset) = operand else {
+        return Err(illegal(instr));
+    };
+    let offset = cpu.xlen.zext(offset) as usize;
+    let up = v.funct6() == VSLIDEUP;
+    if up && v.vd() == v.vs2() {
+        return Err(illegal(instr));
+    }
+
+    write_elements(cpu, instr, config, |cpu, idx| {
+        if up {
+            (idx >= offset).then(|| vregs_ref(cpu).read(v.vs2(), idx - offset, config.sew))
+        } else {
+            let src = idx.checked_add(offset).filter(|&src| src < config.vlmax);
+            Some(src.map_or(0, |src| vregs_ref(cpu).read(v.vs2(), src, config.sew)))
+        }
+    })
+}
+
+/// VRGATHER: `vd[i] = vs2[index]`, zero for out of range indices.
+fn exec_gather(
+    cpu: &mut Cpu,
+    instr: Instr,
+    config: &VConfig,
+    operand: Operand,
+) -> Result<(), Trap> {
+    let v = instr.as_v_type();
+    check_mask_overlap(instr, v)?;
+    if v.vd() == v.vs2() || matches!(operand, Operand::Vector(vs1) if vs1 == v.vd()) {
+        return Err(illegal(instr));
+    }
+
+    let sew = config.sew;
+    write_elements(cpu, instr, config, |cpu, idx| {
+        let index = match operand {
+            Operand::Vector(vs1) => vregs_ref(cpu).read(vs1, idx, sew),
+            Operand::Scalar(val) => cpu.xlen.zext(val),
+        };
+        Some(if index < config.vlmax as u64 {
+            vregs_ref(cpu).read(v.vs2(), index as usize, sew)
+        } else {
+            0
+        })
+    })
+}
+
+/// VMV<nr>R.V: copies whole registers, independent of vtype.
+fn exec_vmv_nr_r(cpu: &mut Cpu, instr: Instr) -> Result<(), Trap> {
+    let v = instr.as_v_type();
+    if !cpu.csr_file.vector_enabled() || !v.vm() {
+        return Err(illegal(instr));
+    }
+
+    let regs = v.rs1() as usize + 1;
+    if !regs.is_power_of_two() || regs > 8 {
+        return Err(illegal(instr));
+    }
+    check_group(instr, v.vd(), regs)?;
+    check_group(instr, v.vs2(), regs)?;
+
+    let src = vregs_ref(cpu).regs(v.vs2(), regs).to_vec();
+    vregs(cpu).regs_mut(v.vd(), regs).copy_from_slice(&src);
+
+    retire(cpu);
+    Ok(())
+}
@@ -0,0 +1,358 @@
+use super::{
+    OPMVV, OPMVX, VConfig, check_group, check_mask_overlap, elem_mask, illegal, is_active, retire,
+    sext_elem, vconfig, vregs, vregs_ref, write_elements,
+};
+use crate::{cpu::Cpu, isa::Instr, trap::Trap};
+
+const VREDSUM: u8 = 0b000000;
+const VREDAND: u8 = 0b000001;
+const VREDOR: u8 = 0b000010;
+const VREDXOR: u8 = 0b000011;
+const VREDMINU: u8 = 0b000100;
+const VREDMIN: u8 = 0b000101;
+const VREDMAXU: u8 = 0b000110;
+const VREDMAX: u8 = 0b000111;
+const VSLIDE1UP: u8 = 0b001110;
+const VSLIDE1DOWN: u8 = 0b001111;
+const VWXUNARY0: u8 = 0b010000; // also VRXUNARY0
+const VMUNARY0: u8 = 0b010100;
+const VMANDN: u8 = 0b011000;
+const VMAND: u8 = 0b011001;
+const VMOR: u8 = 0b011010;
+const VMXOR: u8 = 0b011011;
+const VMORN: u8 = 0b011100;
+const VMNAND: u8 = 0b011101;
+const VMNOR: u8 = 0b011110;
+const VMXNOR: u8 = 0b011111;
+const VDIVU: u8 = 0b100000;
+const VDIV: u8 = 0b100001;
+const VREMU: u8 = 0b100010;
+const VREM: u8 = 0b100011;
+const VMULHU: u8 = 0b100100;
+const VMUL: u8 = 0b100101;
+const VMULHSU: u8 = 0b100110;
+const VMULH: u8 = 0b100111;
+const VMADD: u8 = 0b101001;
+const VNMSUB: u8 = 0b101011;
+const VMACC: u8 = 0b101101;
+const VNMSAC: u8 = 0b101111;
+
+// vs1 encodings of VWXUNARY0 and VMUNARY0
+const VMV_X_S: u8 = 0b00000;
+const VCPOP: u8 = 0b10000;
+const VFIRST: u8 = 0b10001;
+const VMSBF: u8 = 0b00001;
+const VMSOF: u8 = 0b00010;
+const VMSIF: u8 = 0b00011;
+const VIOTA: u8 = 0b10000;
+const VID: u8 = 0b10001;
+
+pub fn exec_opm(cpu: &mut Cpu, instr: Instr) -> Result<(), Trap> {
+    let v = instr.as_v_type();
+    let config = vconfig(cpu, instr)?;
+    let funct6 = v.funct6();
+    let is_vv = instr.funct3() == OPMVV;
+
+    match funct6 {
+        VREDSUM..=VREDMAX if is_vv => exec_reduction(cpu, instr, &config),
+        VSLIDE1UP | VSLIDE1DOWN if !is_vv => exec_slide1(cpu, instr, &config),
+        VWXUNARY0 if is_vv => exec_wxunary0(cpu, instr, &config),
+        VWXUNARY0 => exec_vmv_s_x(cpu, instr, &config),
+        VMUNARY0 if is_vv => exec_munary0(cpu, instr, &config),
+        VMANDN..=VMXNOR if is_vv => exec_mask_logical(cpu, instr),
+        VDIVU..=VMULH => exec_multiply_divide(cpu, instr, &config),
+        VMADD | VNMSUB | VMACC | VNMSAC => exec_multiply_add(cpu, instr, &config),
+        _ => Err(illegal(instr)),
+    }
+}
+
+/// Second operand: `vs1[i]` for OPMVV, `x[rs1]` for OPMVX.
+fn operand(cpu: &Cpu, instr: Instr, idx: usize, sew: u32) -> u64 {
+    let v = instr.as_v_type();
+    match instr.funct3() {
+        OPMVX => cpu.reg_file.read(v.rs1()) & elem_mask(sew),
+        _ => vregs_ref(cpu).read(v.rs1(), idx, sew),
+    }
+}
+
+fn check_vector_groups(instr: Instr, config: &VConfig) -> Result<(), Trap> {
+    let v = instr.as_v_type();
+    let regs = config.group_regs();
+    check_group(instr, v.vd(), regs)?;
+    check_group(instr, v.vs2(), regs)?;
+    if instr.funct3() == OPMVV {
+        check_group(instr, v.rs1(), regs)?;
+    }
+    check_mask_overlap(instr, v)
+}
+
+/// Single-width integer reductions: `vd[0] = op(vs1[0], vs2[*])`.
+fn exec_reduction(cpu: &mut Cpu, instr: Instr, config: &VConfig) -> Result<(), Trap> {
+    let v = instr.as_v_type();
+    if cpu.csr_file.get_vstart() != 0 {
+        return Err(illegal(instr));
+    }
+    check_group(instr, v.vs2(), config.group_regs())?;
+
+    let vl = cpu.csr_file.get_vl() as usize;
+    if vl == 0 {
+        retire(cpu);
+        return Ok(());
+    }
+
+    let sew = config.sew;
+    let mut acc = vregs_ref(cpu).read(v.rs1(), 0, sew);
+    for idx in 0..vl {
+        if !is_active(cpu, v, idx) {
+            continue;
+        }
+        let val = vregs_ref(cpu).read(v.vs2(), idx, sew);
+        let (sacc, sval) = (sext_elem(acc, sew), sext_elem(val, sew));
+        acc = match v.funct6() {
+            VREDSUM => acc.wrapping_add(val),
+            VREDAND => acc & val,
+            VREDOR => acc | val,
+            VREDXOR => acc ^ val,
+            VREDMINU => acc.min(val),
+            VREDMIN => sacc.min(sval) as u64,
+            VREDMAXU => acc.max(val),
+            _ => sacc.max(sval) as u64,
+        } & elem_mask(sew);
+    }
+
+    vregs(cpu).write(v.vd(), 0, sew, acc);
+    retire(cpu);
+    Ok(())
+}
+
+/// VSLIDE1UP / VSLIDE1DOWN: slide by one, inserting `x[rs1]`.
+fn exec_slide1(cpu: &mut Cpu, instr: Instr, config: &VConfig) -> Result<(), Trap> {
+    let v = instr.as_v_type();
+    check_vector_groups(instr, config)?;
+
+    let up = v.funct6() == VSLIDE1UP;
+    if up && v.vd() == v.vs2() {
+        return Err(illegal(instr));
+    }
+
+    let sew = config.sew;
+    let vl = cpu.csr_file.get_vl() as usize;
+    let scalar = cpu.reg_file.read(v.rs1()) & elem_mask(sew);
+
+    write_elements(cpu, instr, config, |cpu, idx| {
+        Some(match (up, idx) {
+            (true, 0) => scalar,
+            (true, _) => vregs_ref(cpu).read(v.vs2(), idx - 1, sew),
+            (false, _) if idx + 1 == vl => scalar,
+            (false, _) => vregs_ref(cpu).read(v.vs2(), idx + 1, sew),
+        })
+    })
+}
+
+/// VMV.X.S / VCPOP.M / VFIRST.M
+fn exec_wxunary0(cpu: &mut Cpu, instr: Instr, config: &VConfig) -> Result<(), Trap> {
+    let v = instr.as_v_type();
+    let vl = cpu.csr_file.get_vl() as usize;
+
+    let res = match v.rs1() {
+        VMV_X_S if v.vm() => {
+            sext_elem(vregs_ref(cpu).read(v.vs2(), 0, config.sew), config.sew) as u64
+        }
+        VCPOP | VFIRST => {
+            if cpu.csr_file.get_vstart() != 0 {
+                return Err(illegal(instr));
+            }
+            let mut set = (0..vl)
+                .filter(|&idx| is_active(cpu, v, idx) && vregs_ref(cpu).read_mask(v.vs2(), idx));
+            if v.rs1() == VCPOP {
+                set.count() as u64
+            } else {
+                set.next().map_or(u64::MAX, |idx| idx as u64)
+            }
+        }
+        _ => return Err(illegal(instr)),
+    };
+
+    cpu.reg_file.write(v.vd(), res);
+    retire(cpu);
+    Ok(())
+}
+
+/// VMV.S.X: `vd[0] = x[rs1]`.
+fn exec_vmv_s_x(cpu: &mut Cpu, instr: Instr, config: &VConfig) -> Result<(), Trap> {
+    let v = instr.as_v_type();
+    if !v.vm() || v.vs2() != 0 {
+        return Err(illegal(instr));
+    }
+
+    if cpu.csr_file.get_vstart() < cpu.csr_file.get_vl() {
+        let val = cpu.reg_file.read(v.rs1()) & elem_mask(config.sew);
+        vregs(cpu).write(v.vd(), 0, config.sew, val);
+    }
+
+    retire(cpu);
+    Ok(())
+}
+
+/// VMSBF / VMSOF / VMSIF / VIOTA / VID
+fn exec_munary0(cpu: &mut Cpu, instr: Instr, config: &VConfig) -> Result<(), Trap> {
+    let v = instr.as_v_type();
+    let vl = cpu.csr_file.get_vl() as usize;
+    let sew = config.sew;
+
+    match v.rs1() {
+        VMSBF | VMSOF | VMSIF => {
+            if cpu.csr_file.get_vstart() != 0 || v.vd() == v.vs2() {
+                return Err(illegal(instr));
+            }
+            check_mask_overlap(instr, v)?;
+
+            let mut seen = false;
+            for idx in 0..vl {
+                if !is_active(cpu, v, idx) {
+                    continue;
+                }
+                let bit = vregs_ref(cpu).read_mask(v.vs2(), idx);
+                let res = match v.rs1() {
+                    VMSBF => !seen && !bit,
+                    VMSIF => !seen,
+                    _ => !seen && bit,
+                };
+                seen |= bit;
+                vregs(cpu).write_mask(v.vd(), idx, res);
+            }
+        }
+        VIOTA => {
+            if cpu.csr_file.get_vstart() != 0 {
+                return Err(illegal(instr));
+            }
+            check_group(instr, v.vd(), config.group_regs())?;
+            check_mask_overlap(instr, v)?;
+
+            let mut count = 0;
+            for idx in 0..vl {
+                if !is_active(cpu, v, idx) {
+                    continue;
+                }
+                let bit = vregs_ref(cpu).read_mask(v.vs2(), idx);
+                vregs(cpu).write(v.vd(), idx, sew, count & elem_mask(sew));
+                count += bit as u64;
+            }
+        }
+        VID if v.vs2() == 0 => {
+            check_group(instr, v.vd(), config.group_regs())?;
+            check_mask_overlap(instr, v)?;
+
+            for idx in cpu.csr_file.get_vstart() as usize..vl {
+                if is_active(cpu, v, idx) {
+                    vregs(cpu).write(v.vd(), idx, sew, idx as u64 & elem_mask(sew));
+                }
+            }
+        }
+        _ => return Err(illegal(instr)),
+    }
+
+    retire(cpu);
+    Ok(())
+}
+
+/// Mask-register logical instructions, always unmasked.
+fn exec_mask_logical(cpu: &mut Cpu, instr: Instr) -> Result<(), Trap> {
+    let v = instr.as_v_type();
+    if !v.vm() {
+        return Err(illegal(instr));
+    }
+
+    let vl = cpu.csr_file.get_vl() as usize;
+    let start = cpu.csr_file.get_vstart() as usize;
+    let results: Vec<bool> = (start..vl)
+        .map(|idx| {
+            let a = vregs_ref(cpu).read_mask(v.vs2(), idx);
+            let b = vregs_ref(cpu).read_mask(v.rs1(), idx);
+            match v.funct6() {
+                VMANDN => a & !b,
+                VMAND => a & b,
+                VMOR => a | b,
+                VMXOR => a ^ b,
+                VMORN => a | !b,
+                VMNAND => !(a & b),
+                VMNOR => !(a | b),
+                _ => !(a ^ b),
+            }
+        })
+        .collect();
+
+    for (idx, bit) in (start..vl).zip(results) {
+        vregs(cpu).write_mask(v.vd(), idx, bit);
+    }
+
+    retire(cpu);
+    Ok(())
+}
+
+fn exec_multiply_divide(cpu: &mut Cpu, instr: Instr, config: &VConfig) -> Result<(), Trap> {
+    let v = instr.as_v_type();
+    check_vector_groups(instr, config)?;
+
+    let sew = config.sew;
+    let mask = elem_mask(sew);
+    let smin = 1u64 << (sew - 1);
+
+    for idx in cpu.csr_file.get_vstart() as usize..cpu.csr_file.get_vl() as usize {
+        if !is_active(cpu, v, idx) {
+            continue;
+        }
+        let a = vregs_ref(cpu).read(v.vs2(), idx, sew);
+        let b = operand(cpu, instr, idx, sew);
+        let (sa, sb) = (sext_elem(a, sew), sext_elem(b, sew));
+
+        let res = match v.funct6() {
+            VDIVU if b == 0 => mask,
+            VDIVU => a / b,
+            VREMU if b == 0 => a,
+            VREMU => a % b,
+            VDIV if b == 0 => mask,
+            VDIV if a == smin && sb == -1 => a,
+            VDIV => (sa / sb) as u64,
+            VREM if b == 0 => a,
+            VREM if a == smin && sb == -1 => 0,
+            VREM => (sa % sb) as u64,
+            VMUL => a.wrapping_mul(b),
+            VMULHU => ((a as u128 * b as u128) >> sew) as u64,
+            VMULH => ((sa as i128 * sb as i128) >> sew) as u64,
+            VMULHSU => ((sa as i128 * b as i128) >> sew) as u64,
+            _ => return Err(illegal(instr)),
+        };
+        vregs(cpu).write(v.vd(), idx, sew, res & mask);
+    }
+
+    retire(cpu);
+    Ok(())
+}
+
+/// VMACC / VNMSAC overwrite the addend, VMADD / VNMSUB the multiplicand.
+fn exec_multiply_add(cpu: &mut Cpu, instr: Instr, config: &VConfig) -> Result<(), Trap> {
+    let v = instr.as_v_type();
+    check_vector_groups(instr, config)?;
+
+    let sew = config.sew;
+    for idx in cpu.csr_file.get_vstart() as usize..cpu.csr_file.get_vl() as usize {
+        if !is_active(cpu, v, idx) {
+            continue;
+        }
+        let vs2 = vregs_ref(cpu).read(v.vs2(), idx, sew);
+        let vd = vregs_ref(cpu).read(v.vd(), idx, sew);
+        let op1 = operand(cpu, instr, idx, sew);
+
+        let res = match v.funct6() {
+            VMACC => op1.wrapping_mul(vs2).wrapping_add(vd),
+            VNMSAC => vd.wrapping_sub(op1.wrapping_mul(vs2)),
+            VMADD => op1.wrapping_mul(vd).wrapping_add(vs2),
+            _ => vs2.wrapping_sub(op1.wrapping_mul(vd)), // VNMSUB
+        };
+        vregs(cpu).write(v.vd(), idx, sew, res & elem_mask(sew));
+    }
+
+    retire(cpu);
+    Ok(())
+}
@@ -0,0 +1,39 @@
+use crate::{
+    cpu::Cpu,
+    isa::Instr,
+    isa::opcodes::CZERO_FUNCT7,
+    trap::{Exception, Trap},
+};
+
+pub fn is_czero(instr: Instr) -> bool {
+    instr.funct7() == CZERO_FUNCT7
+}
+
+pub fn exec_czero(cpu: &mut Cpu, instr: Instr) -> Result<(), Trap> {
+    let r = instr.as_r_type();
+    let rs1_val = cpu.reg_file.read(r.rs1());
+    let cond = cpu.reg_file.read(r.rs2());
+
+    let res = match instr.funct3() {
+        // CZERO.EQZ
+        0b101 if cpu.extensions.zicond => {
+            if cond == 0 {
+                0
+            } else {
+                rs1_val
+            }
+        }
+        // CZERO.NEZ
+        0b111 if cpu.extensions.zicond => {
+            if cond != 0 {
+                0
+            } else {
+                rs1_val
+            }
+        }
+        _ => return Err(Trap::Exception(Exception::IllegalInstruction(instr))),
+    };
+
+    cpu.reg_file.write(r.rd(), res);
+    Ok(())
+}
@@ -6,50 +6,59 @@ use crate::{
 
 pub fn exec_csr(cpu: &mut Cpu, instr: Instr) -> Result<(), Trap> {
     let i = instr.as_i_type();
-    let csr_addr = i.imm() as u16;
+    let csr_addr = (i.imm() as u16) & 0xfff;
     let rd = i.rd();
     let rs1 = i.rs1();
     let funct3 = instr.funct3();
 
-    match funct3 {
+    // the immediate forms use the rs1 field as a 5 bit zero-extended operand
+    let operand = if funct3 & 0b100 != 0 {
+        rs1 as u64
+    } else {
+        cpu.reg_file.read(rs1)
+    };
+
+    match funct3 & 0b011 {
         0b001 => {
-            // CSRRW
+            // CSRRW / CSRRWI
             let csr_val = cpu
                 .csr_file
                 .read(csr_addr)
                 .map_err(|_| Trap::Exception(Exception::IllegalInstruction(instr)))?;
-            let rs1_val = cpu.reg_file.read(rs1);
             cpu.csr_file
-                .write(csr_addr, rs1_val)
+                .write(csr_addr, operand)
                 .map_err(|_| Trap::Exception(Exception::IllegalInstruction(instr)))?;
             cpu.reg_file.write(rd, csr_val);
             Ok(())
         }
         0b010 => {
-            // CSRRS
+            // CSRRS / CSRRSI
             let csr_val = cpu
                 .csr_file
                 .read(csr_addr)
                 .map_err(|_| Trap::Exception(Exception::IllegalInstruction(instr)))?;
-            let rs1_val = cpu.reg_file.read(rs1);
-            let new_csr_val = csr_val | rs1_val;
-            cpu.csr_file
-                .write(csr_addr, new_csr_val)
-                .map_err(|_| Trap::Exception(Exception::IllegalInstruction(instr)))?;
+            // rs1 = x0 (or uimm = 0) reads without writing, e.g. for read-only csrs
+            if rs1 != 0 {
+                let new_csr_val = csr_val | operand;
+                cpu.csr_file
+                    .write(csr_addr, new_csr_val)
+                    .map_err(|_| Trap::Exception(Exception::IllegalInstruction(instr)))?;
+            }
             cpu.reg_file.write(rd, csr_val);
             Ok(())
         }
         0b011 => {
-            // CSRRC
+            // CSRRC / CSRRCI
             let csr_val = cpu
                 .csr_file
                 .read(csr_addr)
                 .map_err(|_| Trap::Exception(Exception::IllegalInstruction(instr)))?;
-            let rs1_val = cpu.reg_file.read(rs1);
-            let new_csr_val = csr_val & !rs1_val;
-            cpu.csr_file
-                .write(csr_addr, new_csr_val)
-                .map_err(|_| Trap::Exception(Exception::IllegalInstruction(instr)))?;
+            if rs1 != 0 {
+                let new_csr_val = csr_val & !operand;
+                cpu.csr_file
+                    .write(csr_addr, new_csr_val)
+                    .map_err(|_| Trap::Exception(Exception::IllegalInstruction(instr)))?;
+            }
             cpu.reg_file.write(rd, csr_val);
             Ok(())
         }
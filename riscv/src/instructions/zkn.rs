@@ -0,0 +1,425 @@
+use crate::{
+    cpu::Cpu,
+    isa::opcodes::{OP_32, OP_IMM, OP_IMM_32, OP_REG},
+    isa::{Instr, Xlen},
+    trap::{Exception, Trap},
+};
+
+// Scalar cryptography, NIST suite: Zbkb (bit manipulation for crypto),
+// Zknh (SHA-2 sigma functions), Zkne/Zknd (AES encryption/decryption).
+
+pub fn exec_zkn(cpu: &mut Cpu, instr: Instr) -> Result<(), Trap> {
+    match instr.opcode() {
+        OP_REG => exec_op_reg(cpu, instr),
+        OP_IMM => exec_op_imm(cpu, instr),
+        OP_32 if cpu.xlen == Xlen::Rv64 => exec_op_32(cpu, instr),
+        OP_IMM_32 if cpu.xlen == Xlen::Rv64 => exec_op_imm_32(cpu, instr),
+        _ => Err(Trap::Exception(Exception::IllegalInstruction(instr))),
+    }
+}
+
+fn exec_op_reg(cpu: &mut Cpu, instr: Instr) -> Result<(), Trap> {
+    let r = instr.as_r_type();
+    let ext = cpu.extensions;
+    let xlen = cpu.xlen;
+    let rs1_val = cpu.reg_file.read(r.rs1());
+    let rs2_val = cpu.reg_file.read(r.rs2());
+    let funct7 = instr.funct7();
+
+    // aes32* encode a byte select in funct7[6:5]
+    if xlen == Xlen::Rv32 && instr.funct3() == 0b000 && funct7 & 0x1f >= 0b10001 {
+        let bs = funct7 >> 5;
+        let (rs1_val, rs2_val) = (rs1_val as u32, rs2_val as u32);
+        let res = match funct7 & 0x1f {
+            0b10001 if ext.zkne => aes32(rs1_val, rs2_val, bs, |b| SBOX[b as usize] as u32),
+            0b10011 if ext.zkne => aes32(rs1_val, rs2_val, bs, |b| {
+                mix_column_byte_fwd(SBOX[b as usize])
+            }),
+            0b10101 if ext.zknd => aes32(rs1_val, rs2_val, bs, |b| INV_SBOX[b as usize] as u32),
+            0b10111 if ext.zknd => aes32(rs1_val, rs2_val, bs, |b| {
+                mix_column_byte_inv(INV_SBOX[b as usize])
+            }),
+            _ => return Err(Trap::Exception(Exception::IllegalInstruction(instr))),
+        };
+        cpu.reg_file.write(r.rd(), res as u64);
+        return Ok(());
+    }
+
+    let res = match (funct7, instr.funct3()) {
+        (0b0100000, 0b111) if ext.zbkb => {
+            // ANDN
+            rs1_val & !rs2_val
+        }
+        (0b0100000, 0b110) if ext.zbkb => {
+            // ORN
+            rs1_val | !rs2_val
+        }
+        (0b0100000, 0b100) if ext.zbkb => {
+            // XNOR
+            !(rs1_val ^ rs2_val)
+        }
+        (0b0110000, 0b001) if ext.zbkb => {
+            // ROL
+            rotate_left(xlen, rs1_val, rs2_val)
+        }
+        (0b0110000, 0b101) if ext.zbkb => {
+            // ROR
+            rotate_right(xlen, rs1_val, rs2_val)
+        }
+        (0b0000100, 0b100) if ext.zbkb => {
+            // PACK
+            let half = xlen.bits() / 2;
+            let mask = (1u64 << half) - 1;
+            ((rs2_val & mask) << half) | (rs1_val & mask)
+        }
+        (0b0000100, 0b111) if ext.zbkb => {
+            // PACKH
+            ((rs2_val & 0xff) << 8) | (rs1_val & 0xff)
+        }
+        _ if ext.zknh && xlen == Xlen::Rv32 => {
+            exec_sha512_rv32(instr, rs1_val as u32, rs2_val as u32)?
+        }
+        _ if xlen == Xlen::Rv64 => exec_aes64(cpu, instr, rs1_val, rs2_val)?,
+        _ => return Err(Trap::Exception(Exception::IllegalInstruction(instr))),
+    };
+
+    cpu.reg_file.write(r.rd(), res);
+    Ok(())
+}
+
+fn exec_op_imm(cpu: &mut Cpu, instr: Instr) -> Result<(), Trap> {
+    let i = instr.as_i_type();
+    let ext = cpu.extensions;
+    let xlen = cpu.xlen;
+    let rs1_val = cpu.reg_file.read(i.rs1());
+    let imm = (i.imm() as u32) & 0xfff;
+    let funct3 = instr.funct3();
+
+    let res = match (funct3, imm) {
+        (0b101, _) if ext.zbkb && instr.funct6() == 0b011000 => {
+            // RORI
+            let shamt = imm & 0x3f;
+            if shamt as u64 > xlen.shamt_mask() {
+                return Err(Trap::Exception(Exception::IllegalInstruction(instr)));
+            }
+            rotate_right(xlen, rs1_val, shamt as u64)
+        }
+        (0b101, 0x698) if ext.zbkb && xlen == Xlen::Rv32 => {
+            // REV8
+            (rs1_val as u32).swap_bytes() as u64
+        }
+        (0b101, 0x6b8) if ext.zbkb && xlen == Xlen::Rv64 => {
+            // REV8
+            rs1_val.swap_bytes()
+        }
+        (0b101, 0x687) if ext.zbkb => {
+            // BREV8
+            u64::from_le_bytes(rs1_val.to_le_bytes().map(u8::reverse_bits))
+        }
+        (0b001, 0x08f) if ext.zbkb && xlen == Xlen::Rv32 => {
+            // ZIP
+            zip(rs1_val as u32) as u64
+        }
+        (0b101, 0x08f) if ext.zbkb && xlen == Xlen::Rv32 => {
+            // UNZIP
+            unzip(rs1_val as u32) as u64
+        }
+        (0b001, 0x100..=0x103) if ext.zknh => {
+            let x = rs1_val as u32;
+            let res = match imm {
+                // SHA256SUM0
+                0x100 => x.rotate_right(2) ^ x.rotate_right(13) ^ x.rotate_right(22),
+                // SHA256SUM1
+                0x101 => x.rotate_right(6) ^ x.rotate_right(11) ^ x.rotate_right(25),
+                // SHA256SIG0
+                0x102 => x.rotate_right(7) ^ x.rotate_right(18) ^ (x >> 3),
+                // SHA256SIG1
+                _ => x.rotate_right(17) ^ x.rotate_right(19) ^ (x >> 10),
+            };
+            res as i32 as u64
+        }
+        (0b001, 0x104..=0x107) if ext.zknh && xlen == Xlen::Rv64 => {
+            let x = rs1_val;
+            match imm {
+                // SHA512SUM0
+                0x104 => x.rotate_right(28) ^ x.rotate_right(34) ^ x.rotate_right(39),
+                // SHA512SUM1
+                0x105 => x.rotate_right(14) ^ x.rotate_right(18) ^ x.rotate_right(41),
+                // SHA512SIG0
+                0x106 => x.rotate_right(1) ^ x.rotate_right(8) ^ (x >> 7),
+                // SHA512SIG1
+                _ => x.rotate_right(19) ^ x.rotate_right(61) ^ (x >> 6),
+            }
+        }
+        (0b001, 0x300) if ext.zknd && xlen == Xlen::Rv64 => {
+            // AES64IM
+            let lo = mix_column_inv(rs1_val as u32) as u64;
+            let hi = mix_column_inv((rs1_val >> 32) as u32) as u64;
+            (hi << 32) | lo
+        }
+        (0b001, 0x310..=0x31a) if (ext.zkne || ext.zknd) && xlen == Xlen::Rv64 => {
+            // AES64KS1I
+            let rnum = imm & 0xf;
+            let word = (rs1_val >> 32) as u32;
+            let word = if rnum == 0xa {
+                word
+            } else {
+                word.rotate_right(8)
+            };
+            let res = sub_word(word, &SBOX) ^ AES_RCON[rnum as usize];
+            ((res as u64) << 32) | res as u64
+        }
+        _ => return Err(Trap::Exception(Exception::IllegalInstruction(instr))),
+    };
+
+    cpu.reg_file.write(i.rd(), res);
+    Ok(())
+}
+
+fn exec_op_32(cpu: &mut Cpu, instr: Instr) -> Result<(), Trap> {
+    let r = instr.as_r_type();
+    let rs1_val = cpu.reg_file.read(r.rs1()) as u32;
+    let rs2_val = cpu.reg_file.read(r.rs2()) as u32;
+
+    if !cpu.extensions.zbkb {
+        return Err(Trap::Exception(Exception::IllegalInstruction(instr)));
+    }
+
+    let res = match (instr.funct7(), instr.funct3()) {
+        (0b0110000, 0b001) => {
+            // ROLW
+            rs1_val.rotate_left(rs2_val & 0x1f)
+        }
+        (0b0110000, 0b101) => {
+            // RORW
+            rs1_val.rotate_right(rs2_val & 0x1f)
+        }
+        (0b0000100, 0b100) => {
+            // PACKW
+            ((rs2_val & 0xffff) << 16) | (rs1_val & 0xffff)
+        }
+        _ => return Err(Trap::Exception(Exception::IllegalInstruction(instr))),
+    };
+
+    cpu.reg_file.write(r.rd(), res as i32 as u64);
+    Ok(())
+}
+
+fn exec_op_imm_32(cpu: &mut Cpu, instr: Instr) -> Result<(), Trap> {
+    let i = instr.as_i_type();
+    let rs1_val = cpu.reg_file.read(i.rs1()) as u32;
+
+    match (instr.funct7(), instr.funct3()) {
+        (0b0110000, 0b101) if cpu.extensions.zbkb => {
+            // RORIW
+            let shamt = (i.imm() & 0x1f) as u32;
+            cpu.reg_file
+                .write(i.rd(), rs1_val.rotate_right(shamt) as i32 as u64);
+            Ok(())
+        }
+        _ => Err(Trap::Exception(Exception::IllegalInstruction(instr))),
+    }
+}
+
+/// SHA512SUM0R / SHA512SUM1R / SHA512SIG0L / SHA512SIG0H / SHA512SIG1L /
+/// SHA512SIG1H: rv32 halves of the SHA-512 functions.
+fn exec_sha512_rv32(instr: Instr, rs1: u32, rs2: u32) -> Result<u64, Trap> {
+    let res = match (instr.funct7(), instr.funct3()) {
+        (0b0101000, 0b000) => {
+            (rs1 << 25) ^ (rs1 << 30) ^ (rs1 >> 28) ^ (rs2 >> 7) ^ (rs2 >> 2) ^ (rs2 << 4)
+        }
+        (0b0101001, 0b000) => {
+            (rs1 << 23) ^ (rs1 >> 14) ^ (rs1 >> 18) ^ (rs2 >> 9) ^ (rs2 << 18) ^ (rs2 << 14)
+        }
+        (0b0101010, 0b000) => {
+            (rs1 >> 1) ^ (rs1 >> 7) ^ (rs1 >> 8) ^ (rs2 << 31) ^ (rs2 << 25) ^ (rs2 << 24)
+        }
+        (0b0101110, 0b000) => (rs1 >> 1) ^ (rs1 >> 7) ^ (rs1 >> 8) ^ (rs2 << 31) ^ (rs2 << 24),
+        (0b0101011, 0b000) => {
+            (rs1 << 3) ^ (rs1 >> 6) ^ (rs1 >> 19) ^ (rs2 >> 29) ^ (rs2 << 26) ^ (rs2 << 13)
+        }
+        (0b0101111, 0b000) => (rs1 << 3) ^ (rs1 >> 6) ^ (rs1 >> 19) ^ (rs2 >> 29) ^ (rs2 << 13),
+        _ => return Err(Trap::Exception(Exception::IllegalInstruction(instr))),
+    };
+    Ok(res as i32 as u64)
+}
+
+/// AES64ES / AES64ESM / AES64DS / AES64DSM / AES64KS2
+fn exec_aes64(cpu: &Cpu, instr: Instr, rs1: u64, rs2: u64) -> Result<u64, Trap> {
+    let ext = cpu.extensions;
+    let res = match (instr.funct7(), instr.funct3()) {
+        (0b0011001, 0b000) if ext.zkne => sub_bytes(shift_rows(rs1, rs2, true), &SBOX),
+        (0b0011011, 0b000) if ext.zkne => {
+            mix_columns(sub_bytes(shift_rows(rs1, rs2, true), &SBOX), mix_column_fwd)
+        }
+        (0b0011101, 0b000) if ext.zknd => sub_bytes(shift_rows(rs1, rs2, false), &INV_SBOX),
+        (0b0011111, 0b000) if ext.zknd => mix_columns(
+            sub_bytes(shift_rows(rs1, rs2, false), &INV_SBOX),
+            mix_column_inv,
+        ),
+        (0b0111111, 0b000) if ext.zkne || ext.zknd => {
+            let w0 = (rs1 >> 32) as u32 ^ rs2 as u32;
+            let w1 = w0 ^ (rs2 >> 32) as u32;
+            ((w1 as u64) << 32) | w0 as u64
+        }
+        _ => return Err(Trap::Exception(Exception::IllegalInstruction(instr))),
+    };
+    Ok(res)
+}
+
+fn rotate_left(xlen: Xlen, val: u64, amount: u64) -> u64 {
+    let amount = (amount & xlen.shamt_mask()) as u32;
+    match xlen {
+        Xlen::Rv32 => (val as u32).rotate_left(amount) as u64,
+        Xlen::Rv64 => val.rotate_left(amount),
+    }
+}
+
+fn rotate_right(xlen: Xlen, val: u64, amount: u64) -> u64 {
+    let amount = (amount & xlen.shamt_mask()) as u32;
+    match xlen {
+        Xlen::Rv32 => (val as u32).rotate_right(amount) as u64,
+        Xlen::Rv64 => val.rotate_right(amount),
+    }
+}
+
+/// Interleaves the low half (even bits) with the high half (odd bits).
+fn zip(val: u32) -> u32 {
+    (0..16).fold(0, |acc, i| {
+        acc | (((val >> i) & 1) << (2 * i)) | (((val >> (i + 16)) & 1) << (2 * i + 1))
+    })
+}
+
+fn unzip(val: u32) -> u32 {
+    (0..16).fold(0, |acc, i| {
+        acc | (((val >> (2 * i)) & 1) << i) | (((val >> (2 * i + 1)) & 1) << (i + 16))
+    })
+}
+
+/// AES32ES(M)I / AES32DS(M)I: transform byte `bs` of rs2 and xor it into rs1
+/// at the same position.
+fn aes32(rs1: u32, rs2: u32, bs: u8, transform: impl Fn(u8) -> u32) -> u32 {
+    let shamt = bs as u32 * 8;
+    let byte = (rs2 >> shamt) as u8;
+    rs1 ^ transform(byte).rotate_left(shamt)
+}
+
+/// Multiplication by x in GF(2^8).
+fn xtime(b: u8) -> u8 {
+    (b << 1) ^ if b & 0x80 != 0 { 0x1b } else { 0 }
+}
+
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut res = 0;
+    while b != 0 {
+        if b & 1 != 0 {
+            res ^= a;
+        }
+        a = xtime(a);
+        b >>= 1;
+    }
+    res
+}
+
+/// One byte's contribution to a forward MixColumns column: {3, 1, 1, 2}.
+fn mix_column_byte_fwd(b: u8) -> u32 {
+    u32::from_le_bytes([gf_mul(b, 2), b, b, gf_mul(b, 3)])
+}
+
+/// One byte's contribution to an inverse MixColumns column: {b, d, 9, e}.
+fn mix_column_byte_inv(b: u8) -> u32 {
+    u32::from_le_bytes([
+        gf_mul(b, 0xe),
+        gf_mul(b, 0x9),
+        gf_mul(b, 0xd),
+        gf_mul(b, 0xb),
+    ])
+}
+
+fn mix_column_fwd(col: u32) -> u32 {
+    (0..4).fold(0, |acc, i| {
+        acc ^ mix_column_byte_fwd((col >> (8 * i)) as u8).rotate_left(8 * i)
+    })
+}
+
+fn mix_column_inv(col: u32) -> u32 {
+    (0..4).fold(0, |acc, i| {
+        acc ^ mix_column_byte_inv((col >> (8 * i)) as u8).rotate_left(8 * i)
+    })
+}
+
+fn mix_columns(val: u64, mix: fn(u32) -> u32) -> u64 {
+    ((mix((val >> 32) as u32) as u64) << 32) | mix(val as u32) as u64
+}
+
+/// Low 64 bits (columns 0 and 1) of ShiftRows, or its inverse, applied to
+/// the 128-bit state `rs2:rs1`.
+fn shift_rows(rs1: u64, rs2: u64, forward: bool) -> u64 {
+    let state = ((rs2 as u128) << 64) | rs1 as u128;
+    let byte = |col: usize, row: usize| (state >> (32 * col + 8 * row)) as u8 as u64;
+
+    let mut res = 0;
+    for col in 0..2 {
+        for row in 0..4 {
+            let src = if forward {
+                (col + row) % 4
+            } else {
+                (col + 4 - row) % 4
+            };
+            res |= byte(src, row) << (32 * col + 8 * row);
+        }
+    }
+    res
+}
+
+fn sub_bytes(val: u64, sbox: &[u8; 256]) -> u64 {
+    u64::from_le_bytes(val.to_le_bytes().map(|b| sbox[b as usize]))
+}
+
+fn sub_word(val: u32, sbox: &[u8; 256]) -> u32 {
+    u32::from_le_bytes(val.to_le_bytes().map(|b| sbox[b as usize]))
+}
+
+const AES_RCON: [u32; 11] = [
+    0x01, 0x02, 0x04, 0x08, 0x10, 0x20, 0x40, 0x80, 0x1b, 0x36, 0x00,
+];
+
+#[rustfmt::skip]
+const SBOX: [u8; 256] = [
+    0x63, 0x7c, 0x77, 0x7b, 0xf2, 0x6b, 0x6f, 0xc5, 0x30, 0x01, 0x67, 0x2b, 0xfe, 0xd7, 0xab, 0x76,
+    0xca, 0x82, 0xc9, 0x7d, 0xfa, 0x59, 0x47, 0xf0, 0xad, 0xd4, 0xa2, 0xaf, 0x9c, 0xa4, 0x72, 0xc0,
+    0xb7, 0xfd, 0x93, 0x26, 0x36, 0x3f, 0xf7, 0xcc, 0x34, 0xa5, 0xe5, 0xf1, 0x71, 0xd8, 0x31, 0x15,
+    0x04, 0xc7, 0x23, 0xc3, 0x18, 0x96, 0x05, 0x9a, 0x07, 0x12, 0x80, 0xe2, 0xeb, 0x27, 0xb2, 0x75,
+    0x09, 0x83, 0x2c, 0x1a, 0x1b, 0x6e, 0x5a, 0xa0, 0x52, 0x3b, 0xd6, 0xb3, 0x29, 0xe3, 0x2f, 0x84,
+    0x53, 0xd1, 0x00, 0xed, 0x20, 0xfc, 0xb1, 0x5b, 0x6a, 0xcb, 0xbe, 0x39, 0x4a, 0x4c, 0x58, 0xcf,
+    0xd0, 0xef, 0xaa, 0xfb, 0x43, 0x4d, 0x33, 0x85, 0x45, 0xf9, 0x02, 0x7f, 0x50, 0x3c, 0x9f, 0xa8,
+    0x51, 0xa3, 0x40, 0x8f, 0x92, 0x9d, 0x38, 0xf5, 0xbc, 0xb6, 0xda, 0x21, 0x10, 0xff, 0xf3, 0xd2,
+    0xcd, 0x0c, 0x13, 0xec, 0x5f, 0x97, 0x44, 0x17, 0xc4, 0xa7, 0x7e, 0x3d, 0x64, 0x5d, 0x19, 0x73,
+    0x60, 0x81, 0x4f, 0xdc, 0x22, 0x2a, 0x90, 0x88, 0x46, 0xee, 0xb8, 0x14, 0xde, 0x5e, 0x0b, 0xdb,
+    0xe0, 0x32, 0x3a, 0x0a, 0x49, 0x06, 0x24, 0x5c, 0xc2, 0xd3, 0xac, 0x62, 0x91, 0x95, 0xe4, 0x79,
+    0xe7, 0xc8, 0x37, 0x6d, 0x8d, 0xd5, 0x4e, 0xa9, 0x6c, 0x56, 0xf4, 0xea, 0x65, 0x7a, 0xae, 0x08,
+    0xba, 0x78, 0x25, 0x2e, 0x1c, 0xa6, 0xb4, 0xc6, 0xe8, 0xdd, 0x74, 0x1f, 0x4b, 0xbd, 0x8b, 0x8a,
+    0x70, 0x3e, 0xb5, 0x66, 0x48, 0x03, 0xf6, 0x0e, 0x61, 0x35, 0x57, 0xb9, 0x86, 0xc1, 0x1d, 0x9e,
+    0xe1, 0xf8, 0x98, 0x11, 0x69, 0xd9, 0x8e, 0x94, 0x9b, 0x1e, 0x87, 0xe9, 0xce, 0x55, 0x28, 0xdf,
+    0x8c, 0xa1, 0x89, 0x0d, 0xbf, 0xe6, 0x42, 0x68, 0x41, 0x99, 0x2d, 0x0f, 0xb0, 0x54, 0xbb, 0x16,
+];
+
+#[rustfmt::skip]
+const INV_SBOX: [u8; 256] = [
+    0x52, 0x09, 0x6a, 0xd5, 0x30, 0x36, 0xa5, 0x38, 0xbf, 0x40, 0xa3, 0x9e, 0x81, 0xf3, 0xd7, 0xfb,
+    0x7c, 0xe3, 0x39, 0x82, 0x9b, 0x2f, 0xff, 0x87, 0x34, 0x8e, 0x43, 0x44, 0xc4, 0xde, 0xe9, 0xcb,
+    0x54, 0x7b, 0x94, 0x32, 0xa6, 0xc2, 0x23, 0x3d, 0xee, 0x4c, 0x95, 0x0b, 0x42, 0xfa, 0xc3, 0x4e,
+    0x08, 0x2e, 0xa1, 0x66, 0x28, 0xd9, 0x24, 0xb2, 0x76, 0x5b, 0xa2, 0x49, 0x6d, 0x8b, 0xd1, 0x25,
+    0x72, 0xf8, 0xf6, 0x64, 0x86, 0x68, 0x98, 0x16, 0xd4, 0xa4, 0x5c, 0xcc, 0x5d, 0x65, 0xb6, 0x92,
+    0x6c, 0x70, 0x48, 0x50, 0xfd, 0xed, 0xb9, 0xda, 0x5e, 0x15, 0x46, 0x57, 0xa7, 0x8d, 0x9d, 0x84,
+    0x90, 0xd8, 0xab, 0x00, 0x8c, 0xbc, 0xd3, 0x0a, 0xf7, 0xe4, 0x58, 0x05, 0xb8, 0xb3, 0x45, 0x06,
+    0xd0, 0x2c, 0x1e, 0x8f, 0xca, 0x3f, 0x0f, 0x02, 0xc1, 0xaf, 0xbd, 0x03, 0x01, 0x13, 0x8a, 0x6b,
+    0x3a, 0x91, 0x11, 0x41, 0x4f, 0x67, 0xdc, 0xea, 0x97, 0xf2, 0xcf, 0xce, 0xf0, 0xb4, 0xe6, 0x73,
+    0x96, 0xac, 0x74, 0x22, 0xe7, 0xad, 0x35, 0x85, 0xe2, 0xf9, 0x37, 0xe8, 0x1c, 0x75, 0xdf, 0x6e,
+    0x47, 0xf1, 0x1a, 0x71, 0x1d, 0x29, 0xc5, 0x89, 0x6f, 0xb7, 0x62, 0x0e, 0xaa, 0x18, 0xbe, 0x1b,
+    0xfc, 0x56, 0x3e, 0x4b, 0xc6, 0xd2, 0x79, 0x20, 0x9a, 0xdb, 0xc0, 0xfe, 0x78, 0xcd, 0x5a, 0xf4,
+    0x1f, 0xdd, 0xa8, 0x33, 0x88, 0x07, 0xc7, 0x31, 0xb1, 0x12, 0x10, 0x59, 0x27, 0x80, 0xec, 0x5f,
+    0x60, 0x51, 0x7f, 0xa9, 0x19, 0xb5, 0x4a, 0x0d, 0x2d, 0xe5, 0x7a, 0x9f, 0x93, 0xc9, 0x9c, 0xef,
+    0xa0, 0xe0, 0x3b, 0x4d, 0xae, 0x2a, 0xf5, 0xb0, 0xc8, 0xeb, 0xbb, 0x3c, 0x83, 0x53, 0x99, 0x61,
+    0x17, 0x2b, 0x04, 0x7e, 0xba, 0x77, 0xd6, 0x26, 0xe1, 0x69, 0x14, 0x63, 0x55, 0x21, 0x0c, 0x7d,
+];
@@ -156,3 +156,67 @@ impl BType {
         (imm << 19) >> 19
     }
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(transparent)]
+pub struct VType(pub(super) Instr);
+
+impl VType {
+    #[inline(always)]
+    pub fn vd(&self) -> u8 {
+        let raw = (self.0).0;
+        ((raw >> 7) & 0x1f) as u8
+    }
+
+    #[inline(always)]
+    pub fn rs1(&self) -> u8 {
+        let raw = (self.0).0;
+        ((raw >> 15) & 0x1f) as u8
+    }
+
+    #[inline(always)]
+    pub fn vs2(&self) -> u8 {
+        let raw = (self.0).0;
+        ((raw >> 20) & 0x1f) as u8
+    }
+
+    /// `true` if the instruction is unmasked.
+    #[inline(always)]
+    pub fn vm(&self) -> bool {
+        let raw = (self.0).0;
+        (raw >> 25) & 0x1 != 0
+    }
+
+    #[inline(always)]
+    pub fn funct6(&self) -> u8 {
+        let raw = (self.0).0;
+        ((raw >> 26) & 0x3f) as u8
+    }
+
+    /// 5 bit immediate in the rs1 field, sign-extended.
+    #[inline(always)]
+    pub fn simm5(&self) -> i32 {
+        let raw = (self.0).0;
+        ((raw as i32) << 12) >> 27
+    }
+
+    // memory operations
+
+    #[inline(always)]
+    pub fn mop(&self) -> u8 {
+        let raw = (self.0).0;
+        ((raw >> 26) & 0x3) as u8
+    }
+
+    #[inline(always)]
+    pub fn mew(&self) -> bool {
+        let raw = (self.0).0;
+        (raw >> 28) & 0x1 != 0
+    }
+
+    #[inline(always)]
+    pub fn nf(&self) -> u8 {
+        let raw = (self.0).0;
+        ((raw >> 29) & 0x7) as u8
+    }
+}
@@ -1,9 +1,11 @@
 pub mod formats;
 pub mod opcodes;
 pub mod priv_mode;
+pub mod xlen;
 
 pub use formats::*;
 pub use priv_mode::*;
+pub use xlen::*;
 
 pub const INSTRUCTION_SIZE: u8 = 4;
 
@@ -32,6 +34,11 @@ impl Instr {
         ((self.word() >> 12) & 0x07) as u8
     }
 
+    #[inline(always)]
+    pub fn funct6(&self) -> u8 {
+        ((self.word() >> 26) & 0x3F) as u8
+    }
+
     #[inline(always)]
     pub fn funct7(&self) -> u8 {
         ((self.word() >> 25) & 0x7F) as u8
@@ -60,6 +67,10 @@ impl Instr {
     pub fn as_b_type(&self) -> BType {
         BType(*self)
     }
+
+    pub fn as_v_type(&self) -> VType {
+        VType(*self)
+    }
 }
 
 impl From<u32> for Instr {
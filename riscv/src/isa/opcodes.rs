@@ -1,5 +1,7 @@
 pub const OP_IMM: u8 = 0x13;
 pub const OP_REG: u8 = 0x33;
+pub const OP_IMM_32: u8 = 0x1b;
+pub const OP_32: u8 = 0x3b;
 pub const LUI: u8 = 0x37;
 pub const AUIPC: u8 = 0x17;
 pub const JAL: u8 = 0x6f;
@@ -9,7 +11,11 @@ pub const LOAD: u8 = 0x03;
 pub const STORE: u8 = 0x23;
 pub const MISC_MEM: u8 = 0x0f;
 pub const SYSTEM: u8 = 0x73;
+pub const LOAD_FP: u8 = 0x07;
+pub const STORE_FP: u8 = 0x27;
+pub const OP_V: u8 = 0x57;
 pub const FENCE: u8 = 0x0f;
 pub const FENCE_I: u8 = 0x0f;
 pub const ECALL: u32 = 0x00000073;
 pub const SFENCE_VMA_FUNCT7: u8 = 0b0001001;
+pub const CZERO_FUNCT7: u8 = 0b0000111;
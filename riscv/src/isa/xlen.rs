@@ -0,0 +1,53 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Xlen {
+    #[default]
+    Rv32,
+    Rv64,
+}
+
+impl Xlen {
+    #[inline(always)]
+    pub fn bits(self) -> u32 {
+        match self {
+            Xlen::Rv32 => 32,
+            Xlen::Rv64 => 64,
+        }
+    }
+
+    /// Value of the MXL/SXL/UXL fields for this register width.
+    pub fn mxl(self) -> u64 {
+        match self {
+            Xlen::Rv32 => 1,
+            Xlen::Rv64 => 2,
+        }
+    }
+
+    /// Canonical register representation: values are kept sign-extended
+    /// from XLEN to 64 bits, so RV32 results can be computed in 64 bits.
+    #[inline(always)]
+    pub fn sext(self, val: u64) -> u64 {
+        match self {
+            Xlen::Rv32 => val as i32 as i64 as u64,
+            Xlen::Rv64 => val,
+        }
+    }
+
+    /// Zero-extends the low XLEN bits, e.g. for addresses and CSR values.
+    #[inline(always)]
+    pub fn zext(self, val: u64) -> u64 {
+        match self {
+            Xlen::Rv32 => val & 0xffff_ffff,
+            Xlen::Rv64 => val,
+        }
+    }
+
+    #[inline(always)]
+    pub fn shamt_mask(self) -> u64 {
+        (self.bits() - 1) as u64
+    }
+
+    #[inline(always)]
+    pub fn sign_bit(self) -> u64 {
+        1 << (self.bits() - 1)
+    }
+}
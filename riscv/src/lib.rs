@@ -0,0 +1,14 @@
+// Fallible lookups (csr access, flashing) report a bare `Err(())` by design.
+#![allow(clippy::result_unit_err)]
+
+pub mod cpu;
+pub mod csrs;
+pub mod debug;
+pub mod devices;
+pub mod instructions;
+pub mod isa;
+pub mod profiling;
+pub mod regs;
+pub mod scripting;
+pub mod trap;
+pub mod vregs;
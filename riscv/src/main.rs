@@ -1,67 +1,788 @@
-mod cpu;
-mod csrs;
-mod debug;
-mod devices;
-mod instructions;
-mod isa;
-mod profiling;
-mod regs;
-mod trap;
-
 use goblin::elf::{self, program_header};
 use std::fs;
+use std::io;
 use std::path::Path;
+use std::process;
+
+use riscv::cpu::{Cpu, CpuConfig, Extensions};
+use riscv::devices::{
+    Bus, CableBackend, ConsolePort, DEFAULT_MAC, DEFAULT_NS_PER_CYCLE, Device, Disk, DiskImage,
+    Dram, DumpConfig, EscapeAction, FdtCpu, FileBackend, Framebuffer, GoldfishRtc, Htif, Loopback,
+    MemoryBackend, MemoryImage, Mode, NetBackend, PcapRecorder, PcapReplay, PixelFormat,
+    PowerControl, PowerRequest, PtyBackend, RawFile, ReadOnly, Screen, SparseDram, SparseImage,
+    StdioBackend, TestFinisher, Uart, UnixSocketBackend, Virtio9p, VirtioBlk, VirtioConsole,
+    VirtioMmio, VirtioNet, VirtioRng, Watchdog, WatchdogAction, boot_rom, machine_fdt,
+};
+use riscv::isa::Xlen;
+use riscv::profiling::IpsMonitor;
+use riscv::scripting::{Outcome, ScriptRunner, parser};
+
+const DEFAULT_VLEN: u32 = 128;
+
+/// Optional extensions the hart has unless `--ext` says otherwise.
+const ALL_EXTENSIONS: Extensions = Extensions {
+    zbkb: true,
+    zknh: true,
+    zkne: true,
+    zknd: true,
+    zicond: true,
+};
 
-use crate::cpu::Cpu;
-use crate::devices::{Bus, Disk, Dram, Uart};
-use crate::profiling::IpsMonitor;
+/// How often, in cycles, the main loop looks at the HTIF mailbox.
+const HTIF_POLL_INTERVAL: u64 = 1024;
 
-fn load_elf_into_ram(filename: &str, ram: &mut Dram, base_addr: u32) -> Result<(), String> {
+struct Kernel {
+    xlen: Xlen,
+    entry: u64,
+    /// HTIF mailboxes, present in riscv-tests and proxy kernel binaries.
+    tohost: Option<u64>,
+    fromhost: Option<u64>,
+}
+
+/// Loads the ELF's segments at their physical addresses and looks up the
+/// symbols the host needs.
+fn load_elf(filename: &str, bus: &mut Bus) -> Result<Kernel, String> {
     let filepath = Path::new(filename);
     let elf_data = fs::read(filepath).map_err(|e| format!("Failed to read ELF file: {}", e))?;
 
     let elf = elf::Elf::parse(&elf_data).map_err(|e| format!("Failed to parse ELF: {}", e))?;
 
-    for ph in elf.program_headers {
+    for ph in &elf.program_headers {
         if ph.p_type == program_header::PT_LOAD {
             let file_offset = ph.p_offset as usize;
-            let mem_addr = ph.p_vaddr as u32 - base_addr;
+            let mem_addr = ph.p_vaddr;
             let file_size = ph.p_filesz as usize;
-            let _mem_size = ph.p_memsz as usize;
+            let mem_size = ph.p_memsz as usize;
 
             let segment_data = &elf_data[file_offset..file_offset + file_size];
-            ram.flash(mem_addr, segment_data)
+            bus.write_bytes(mem_addr, segment_data)
                 .map_err(|e| format!("Failed to load segment into RAM: {:?}", e))?;
+
+            // .bss; RAM may still hold whatever ran before a reset
+            if mem_size > file_size {
+                let zeros = vec![0; mem_size - file_size];
+                bus.write_bytes(mem_addr + file_size as u64, &zeros)
+                    .map_err(|e| format!("Failed to clear segment in RAM: {:?}", e))?;
+            }
         }
     }
 
-    Ok(())
+    let symbol = |name: &str| {
+        elf.syms
+            .iter()
+            .find(|sym| elf.strtab.get_at(sym.st_name) == Some(name))
+            .map(|sym| sym.st_value)
+    };
+
+    Ok(Kernel {
+        xlen: if elf.is_64 { Xlen::Rv64 } else { Xlen::Rv32 },
+        entry: elf.entry,
+        tohost: symbol("tohost"),
+        fromhost: symbol("fromhost"),
+    })
 }
 
-fn main() {
-    let uart0 = Uart::new();
+const DEFAULT_KERNEL: &str =
+    "/Users/matthias/Documents/private/projects/osv/kernel/target/kernel.elf";
+const DEFAULT_DISK: &str = "/Users/matthias/Documents/private/projects/osv/kernel/target/disk";
+
+const USAGE: &str = "usage: riscv [--script FILE] [--debug-log FILE] [--rng-seed SEED] \
+                     [--share DIR | --share-ro DIR] [--snapshot] [--delta FILE] \
+                     [--apply-delta FILE] [--drive KIND:ARG]... [--disk-latency CYCLES] \
+                     [--ram SIZE] [--ram-file FILE] [--rom FILE] [--rtc CLOCK] \
+                     [--fb WxH[:FORMAT]] [--fb-dump FILE] [--fb-every N] \
+                     [--net KIND[:ARG]] [--net-record FILE] [--mac MAC] \
+                     [--watchdog reset|halt] [--serial KIND[:PATH]] [--ext LIST] \
+                     [--dump-dtb FILE] [KERNEL [DISK]]
+
+drive kinds: raw:FILE, ro:FILE, mem:FILE (in-memory copy), sparse:SIZE
+sizes take a K, M or G suffix
+rtc clocks: host, fixed:SECONDS (start time since the epoch, then real time),
+            cycles:SECONDS (start time, then a fixed step per cycle)
+fb formats: xrgb8888 (default), rgb565; dumps are PPM for .ppm files, PNG otherwise
+net kinds: loopback, listen:SOCKET, connect:SOCKET (a cable to another emulator),
+           replay:PCAP (feeds the capture to the guest)
+serial lines: stdio (default, with the Ctrl-A commands), pty, unix:SOCKET,
+              file:PATH (output only)
+extensions: comma-separated from v, zbkb, zknh, zkne, zknd, zicond, or none
+            (default: all of them)
+--dump-dtb writes the generated device tree and exits";
+
+const DEFAULT_RAM_SIZE: u32 = 1024 * 1024;
+
+/// The boot ROM, where the hart starts; below all other devices. A `--rom`
+/// image replaces the built-in one.
+const ROM_BASE: u64 = 0x1000;
+
+/// Nominal hart clock, matching the deterministic RTC's step. There is no
+/// `time` CSR; the guest can scale `mcycle` by this.
+const TIMEBASE_FREQUENCY: u32 = (1_000_000_000 / DEFAULT_NS_PER_CYCLE) as u32;
+
+/// Disks added with `--drive` are mapped one page apart from here.
+const EXTRA_DISK_BASE: u64 = 0x1001_0000;
+
+/// Framebuffer registers, followed by its pixels from `PIXEL_OFFSET`.
+const FRAMEBUFFER_BASE: u64 = 0x3000_0000;
+
+/// 9p mount tag of the shared directory.
+const SHARE_TAG: &str = "host";
+
+struct Share {
+    dir: String,
+    read_only: bool,
+}
+
+/// Host side of the UART.
+enum Serial {
+    Stdio,
+    Pty,
+    Unix(String),
+    File(String),
+}
+
+struct Args {
+    kernel: String,
+    disk: String,
+    script: Option<String>,
+    debug_log: Option<String>,
+    rng_seed: Option<u64>,
+    share: Option<Share>,
+    /// Keep guest writes in memory instead of the disk image.
+    snapshot: bool,
+    /// Where Ctrl-A s saves the snapshot delta.
+    delta: Option<String>,
+    apply_delta: Option<String>,
+    drives: Vec<DiskImage>,
+    ram_size: u32,
+    /// Back guest RAM with this file instead of anonymous memory.
+    ram_file: Option<String>,
+    rom: Option<String>,
+    rtc: GoldfishRtc,
+    fb: Option<Mode>,
+    /// Written at exit and on Ctrl-A p; numbered copies for guest dumps
+    /// and every `fb_every` frames.
+    fb_dump: Option<String>,
+    fb_every: Option<u64>,
+    net: Option<Box<dyn NetBackend>>,
+    mac: [u8; 6],
+    /// What the watchdog does when the guest stops refreshing it.
+    watchdog: WatchdogAction,
+    dump_dtb: Option<String>,
+    serial: Serial,
+    /// Cycles the disk controllers spend on each DMA sector.
+    disk_latency: u64,
+    /// `None` leaves out the v extension.
+    vlen: Option<u32>,
+    extensions: Extensions,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut script = None;
+    let mut debug_log = None;
+    let mut rng_seed = None;
+    let mut share = None;
+    let mut snapshot = false;
+    let mut delta = None;
+    let mut apply_delta = None;
+    let mut drives = Vec::new();
+    let mut ram_size = DEFAULT_RAM_SIZE;
+    let mut ram_file = None;
+    let mut rom = None;
+    let mut rtc = GoldfishRtc::host();
+    let mut fb = None;
+    let mut fb_dump = None;
+    let mut fb_every = None;
+    let mut net = None;
+    let mut net_record = None;
+    let mut mac = DEFAULT_MAC;
+    let mut watchdog = WatchdogAction::Reset;
+    let mut dump_dtb = None;
+    let mut serial = Serial::Stdio;
+    let mut disk_latency = 0;
+    let mut vlen = Some(DEFAULT_VLEN);
+    let mut extensions = ALL_EXTENSIONS;
+    let mut positional = Vec::new();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--script" => {
+                script = Some(args.next().ok_or("--script needs a file argument")?);
+            }
+            "--debug-log" => {
+                debug_log = Some(args.next().ok_or("--debug-log needs a file argument")?);
+            }
+            "--rng-seed" => {
+                let seed = args.next().ok_or("--rng-seed needs a number")?;
+                let seed = seed
+                    .parse()
+                    .map_err(|_| format!("invalid rng seed `{}`", seed))?;
+                rng_seed = Some(seed);
+            }
+            "--share" | "--share-ro" => {
+                let dir = args
+                    .next()
+                    .ok_or_else(|| format!("{} needs a directory argument", arg))?;
+                let read_only = arg == "--share-ro";
+                share = Some(Share { dir, read_only });
+            }
+            "--snapshot" => snapshot = true,
+            "--delta" => {
+                delta = Some(args.next().ok_or("--delta needs a file argument")?);
+            }
+            "--apply-delta" => {
+                apply_delta = Some(args.next().ok_or("--apply-delta needs a file argument")?);
+            }
+            "--drive" => {
+                let spec = args.next().ok_or("--drive needs a KIND:ARG argument")?;
+                drives.push(open_drive(&spec)?);
+            }
+            "--ram" => {
+                let size = args.next().ok_or("--ram needs a size")?;
+                ram_size = parse_size(&size)
+                    .and_then(|size| u32::try_from(size).ok())
+                    .ok_or_else(|| format!("invalid ram size `{}`", size))?;
+            }
+            "--ram-file" => {
+                ram_file = Some(args.next().ok_or("--ram-file needs a file argument")?);
+            }
+            "--rom" => {
+                rom = Some(args.next().ok_or("--rom needs a file argument")?);
+            }
+            "--rtc" => {
+                let clock = args.next().ok_or("--rtc needs a clock argument")?;
+                rtc = parse_rtc(&clock)?;
+            }
+            "--fb" => {
+                let mode = args.next().ok_or("--fb needs a WxH[:FORMAT] argument")?;
+                fb = Some(parse_mode(&mode)?);
+            }
+            "--fb-dump" => {
+                fb_dump = Some(args.next().ok_or("--fb-dump needs a file argument")?);
+            }
+            "--fb-every" => {
+                let every = args.next().ok_or("--fb-every needs a frame count")?;
+                let every = every
+                    .parse()
+                    .ok()
+                    .filter(|&every| every > 0)
+                    .ok_or_else(|| format!("invalid frame count `{}`", every))?;
+                fb_every = Some(every);
+            }
+            "--net" => {
+                let spec = args.next().ok_or("--net needs a KIND[:ARG] argument")?;
+                net = Some(open_net(&spec)?);
+            }
+            "--net-record" => {
+                net_record = Some(args.next().ok_or("--net-record needs a file argument")?);
+            }
+            "--mac" => {
+                let addr = args.next().ok_or("--mac needs an address")?;
+                mac = parse_mac(&addr).ok_or_else(|| format!("invalid mac address `{}`", addr))?;
+            }
+            "--watchdog" => {
+                watchdog = match args.next().as_deref() {
+                    Some("reset") => WatchdogAction::Reset,
+                    Some("halt") => WatchdogAction::Halt,
+                    _ => return Err("--watchdog needs `reset` or `halt`".to_string()),
+                };
+            }
+            "--disk-latency" => {
+                let cycles = args.next().ok_or("--disk-latency needs a cycle count")?;
+                disk_latency = cycles
+                    .parse()
+                    .map_err(|_| format!("invalid disk latency `{}`", cycles))?;
+            }
+            "--serial" => {
+                let spec = args.next().ok_or("--serial needs a KIND[:PATH] argument")?;
+                serial = parse_serial(&spec)?;
+            }
+            "--ext" => {
+                let list = args.next().ok_or("--ext needs an extension list")?;
+                (vlen, extensions) = parse_extensions(&list)?;
+            }
+            "--dump-dtb" => {
+                dump_dtb = Some(args.next().ok_or("--dump-dtb needs a file argument")?);
+            }
+            "-h" | "--help" => return Err(USAGE.to_string()),
+            _ if arg.starts_with('-') => return Err(format!("unknown option `{}`", arg)),
+            _ => positional.push(arg),
+        }
+    }
+
+    if let Some(path) = net_record {
+        let inner = net.ok_or("--net-record needs a --net backend")?;
+        let recorder = PcapRecorder::new(inner, &path)
+            .map_err(|e| format!("Failed to create capture {}: {}", path, e))?;
+        net = Some(Box::new(recorder));
+    }
+
+    if script.is_some() && !matches!(serial, Serial::Stdio) {
+        return Err("--script drives the UART itself, drop --serial".to_string());
+    }
+
+    if positional.len() > 2 {
+        return Err(USAGE.to_string());
+    }
+    let mut positional = positional.into_iter();
+
+    Ok(Args {
+        kernel: positional.next().unwrap_or(DEFAULT_KERNEL.to_string()),
+        disk: positional.next().unwrap_or(DEFAULT_DISK.to_string()),
+        script,
+        debug_log,
+        rng_seed,
+        share,
+        snapshot,
+        delta,
+        apply_delta,
+        drives,
+        ram_size,
+        ram_file,
+        rom,
+        rtc,
+        fb,
+        fb_dump,
+        fb_every,
+        net,
+        mac,
+        watchdog,
+        dump_dtb,
+        serial,
+        disk_latency,
+        vlen,
+        extensions,
+    })
+}
+
+fn parse_size(size: &str) -> Option<u64> {
+    let (digits, unit) = match size.char_indices().last()? {
+        (i, 'K' | 'k') => (&size[..i], 1 << 10),
+        (i, 'M' | 'm') => (&size[..i], 1 << 20),
+        (i, 'G' | 'g') => (&size[..i], 1 << 30),
+        _ => (size, 1),
+    };
+    digits.parse::<u64>().ok()?.checked_mul(unit)
+}
+
+fn parse_rtc(clock: &str) -> Result<GoldfishRtc, String> {
+    let (kind, start) = clock.split_once(':').unwrap_or((clock, ""));
+    let start = || {
+        start
+            .parse::<u64>()
+            .ok()
+            .and_then(|secs| secs.checked_mul(1_000_000_000))
+            .ok_or_else(|| format!("invalid rtc start time `{}`", start))
+    };
+
+    match kind {
+        "host" => Ok(GoldfishRtc::host()),
+        "fixed" => Ok(GoldfishRtc::starting_at(start()?)),
+        "cycles" => Ok(GoldfishRtc::deterministic(start()?, DEFAULT_NS_PER_CYCLE)),
+        _ => Err(format!("unknown rtc clock `{}`", kind)),
+    }
+}
+
+fn parse_serial(spec: &str) -> Result<Serial, String> {
+    let (kind, path) = spec.split_once(':').unwrap_or((spec, ""));
+    match (kind, path) {
+        ("stdio", "") => Ok(Serial::Stdio),
+        ("pty", "") => Ok(Serial::Pty),
+        ("unix", path) if !path.is_empty() => Ok(Serial::Unix(path.to_string())),
+        ("file", path) if !path.is_empty() => Ok(Serial::File(path.to_string())),
+        _ => Err(format!("invalid serial line `{}`", spec)),
+    }
+}
+
+/// Comma-separated optional extensions, or `none` for plain rv32i/rv64i.
+fn parse_extensions(list: &str) -> Result<(Option<u32>, Extensions), String> {
+    let mut vlen = None;
+    let mut extensions = Extensions::default();
+    for name in list.split(',').filter(|name| *name != "none") {
+        match name {
+            "v" => vlen = Some(DEFAULT_VLEN),
+            "zbkb" => extensions.zbkb = true,
+            "zknh" => extensions.zknh = true,
+            "zkne" => extensions.zkne = true,
+            "zknd" => extensions.zknd = true,
+            "zicond" => extensions.zicond = true,
+            _ => return Err(format!("unknown extension `{}`", name)),
+        }
+    }
+    Ok((vlen, extensions))
+}
+
+fn parse_mode(mode: &str) -> Result<Mode, String> {
+    let invalid = || format!("invalid framebuffer mode `{}`", mode);
+    let (size, format) = mode.split_once(':').unwrap_or((mode, "xrgb8888"));
+    let (width, height) = size.split_once('x').ok_or_else(invalid)?;
+    let format = PixelFormat::from_name(format)
+        .ok_or_else(|| format!("unknown pixel format `{}`", format))?;
+    let mode = Mode {
+        width: width.parse().map_err(|_| invalid())?,
+        height: height.parse().map_err(|_| invalid())?,
+        format,
+    };
+
+    // the registers and pixels have to fit in the 32-bit device window
+    let len = mode.width as u64 * mode.height as u64 * format.bytes_per_pixel() as u64;
+    if len == 0 || len > 0x1000_0000 {
+        return Err(invalid());
+    }
+    Ok(mode)
+}
+
+fn open_net(spec: &str) -> Result<Box<dyn NetBackend>, String> {
+    let (kind, arg) = spec.split_once(':').unwrap_or((spec, ""));
+    let open_err = |e| format!("Failed to open network {}: {}", spec, e);
+
+    let backend: Box<dyn NetBackend> = match kind {
+        "loopback" => Box::new(Loopback::new()),
+        "listen" => Box::new(CableBackend::listen(arg).map_err(open_err)?),
+        "connect" => Box::new(CableBackend::connect(arg).map_err(open_err)?),
+        "replay" => Box::new(PcapReplay::open(arg).map_err(open_err)?),
+        _ => return Err(format!("unknown network kind `{}`", kind)),
+    };
+    Ok(backend)
+}
+
+fn parse_mac(addr: &str) -> Option<[u8; 6]> {
+    let mut mac = [0; 6];
+    let mut parts = addr.split(':');
+    for byte in &mut mac {
+        *byte = u8::from_str_radix(parts.next()?, 16).ok()?;
+    }
+    parts.next().is_none().then_some(mac)
+}
 
-    let mut ram = Dram::new(1024 * 1024); // 1 MB RAM
-    load_elf_into_ram(
-        "/Users/matthias/Documents/private/projects/osv/kernel/target/kernel.elf",
-        &mut ram,
-        0x8000_0000,
+fn open_drive(spec: &str) -> Result<DiskImage, String> {
+    let (kind, arg) = spec
+        .split_once(':')
+        .ok_or_else(|| format!("invalid drive `{}`, expected KIND:ARG", spec))?;
+    let open_err = |e| format!("Failed to open drive {}: {}", arg, e);
+
+    let image = match kind {
+        "raw" => DiskImage::new(RawFile::open(arg).map_err(open_err)?),
+        "ro" => DiskImage::new(ReadOnly(RawFile::open_read_only(arg).map_err(open_err)?)),
+        "mem" => DiskImage::new(MemoryImage::from_bytes(fs::read(arg).map_err(open_err)?)),
+        "sparse" => {
+            let size = parse_size(arg).ok_or_else(|| format!("invalid drive size `{}`", arg))?;
+            DiskImage::new(SparseImage::new(size))
+        }
+        _ => return Err(format!("unknown drive kind `{}`", kind)),
+    };
+    Ok(image)
+}
+
+/// Ctrl-A c commits the snapshot to the disk image, Ctrl-A s saves it to
+/// `delta` for a later `--apply-delta`.
+fn snapshot_escapes(image: &DiskImage, delta: String) -> Vec<(u8, EscapeAction)> {
+    let commit = image.clone();
+    let save = image.clone();
+    vec![
+        (
+            b'c',
+            Box::new(move || match commit.commit() {
+                Ok(count) => eprintln!("\nCommitted {} sectors to the disk image", count),
+                Err(e) => eprintln!("\nFailed to commit snapshot: {}", e),
+            }),
+        ),
+        (
+            b's',
+            Box::new(move || match save.save_delta(&delta) {
+                Ok(count) => eprintln!("\nSaved {} sectors to {}", count, delta),
+                Err(e) => eprintln!("\nFailed to save snapshot delta: {}", e),
+            }),
+        ),
+    ]
+}
+
+/// Ctrl-A p saves the framebuffer to `path`.
+fn screen_escape(screen: Screen, path: String) -> (u8, EscapeAction) {
+    (
+        b'p',
+        Box::new(move || match screen.save(&path) {
+            Ok(()) => eprintln!("\nSaved framebuffer to {}", path),
+            Err(e) => eprintln!("\nFailed to dump framebuffer: {}", e),
+        }),
     )
-    .expect("Failed to load kernel ELF into RAM");
+}
+
+/// Post-mortem for a halted machine.
+fn dump_cpu(cpu: &Cpu) {
+    eprintln!("pc: {:#010x} ({:?})", cpu.pc, cpu.priv_mode);
+    eprintln!("{:#?}", cpu.reg_file);
+    eprintln!("{:#?}", cpu.csr_file);
+}
+
+/// Ctrl-A x powers the machine off like the guest would, so the exit goes
+/// through `shut_down`.
+fn quit_escape(power: PowerControl) -> (u8, EscapeAction) {
+    (
+        b'x',
+        Box::new(move || power.request(PowerRequest::Off { code: 0 })),
+    )
+}
+
+/// Writes the final framebuffer picture, if dumps were asked for.
+fn dump_screen(screen: &Option<Screen>, path: &Option<String>) {
+    if let (Some(screen), Some(path)) = (screen, path)
+        && let Err(e) = screen.save(path)
+    {
+        eprintln!("Failed to dump framebuffer to {}: {}", path, e);
+    }
+}
+
+/// Handles a guest reset: starts over from a pristine kernel image, with
+/// its .bss cleared again. The rest of RAM keeps its contents, as on real
+/// hardware.
+fn restart(cpu: &mut Cpu, kernel: &str) {
+    cpu.reset_machine(ROM_BASE);
+    load_elf(kernel, &mut cpu.bus).expect("Failed to reload kernel ELF");
+}
+
+/// Exits with `code` once file-backed RAM is synced and the final picture
+/// dumped. Dropping the machine restores the terminal.
+fn shut_down(mut cpu: Cpu, screen: &Option<Screen>, fb_dump: &Option<String>, code: i32) -> ! {
+    if let Err(e) = cpu.bus.sync() {
+        eprintln!("Failed to sync RAM file: {}", e);
+    }
+    dump_screen(screen, fb_dump);
+    drop(cpu);
+    process::exit(code);
+}
+
+fn main() {
+    let args = parse_args().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(2);
+    });
+
+    let script = args.script.map(|path| {
+        let src = fs::read_to_string(&path).unwrap_or_else(|e| {
+            eprintln!("Failed to read script {}: {}", path, e);
+            process::exit(2);
+        });
+        parser::parse(&src).unwrap_or_else(|e| {
+            eprintln!("{}: {}", path, e);
+            process::exit(2);
+        })
+    });
+
+    let image = match args.snapshot {
+        true => DiskImage::snapshot(&args.disk),
+        false => DiskImage::open(&args.disk),
+    };
+    let image = image.expect("Failed to load disk file.");
+    if let Some(path) = &args.apply_delta {
+        let count = image
+            .apply_delta(path)
+            .expect("Failed to apply disk delta.");
+        eprintln!("Applied {} sectors from {}", count, path);
+    }
+
+    let screen = args.fb.map(Screen::new);
+
+    let power = PowerControl::new();
+
+    // scripted runs talk to the guest through an in-memory console
+    let console = MemoryBackend::new();
+    let uart0 = match (&script, &args.serial) {
+        (Some(_), _) => Uart::new(Box::new(console.clone())),
+        (None, Serial::Pty) => Uart::new(Box::new(
+            PtyBackend::open().expect("Failed to allocate a pseudo-terminal."),
+        )),
+        (None, Serial::Unix(path)) => Uart::new(Box::new(
+            UnixSocketBackend::bind(path).expect("Failed to bind the UART socket."),
+        )),
+        (None, Serial::File(path)) => Uart::new(Box::new(
+            FileBackend::create(path).expect("Failed to create the UART log."),
+        )),
+        (None, Serial::Stdio) => {
+            let mut escapes = vec![quit_escape(power.clone())];
+            if args.snapshot {
+                let delta = args.delta.unwrap_or(format!("{}.delta", args.disk));
+                escapes.extend(snapshot_escapes(&image, delta));
+            }
+            if let (Some(screen), Some(path)) = (&screen, &args.fb_dump) {
+                escapes.push(screen_escape(screen.clone(), path.clone()));
+            }
+            Uart::new(Box::new(StdioBackend::with_escapes(escapes)))
+        }
+    };
 
-    let disk = Disk::new("/Users/matthias/Documents/private/projects/osv/kernel/target/disk")
-        .expect("Failed to load disk file.");
+    let ram: Box<dyn Device> = match &args.ram_file {
+        Some(path) => Box::new(Dram::mapped(path, args.ram_size).expect("Failed to map RAM file.")),
+        // pages are only backed by host memory once the guest touches them
+        None => Box::new(SparseDram::new(args.ram_size)),
+    };
+
+    // same image behind the standard interface; a guest drives one or the other
+    let disk = Disk::with_image(image.clone()).with_dma_latency(args.disk_latency);
+    let virtio_blk = VirtioBlk::with_image(image);
 
     let mut bus = Bus::new();
-    bus.map_to(0x8000_0000, Box::new(ram));
+    bus.map_to(0x8000_0000, ram);
     bus.map_to(0x1000_0000, Box::new(uart0));
     bus.map_to(0x1000_1000, Box::new(disk));
+    bus.map_to(0x1000_2000, Box::new(VirtioMmio::new(Box::new(virtio_blk))));
+
+    for (i, image) in args.drives.into_iter().enumerate() {
+        let base = EXTRA_DISK_BASE + i as u64 * 0x1000;
+        let disk = Disk::with_image(image).with_dma_latency(args.disk_latency);
+        bus.map_to(base, Box::new(disk));
+    }
+
+    if let Some(path) = &args.debug_log {
+        let log = FileBackend::create(path).expect("Failed to create debug log.");
+        let console = VirtioConsole::new(vec![ConsolePort::new(None, Box::new(log))]);
+        bus.map_to(0x1000_3000, Box::new(VirtioMmio::new(Box::new(console))));
+    }
+
+    let rng = match args.rng_seed {
+        Some(seed) => VirtioRng::seeded(seed),
+        None => VirtioRng::from_os().expect("Failed to open host RNG."),
+    };
+    bus.map_to(0x1000_4000, Box::new(VirtioMmio::new(Box::new(rng))));
+
+    if let Some(share) = &args.share {
+        let p9 = Virtio9p::new(&share.dir, SHARE_TAG, share.read_only)
+            .expect("Failed to open shared directory.");
+        bus.map_to(0x1000_5000, Box::new(VirtioMmio::new(Box::new(p9))));
+    }
+
+    bus.map_to(0x1000_6000, Box::new(args.rtc));
+
+    if let Some(net) = args.net {
+        let net = VirtioNet::new(args.mac, net);
+        bus.map_to(0x1000_7000, Box::new(VirtioMmio::new(Box::new(net))));
+    }
+
+    if let Some(screen) = &screen {
+        let mut fb = Framebuffer::new(screen.clone());
+        if let Some(path) = &args.fb_dump {
+            fb = fb.with_dumps(DumpConfig {
+                path: path.into(),
+                every: args.fb_every,
+            });
+        }
+        bus.map_to(FRAMEBUFFER_BASE, Box::new(fb));
+    }
 
-    let mut cpu = Cpu::new(bus, None);
+    bus.map_to(0x0010_0000, Box::new(TestFinisher::new(power.clone())));
+    bus.map_to(
+        0x1000_8000,
+        Box::new(Watchdog::new(power.clone(), args.watchdog)),
+    );
+
+    let kernel = load_elf(&args.kernel, &mut bus).expect("Failed to load kernel ELF into RAM");
+    // riscv-tests report their result through tohost instead of a device
+    let mut htif = kernel.tohost.map(|tohost| {
+        Htif::new(
+            tohost,
+            kernel.fromhost,
+            Box::new(io::stdout()),
+            power.clone(),
+        )
+    });
+
+    let config = CpuConfig {
+        xlen: kernel.xlen,
+        vlen: args.vlen,
+        extensions: args.extensions,
+    };
+
+    // describes every device mapped so far; the ROM itself is left out
+    let dtb = machine_fdt(
+        &bus,
+        &FdtCpu {
+            isa: &config.isa_string(),
+            mmu_type: config.mmu_type(),
+            timebase_frequency: TIMEBASE_FREQUENCY,
+        },
+    );
+    if let Some(path) = &args.dump_dtb {
+        fs::write(path, &dtb).unwrap_or_else(|e| {
+            eprintln!("Failed to write device tree to {}: {}", path, e);
+            process::exit(2);
+        });
+        process::exit(0);
+    }
+
+    // a custom ROM finds its own way to the kernel, without a device tree
+    let rom = match &args.rom {
+        Some(path) => Dram::rom(path).expect("Failed to map ROM image."),
+        None => Dram::rom_from_bytes(boot_rom(kernel.xlen, kernel.entry, Some(&dtb))),
+    };
+    bus.map_to(ROM_BASE, Box::new(rom));
+
+    let mut cpu = Cpu::new(bus, config, Some(ROM_BASE)).with_power(power.clone());
+
+    if let Some(script) = script {
+        let kernel = args.kernel.clone();
+        let mut runner = ScriptRunner::new(console)
+            .echo(true)
+            .with_power(power.clone(), move |cpu| restart(cpu, &kernel));
+        if let Some(mut htif) = htif {
+            runner = runner.poll(move |cpu| {
+                if let Err(e) = htif.poll(&mut cpu.bus) {
+                    eprintln!("HTIF access failed: {:?}", e);
+                    power.request(PowerRequest::Off { code: 1 });
+                }
+            });
+        }
+        match runner.run(&mut cpu, &script) {
+            Ok(Outcome::Completed) => {
+                println!("\nscript passed");
+                shut_down(cpu, &screen, &args.fb_dump, 0);
+            }
+            Ok(Outcome::PoweredOff { code }) => {
+                match code {
+                    0 => println!("\nscript passed: guest powered off"),
+                    _ => eprintln!("\nscript failed: guest powered off with code {}", code),
+                }
+                shut_down(cpu, &screen, &args.fb_dump, code);
+            }
+            Ok(Outcome::Halted) => {
+                eprintln!("\nscript failed: machine halted");
+                dump_cpu(&cpu);
+                shut_down(cpu, &screen, &args.fb_dump, 1);
+            }
+            Ok(Outcome::Stopped) => unreachable!("the runner acts on power requests"),
+            Err(e) => {
+                eprintln!("\nscript failed: {}", e);
+                shut_down(cpu, &screen, &args.fb_dump, 1);
+            }
+        }
+    }
 
     let mut ips_monitor = IpsMonitor::default();
     loop {
         cpu.step();
         ips_monitor.update(cpu.cycles());
+
+        if cpu.cycles().is_multiple_of(HTIF_POLL_INTERVAL)
+            && let Some(htif) = &mut htif
+            && let Err(e) = htif.poll(&mut cpu.bus)
+        {
+            eprintln!("HTIF access failed: {:?}", e);
+            shut_down(cpu, &screen, &args.fb_dump, 1);
+        }
+
+        // the hart stops as soon as a request is made, so a halt dumps the
+        // state it was in at that point
+        if power.pending() {
+            match power.take() {
+                Some(PowerRequest::Off { code }) => {
+                    shut_down(cpu, &screen, &args.fb_dump, code);
+                }
+                Some(PowerRequest::Reset) => restart(&mut cpu, &args.kernel),
+                Some(PowerRequest::Halt) => {
+                    dump_cpu(&cpu);
+                    shut_down(cpu, &screen, &args.fb_dump, 1);
+                }
+                None => {}
+            }
+        }
     }
 }
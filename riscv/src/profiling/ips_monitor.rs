@@ -16,7 +16,7 @@ impl IpsMonitor {
     }
 
     pub fn update(&mut self, current_cycles: u64) {
-        if current_cycles % CYCLE_INTERVAL != 0 {
+        if !current_cycles.is_multiple_of(CYCLE_INTERVAL) {
             return;
         }
 
@@ -1,5 +1,7 @@
 use std::fmt;
 
+use crate::isa::Xlen;
+
 const NUM_REGS: usize = 32;
 
 pub const ABI_REG_NAMES: [&str; NUM_REGS] = [
@@ -9,30 +11,32 @@ pub const ABI_REG_NAMES: [&str; NUM_REGS] = [
 ];
 
 pub struct RegFile {
-    regs: [u32; NUM_REGS],
+    regs: [u64; NUM_REGS],
+    xlen: Xlen,
 }
 
 impl RegFile {
-    pub fn new() -> Self {
+    pub fn new(xlen: Xlen) -> Self {
         Self {
             regs: [0; NUM_REGS],
+            xlen,
         }
     }
 
-    pub fn read(&self, idx: u8) -> u32 {
+    pub fn read(&self, idx: u8) -> u64 {
         self.regs[idx as usize]
     }
 
-    pub fn write(&mut self, idx: u8, val: u32) {
+    pub fn write(&mut self, idx: u8, val: u64) {
         if idx != 0 {
-            self.regs[idx as usize] = val;
+            self.regs[idx as usize] = self.xlen.sext(val);
         }
     }
 }
 
 impl Default for RegFile {
     fn default() -> Self {
-        Self::new()
+        Self::new(Xlen::default())
     }
 }
 
@@ -41,10 +45,11 @@ impl fmt::Debug for RegFile {
         let mut ds = f.debug_struct("RegFile");
         for (i, &reg) in self.regs.iter().enumerate() {
             if reg != 0 {
-                ds.field(
-                    &format!("x{}({})", i, ABI_REG_NAMES[i]),
-                    &format_args!("{:#010x}", reg),
-                );
+                let name = format!("x{}({})", i, ABI_REG_NAMES[i]);
+                match self.xlen {
+                    Xlen::Rv32 => ds.field(&name, &format_args!("{:#010x}", reg as u32)),
+                    Xlen::Rv64 => ds.field(&name, &format_args!("{:#018x}", reg)),
+                };
             }
         }
         ds.finish()
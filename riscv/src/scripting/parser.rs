@@ -0,0 +1,141 @@
+//! Script files, one command per line:
+//!
+//! ```text
+//! # comments and blank lines are ignored
+//! timeout 50000000        # default timeout (cycles) for later expects
+//! expect "Root mounted at"
+//! send "ls\n"
+//! expect "$ " 1000000     # with an explicit timeout
+//! ```
+//!
+//! Strings support the escapes `\n`, `\r`, `\t`, `\\`, `\"` and `\xNN`.
+
+use std::fmt;
+
+use crate::scripting::{DEFAULT_TIMEOUT, Script, Step};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+pub fn parse(src: &str) -> Result<Script, ParseError> {
+    let mut script = Script::new();
+    let mut timeout = DEFAULT_TIMEOUT;
+
+    for (i, line) in src.lines().enumerate() {
+        let error = |message: String| ParseError {
+            line: i + 1,
+            message,
+        };
+
+        let line = line.trim_start();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        match command {
+            "timeout" => {
+                let (cycles, rest) = parse_number(rest).map_err(error)?;
+                expect_end(rest).map_err(error)?;
+                timeout = cycles;
+            }
+            "expect" => {
+                let (pattern, rest) = parse_string(rest).map_err(error)?;
+                let rest = rest.trim_start();
+                let (cycles, rest) = if rest.is_empty() || rest.starts_with('#') {
+                    (timeout, rest)
+                } else {
+                    parse_number(rest).map_err(error)?
+                };
+                expect_end(rest).map_err(error)?;
+                script.push(Step::Expect {
+                    pattern,
+                    timeout: cycles,
+                });
+            }
+            "send" => {
+                let (data, rest) = parse_string(rest).map_err(error)?;
+                expect_end(rest).map_err(error)?;
+                script.push(Step::Send(data));
+            }
+            _ => return Err(error(format!("unknown command `{}`", command))),
+        }
+    }
+
+    Ok(script)
+}
+
+fn parse_number(src: &str) -> Result<(u64, &str), String> {
+    let src = src.trim_start();
+    let end = src
+        .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+        .unwrap_or(src.len());
+    let (token, rest) = src.split_at(end);
+    let digits = token.replace('_', "");
+
+    let value = match digits.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => digits.parse(),
+    };
+    value
+        .map(|value| (value, rest))
+        .map_err(|_| format!("expected a number, found `{}`", token))
+}
+
+fn parse_string(src: &str) -> Result<(Vec<u8>, &str), String> {
+    let src = src.trim_start();
+    let Some(body) = src.strip_prefix('"') else {
+        return Err("expected a quoted string".to_string());
+    };
+
+    let mut out = Vec::new();
+    let mut chars = body.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Ok((out, &body[i + 1..])),
+            '\\' => {
+                let escaped = match chars.next().map(|(_, c)| c) {
+                    Some('n') => b'\n',
+                    Some('r') => b'\r',
+                    Some('t') => b'\t',
+                    Some('\\') => b'\\',
+                    Some('"') => b'"',
+                    Some('x') => {
+                        let hex: String = chars.by_ref().take(2).map(|(_, c)| c).collect();
+                        u8::from_str_radix(&hex, 16)
+                            .map_err(|_| format!("invalid escape `\\x{}`", hex))?
+                    }
+                    Some(c) => return Err(format!("invalid escape `\\{}`", c)),
+                    None => break,
+                };
+                out.push(escaped);
+            }
+            c => {
+                let mut buf = [0; 4];
+                out.extend(c.encode_utf8(&mut buf).as_bytes());
+            }
+        }
+    }
+
+    Err("unterminated string".to_string())
+}
+
+fn expect_end(rest: &str) -> Result<(), String> {
+    let rest = rest.trim_start();
+    if rest.is_empty() || rest.starts_with('#') {
+        Ok(())
+    } else {
+        Err(format!("unexpected `{}`", rest))
+    }
+}
@@ -0,0 +1,243 @@
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::cpu::Cpu;
+use crate::devices::{MemoryBackend, PowerControl, PowerRequest};
+
+/// Instructions run between two looks at the console output.
+const POLL_INTERVAL: u64 = 1024;
+
+pub const DEFAULT_TIMEOUT: u64 = 100_000_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Step {
+    /// Run until the console output contains `pattern`, for at most
+    /// `timeout` cycles.
+    Expect { pattern: Vec<u8>, timeout: u64 },
+    /// Queue bytes on the console input.
+    Send(Vec<u8>),
+}
+
+/// An expect/send conversation with the guest console.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Script {
+    steps: Vec<Step>,
+}
+
+impl Script {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn expect(mut self, pattern: impl AsRef<[u8]>, timeout: u64) -> Self {
+        self.steps.push(Step::Expect {
+            pattern: pattern.as_ref().to_vec(),
+            timeout,
+        });
+        self
+    }
+
+    pub fn send(mut self, data: impl AsRef<[u8]>) -> Self {
+        self.steps.push(Step::Send(data.as_ref().to_vec()));
+        self
+    }
+
+    pub fn push(&mut self, step: Step) {
+        self.steps.push(step);
+    }
+
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+}
+
+/// How a script run ended, short of a timeout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// Every step matched.
+    Completed,
+    /// The guest powered the machine off before the script was done.
+    PoweredOff { code: i32 },
+    /// The machine was halted, e.g. by the watchdog.
+    Halted,
+    /// The hart stopped on a power request, but the runner was not given
+    /// `with_power` to act on it. The request is still pending.
+    Stopped,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScriptError {
+    /// Index of the failing step.
+    pub step: usize,
+    pub pattern: Vec<u8>,
+    pub timeout: u64,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "step {}: timed out after {} cycles waiting for {:?}",
+            self.step + 1,
+            self.timeout,
+            String::from_utf8_lossy(&self.pattern)
+        )
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+/// Why an expect step stopped without a match.
+enum Stop {
+    Timeout,
+    Power(Outcome),
+}
+
+/// Restarts the machine on a guest reset request, or services the
+/// machine between two batches of instructions.
+type Hook = Box<dyn FnMut(&mut Cpu)>;
+
+/// Runs scripts against a cpu whose UART is attached to `console`.
+pub struct ScriptRunner {
+    console: MemoryBackend,
+    transcript: Vec<u8>,
+    /// Start of the output not yet consumed by a match.
+    cursor: usize,
+    echo: bool,
+    power: Option<(PowerControl, Hook)>,
+    poll: Option<Hook>,
+}
+
+impl ScriptRunner {
+    pub fn new(console: MemoryBackend) -> Self {
+        Self {
+            console,
+            transcript: Vec::new(),
+            cursor: 0,
+            echo: false,
+            power: None,
+            poll: None,
+        }
+    }
+
+    /// Acts on the guest's power requests while waiting for output: a
+    /// poweroff or halt ends the run, a reset calls `reset` and the
+    /// current step carries on with what is left of its timeout.
+    pub fn with_power(
+        mut self,
+        power: PowerControl,
+        reset: impl FnMut(&mut Cpu) + 'static,
+    ) -> Self {
+        self.power = Some((power, Box::new(reset)));
+        self
+    }
+
+    /// Also copy guest output to stdout as it arrives.
+    pub fn echo(mut self, echo: bool) -> Self {
+        self.echo = echo;
+        self
+    }
+
+    /// Calls `poll` every time the console is looked at, for host services
+    /// that aren't devices, e.g. HTIF. It runs before power requests are
+    /// acted on, so it can make one.
+    pub fn poll(mut self, poll: impl FnMut(&mut Cpu) + 'static) -> Self {
+        self.poll = Some(Box::new(poll));
+        self
+    }
+
+    /// Everything the guest has printed so far.
+    pub fn transcript(&self) -> &[u8] {
+        &self.transcript
+    }
+
+    pub fn run(&mut self, cpu: &mut Cpu, script: &Script) -> Result<Outcome, ScriptError> {
+        for (i, step) in script.steps().iter().enumerate() {
+            match step {
+                Step::Expect { pattern, timeout } => match self.expect(cpu, pattern, *timeout) {
+                    Ok(()) => {}
+                    Err(Stop::Power(outcome)) => return Ok(outcome),
+                    Err(Stop::Timeout) => {
+                        return Err(ScriptError {
+                            step: i,
+                            pattern: pattern.clone(),
+                            timeout: *timeout,
+                        });
+                    }
+                },
+                Step::Send(data) => self.console.push_input(data),
+            }
+        }
+        Ok(Outcome::Completed)
+    }
+
+    fn expect(&mut self, cpu: &mut Cpu, pattern: &[u8], timeout: u64) -> Result<(), Stop> {
+        // counted here rather than read from mcycle, which the guest can
+        // write and a reset clears
+        let mut left = timeout;
+
+        loop {
+            if let Some(pos) = self.find(pattern) {
+                self.cursor = pos + pattern.len();
+                return Ok(());
+            }
+            if left == 0 {
+                return Err(Stop::Timeout);
+            }
+
+            for _ in 0..POLL_INTERVAL.min(left) {
+                cpu.step();
+                left -= 1;
+                if cpu.stopped()
+                    || self
+                        .power
+                        .as_ref()
+                        .is_some_and(|(power, _)| power.pending())
+                {
+                    break;
+                }
+            }
+            self.collect_output();
+            if let Some(poll) = &mut self.poll {
+                poll(cpu);
+            }
+
+            let Some((power, reset)) = &mut self.power else {
+                // nothing will take the request, so the hart never moves again
+                if cpu.stopped() {
+                    return Err(Stop::Power(Outcome::Stopped));
+                }
+                continue;
+            };
+            match power.take() {
+                Some(PowerRequest::Off { code }) => {
+                    return Err(Stop::Power(Outcome::PoweredOff { code }));
+                }
+                Some(PowerRequest::Halt) => return Err(Stop::Power(Outcome::Halted)),
+                Some(PowerRequest::Reset) => reset(cpu),
+                None => {}
+            }
+        }
+    }
+
+    fn find(&self, pattern: &[u8]) -> Option<usize> {
+        let haystack = &self.transcript[self.cursor..];
+        if pattern.is_empty() {
+            return Some(self.cursor);
+        }
+        haystack
+            .windows(pattern.len())
+            .position(|window| window == pattern)
+            .map(|pos| self.cursor + pos)
+    }
+
+    fn collect_output(&mut self) {
+        let output = self.console.take_output();
+        if self.echo && !output.is_empty() {
+            let mut stdout = io::stdout();
+            let _ = stdout.write_all(&output);
+            let _ = stdout.flush();
+        }
+        self.transcript.extend(output);
+    }
+}
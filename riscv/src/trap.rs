@@ -7,41 +7,65 @@ use core::fmt;
 #[derive(Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
 pub enum Exception {
+    InstructionAccessFault(u64),
     IllegalInstruction(Instr),
-    LoadAccessFault(u32),
-    StoreAccessFault(u32),
+    LoadAccessFault(u64),
+    StoreAccessFault(u64),
     EnvironmentCall(PrivilegeMode),
+    InstructionPageFault(u64),
+    LoadPageFault(u64),
+    StorePageFault(u64),
 }
 
-// enum Interrupt {
-// }
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Interrupt {
+    MachineExternal,
+}
 
 #[derive(Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
 pub enum Trap {
     Exception(Exception),
+    Interrupt(Interrupt),
 }
 
 impl Trap {
     pub fn cause_code(&self) -> u8 {
         match self {
             Trap::Exception(exception) => match exception {
+                Exception::InstructionAccessFault(_) => 1,
                 Exception::IllegalInstruction(_) => 2,
                 Exception::LoadAccessFault(_) => 5,
                 Exception::StoreAccessFault(_) => 7,
                 Exception::EnvironmentCall(priv_mode) => *priv_mode as u8 + 8,
+                Exception::InstructionPageFault(_) => 12,
+                Exception::LoadPageFault(_) => 13,
+                Exception::StorePageFault(_) => 15,
+            },
+            Trap::Interrupt(interrupt) => match interrupt {
+                Interrupt::MachineExternal => 11,
             },
         }
     }
 
-    pub fn value(&self) -> u32 {
+    pub fn is_interrupt(&self) -> bool {
+        matches!(self, Trap::Interrupt(_))
+    }
+
+    pub fn value(&self) -> u64 {
         match self {
             Trap::Exception(exception) => match exception {
-                Exception::IllegalInstruction(instr) => instr.word(),
+                Exception::InstructionAccessFault(addr) => *addr,
+                Exception::IllegalInstruction(instr) => instr.word() as u64,
                 Exception::LoadAccessFault(addr) => *addr,
                 Exception::StoreAccessFault(addr) => *addr,
                 Exception::EnvironmentCall(_) => 0,
+                Exception::InstructionPageFault(addr) => *addr,
+                Exception::LoadPageFault(addr) => *addr,
+                Exception::StorePageFault(addr) => *addr,
             },
+            Trap::Interrupt(_) => 0,
         }
     }
 }
@@ -50,6 +74,9 @@ impl fmt::Debug for Trap {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             Trap::Exception(exception) => match exception {
+                Exception::InstructionAccessFault(addr) => {
+                    write!(f, "InstructionAccessFault {{ addr: {:#010x} }}", addr)
+                }
                 Exception::IllegalInstruction(instr) => {
                     write!(f, "IllegalInstruction {{ word: {:#010x} }}", instr.word())
                 }
@@ -62,7 +89,17 @@ impl fmt::Debug for Trap {
                 Exception::EnvironmentCall(priv_mode) => {
                     f.debug_tuple("EnvironmentCall").field(priv_mode).finish()
                 }
+                Exception::InstructionPageFault(addr) => {
+                    write!(f, "InstructionPageFault {{ addr: {:#010x} }}", addr)
+                }
+                Exception::LoadPageFault(addr) => {
+                    write!(f, "LoadPageFault {{ addr: {:#010x} }}", addr)
+                }
+                Exception::StorePageFault(addr) => {
+                    write!(f, "StorePageFault {{ addr: {:#010x} }}", addr)
+                }
             },
+            Trap::Interrupt(interrupt) => interrupt.fmt(f),
         }
     }
 }
@@ -0,0 +1,86 @@
+use std::fmt;
+
+const NUM_VREGS: usize = 32;
+
+/// Vector register file. Registers are stored back to back, so a register
+/// group `vN..vN+LMUL` is a contiguous byte range starting at `vN`.
+pub struct VRegFile {
+    vlenb: usize,
+    bytes: Vec<u8>,
+}
+
+impl VRegFile {
+    pub fn new(vlen: u32) -> Self {
+        assert!(
+            vlen.is_power_of_two() && (128..=65536).contains(&vlen),
+            "VLEN must be a power of two between 128 and 65536, got {}",
+            vlen
+        );
+
+        let vlenb = (vlen / 8) as usize;
+        Self {
+            vlenb,
+            bytes: vec![0; vlenb * NUM_VREGS],
+        }
+    }
+
+    pub fn vlenb(&self) -> usize {
+        self.vlenb
+    }
+
+    /// Reads element `idx` of width `sew` bits from the group starting at `vreg`.
+    pub fn read(&self, vreg: u8, idx: usize, sew: u32) -> u64 {
+        let width = (sew / 8) as usize;
+        let start = vreg as usize * self.vlenb + idx * width;
+
+        let mut buf = [0u8; 8];
+        buf[..width].copy_from_slice(&self.bytes[start..start + width]);
+        u64::from_le_bytes(buf)
+    }
+
+    /// Writes the low `sew` bits of `val` to element `idx` of the group at `vreg`.
+    pub fn write(&mut self, vreg: u8, idx: usize, sew: u32, val: u64) {
+        let width = (sew / 8) as usize;
+        let start = vreg as usize * self.vlenb + idx * width;
+
+        self.bytes[start..start + width].copy_from_slice(&val.to_le_bytes()[..width]);
+    }
+
+    pub fn read_mask(&self, vreg: u8, idx: usize) -> bool {
+        let byte = self.bytes[vreg as usize * self.vlenb + idx / 8];
+        (byte >> (idx % 8)) & 1 != 0
+    }
+
+    pub fn write_mask(&mut self, vreg: u8, idx: usize, bit: bool) {
+        let byte = &mut self.bytes[vreg as usize * self.vlenb + idx / 8];
+        if bit {
+            *byte |= 1 << (idx % 8);
+        } else {
+            *byte &= !(1 << (idx % 8));
+        }
+    }
+
+    /// Raw bytes of `count` whole registers starting at `vreg`.
+    pub fn regs(&self, vreg: u8, count: usize) -> &[u8] {
+        let start = vreg as usize * self.vlenb;
+        &self.bytes[start..start + count * self.vlenb]
+    }
+
+    pub fn regs_mut(&mut self, vreg: u8, count: usize) -> &mut [u8] {
+        let start = vreg as usize * self.vlenb;
+        &mut self.bytes[start..start + count * self.vlenb]
+    }
+}
+
+impl fmt::Debug for VRegFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut ds = f.debug_struct("VRegFile");
+        for (i, reg) in self.bytes.chunks(self.vlenb).enumerate() {
+            if reg.iter().any(|&b| b != 0) {
+                let hex: String = reg.iter().rev().map(|b| format!("{:02x}", b)).collect();
+                ds.field(&format!("v{}", i), &format_args!("0x{}", hex));
+            }
+        }
+        ds.finish()
+    }
+}
@@ -0,0 +1,72 @@
+mod common;
+
+use common::RAM_BASE;
+use riscv::cpu::Cpu;
+use riscv::devices::{Disk, DiskImage, MemoryImage, SECTOR_SIZE};
+
+const DISK_BASE: u64 = 0x1000_1000;
+
+/// Writes "AB" to sector 1 through the data port.
+const PIO_WRITE: [u32; 10] = [
+    0x1000_12b7, // lui t0, 0x10001
+    0x0010_0313, // li t1, 1
+    0x0062_a223, // sw t1, 4(t0)     # REG_SECTOR
+    0x0410_0313, // li t1, 'A'
+    0x0062_a423, // sw t1, 8(t0)     # REG_DATA
+    0x0420_0313, // li t1, 'B'
+    0x0062_a423, // sw t1, 8(t0)
+    0x0020_0313, // li t1, 2
+    0x0062_a023, // sw t1, 0(t0)     # CMD_WRITE
+    0x0000_006f, // j .
+];
+
+/// DMAs sector 0 to 0x80001000.
+const DMA_READ: [u32; 8] = [
+    0x1000_12b7, // lui t0, 0x10001
+    0x8000_1337, // lui t1, 0x80001
+    0x0062_ac23, // sw t1, 0x18(t0)  # REG_DMA_ADDR_LO
+    0x0010_0313, // li t1, 1
+    0x0262_a023, // sw t1, 0x20(t0)  # REG_DMA_COUNT
+    0x0030_0313, // li t1, 3
+    0x0062_a023, // sw t1, 0(t0)     # CMD_DMA_READ
+    0x0000_006f, // j .
+];
+
+fn machine(program: &[u32], image: &MemoryImage) -> Cpu {
+    common::machine(program, |bus| {
+        let disk = Disk::with_image(DiskImage::new(image.clone()));
+        bus.map_to(DISK_BASE, Box::new(disk));
+    })
+}
+
+fn run(cpu: &mut Cpu, steps: usize) {
+    for _ in 0..steps {
+        cpu.step();
+    }
+}
+
+#[test]
+fn guest_writes_land_in_memory_image() {
+    let image = MemoryImage::new(4 * SECTOR_SIZE);
+    let mut cpu = machine(&PIO_WRITE, &image);
+    run(&mut cpu, 100);
+
+    let data = image.data();
+    assert_eq!(&data[SECTOR_SIZE..SECTOR_SIZE + 2], b"AB");
+    assert!(data[..SECTOR_SIZE].iter().all(|&byte| byte == 0));
+    assert!(data[SECTOR_SIZE + 2..].iter().all(|&byte| byte == 0));
+}
+
+#[test]
+fn guest_reads_prepared_memory_image() {
+    let mut data = vec![0; 2 * SECTOR_SIZE];
+    data[..5].copy_from_slice(b"hello");
+    data[SECTOR_SIZE - 1] = 0x5a;
+    let image = MemoryImage::from_bytes(data.clone());
+    let mut cpu = machine(&DMA_READ, &image);
+    run(&mut cpu, 100);
+
+    let ram = cpu.bus.dump(RAM_BASE + 0x1000, SECTOR_SIZE as u64).unwrap();
+    assert_eq!(ram, data[..SECTOR_SIZE]);
+    assert_eq!(image.data(), data);
+}
@@ -0,0 +1,20 @@
+//! Shared by the integration tests: a bare machine with a hand-assembled
+//! program at the start of RAM.
+
+use riscv::cpu::{Cpu, CpuConfig};
+use riscv::devices::{Bus, Dram};
+
+pub const RAM_BASE: u64 = 0x8000_0000;
+const RAM_SIZE: u32 = 0x2000;
+
+/// A hart at `RAM_BASE` running `program`, with whatever devices `map` puts
+/// on the bus.
+pub fn machine(program: &[u32], map: impl FnOnce(&mut Bus)) -> Cpu {
+    let mut bus = Bus::new();
+    bus.map_to(RAM_BASE, Box::new(Dram::new(RAM_SIZE)));
+    map(&mut bus);
+
+    let code: Vec<u8> = program.iter().flat_map(|word| word.to_le_bytes()).collect();
+    bus.write_bytes(RAM_BASE, &code).unwrap();
+    Cpu::new(bus, CpuConfig::default(), Some(RAM_BASE))
+}
@@ -0,0 +1,65 @@
+use std::fs;
+use std::os::unix::fs::symlink;
+use std::path::PathBuf;
+
+use riscv::devices::virtio::Virtio9p;
+use riscv::devices::virtio::p9::wire::Writer;
+
+const TVERSION: u8 = 100;
+const TATTACH: u8 = 104;
+const TLCREATE: u8 = 14;
+const RLERROR: u8 = 7;
+
+const NO_FID: u32 = !0;
+const O_WRONLY: u32 = 0o1;
+const O_TRUNC: u32 = 0o1000;
+
+/// A fresh directory under the system temp dir, unique to `name`.
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("riscv-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Sends a T-message and returns the reply's kind and body.
+fn call(p9: &mut Virtio9p, kind: u8, body: &mut Writer) -> (u8, Vec<u8>) {
+    let body = std::mem::take(body).into_inner();
+    let mut msg = Writer::new();
+    msg.u32(7 + body.len() as u32).u8(kind).u16(1).bytes(&body);
+
+    let reply = p9.handle(&msg.into_inner());
+    (reply[4], reply[7..].to_vec())
+}
+
+#[test]
+fn lcreate_does_not_follow_links_out_of_the_share() {
+    let dir = scratch_dir("p9-confine");
+    let share = dir.join("share");
+    let outside = dir.join("outside");
+    fs::create_dir(&share).unwrap();
+    fs::write(&outside, "host data").unwrap();
+    symlink(&outside, share.join("x")).unwrap();
+
+    let mut p9 = Virtio9p::new(&share, "share", false).unwrap();
+    call(
+        &mut p9,
+        TVERSION,
+        Writer::new().u32(8192).string("9P2000.L"),
+    );
+    let (kind, _) = call(&mut p9, TATTACH, Writer::new().u32(0).u32(NO_FID));
+    assert_eq!(kind, TATTACH + 1);
+
+    let mut create = Writer::new();
+    create
+        .u32(0)
+        .string("x")
+        .u32(O_WRONLY | O_TRUNC)
+        .u32(0o644)
+        .u32(0);
+    let (kind, _) = call(&mut p9, TLCREATE, &mut create);
+
+    assert_eq!(kind, RLERROR);
+    assert_eq!(fs::read_to_string(&outside).unwrap(), "host data");
+    fs::remove_dir_all(&dir).unwrap();
+}
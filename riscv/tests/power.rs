@@ -0,0 +1,74 @@
+mod common;
+
+use riscv::cpu::Cpu;
+use riscv::devices::{PowerControl, PowerRequest, Watchdog, WatchdogAction};
+
+const WATCHDOG_BASE: u64 = 0x1000_8000;
+
+/// Starts the watchdog with a 100 cycle timeout, then counts in t2 without
+/// ever kicking it.
+const STARVE: [u32; 7] = [
+    0x1000_82b7, // lui t0, 0x10008
+    0x0640_0313, // li t1, 100
+    0x0062_a223, // sw t1, 4(t0)     # REG_TIMEOUT
+    0x0010_0313, // li t1, 1
+    0x0062_a023, // sw t1, 0(t0)     # REG_CTRL
+    0x0013_8393, // loop: addi t2, t2, 1
+    0xffdf_f06f, // j loop
+];
+
+/// Like STARVE, but keeps rewinding mcycle instead of counting.
+const REWIND: [u32; 7] = [
+    0x1000_82b7, // lui t0, 0x10008
+    0x0640_0313, // li t1, 100
+    0x0062_a223, // sw t1, 4(t0)     # REG_TIMEOUT
+    0x0010_0313, // li t1, 1
+    0x0062_a023, // sw t1, 0(t0)     # REG_CTRL
+    0xb000_1073, // loop: csrw mcycle, zero
+    0xffdf_f06f, // j loop
+];
+
+const T2: u8 = 7;
+
+fn watchdog_machine(program: &[u32], power: &PowerControl) -> Cpu {
+    common::machine(program, |bus| {
+        let watchdog = Watchdog::new(power.clone(), WatchdogAction::Halt);
+        bus.map_to(WATCHDOG_BASE, Box::new(watchdog));
+    })
+    .with_power(power.clone())
+}
+
+#[test]
+fn watchdog_bite_stops_the_hart() {
+    let power = PowerControl::new();
+    let mut cpu = watchdog_machine(&STARVE, &power);
+    while !power.pending() && cpu.cycles() < 1_000 {
+        cpu.step();
+    }
+
+    // enabled by the store in cycle 4, so it bites at the start of cycle 104
+    assert_eq!(cpu.cycles(), 104);
+    let count = cpu.reg_file.read(T2);
+
+    for _ in 0..10 {
+        cpu.step();
+    }
+    assert_eq!(cpu.cycles(), 104);
+    assert_eq!(cpu.reg_file.read(T2), count);
+    assert_eq!(power.take(), Some(PowerRequest::Halt));
+}
+
+#[test]
+fn rewinding_mcycle_does_not_hold_off_the_watchdog() {
+    let power = PowerControl::new();
+    let mut cpu = watchdog_machine(&REWIND, &power);
+    let mut steps = 0;
+    while !power.pending() && steps < 1_000 {
+        cpu.step();
+        steps += 1;
+    }
+
+    // the 105th step is the one the watchdog stops, as above
+    assert_eq!(steps, 105);
+    assert_eq!(power.take(), Some(PowerRequest::Halt));
+}
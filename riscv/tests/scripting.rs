@@ -0,0 +1,135 @@
+use std::cell::Cell;
+use std::rc::Rc;
+
+mod common;
+
+use common::RAM_BASE;
+use riscv::cpu::Cpu;
+use riscv::devices::{MemoryBackend, PowerControl, PowerRequest, TestFinisher, Uart};
+use riscv::scripting::{Outcome, Script, ScriptRunner, parser};
+
+const UART_BASE: u64 = 0x1000_0000;
+const FINISHER_BASE: u64 = 0x0010_0000;
+
+/// Prints "> ", then echoes every byte received on the UART.
+const ECHO: [u32; 11] = [
+    0x1000_02b7, // lui t0, 0x10000
+    0x03e0_0313, // li t1, '>'
+    0x0062_8023, // sb t1, 0(t0)
+    0x0200_0313, // li t1, ' '
+    0x0062_8023, // sb t1, 0(t0)
+    0x0052_c303, // loop: lbu t1, 5(t0)    # LSR
+    0x0013_7313, // andi t1, t1, 1         # data ready
+    0xfe03_0ce3, // beqz t1, loop
+    0x0002_c303, // lbu t1, 0(t0)
+    0x0062_8023, // sb t1, 0(t0)
+    0xfedf_f06f, // j loop
+];
+
+/// Counts boots at 0x80001000; resets the machine on the first one and
+/// fails with code 3 on the second.
+const RESET_THEN_FAIL: [u32; 15] = [
+    0x8000_12b7, // lui t0, 0x80001
+    0x0002_a303, // lw t1, 0(t0)
+    0x0013_0313, // addi t1, t1, 1
+    0x0062_a023, // sw t1, 0(t0)
+    0x0010_03b7, // lui t2, 0x100
+    0x0020_0e13, // li t3, 2
+    0x01c3_4a63, // blt t1, t3, reset
+    0x0003_3e37, // lui t3, 0x33
+    0x333e_0e13, // addi t3, t3, 0x333  # FINISHER_FAIL | 3 << 16
+    0x01c3_a023, // sw t3, 0(t2)
+    0x0000_006f, // j .
+    0x0000_7e37, // reset: lui t3, 0x7
+    0x777e_0e13, // addi t3, t3, 0x777  # FINISHER_RESET
+    0x01c3_a023, // sw t3, 0(t2)
+    0x0000_006f, // j .
+];
+
+fn echo_machine() -> (Cpu, MemoryBackend) {
+    let console = MemoryBackend::new();
+    let cpu = common::machine(&ECHO, |bus| {
+        bus.map_to(UART_BASE, Box::new(Uart::new(Box::new(console.clone()))));
+    });
+    (cpu, console)
+}
+
+#[test]
+fn expect_and_send_round_trip() {
+    let (mut cpu, console) = echo_machine();
+    let script = Script::new()
+        .expect("> ", 10_000)
+        .send("hello\n")
+        .expect("hello\n", 100_000);
+
+    let mut runner = ScriptRunner::new(console);
+    runner.run(&mut cpu, &script).unwrap();
+    assert_eq!(runner.transcript(), b"> hello\n");
+}
+
+#[test]
+fn expect_times_out() {
+    let (mut cpu, console) = echo_machine();
+    let script = Script::new().expect("> ", 10_000).expect("login:", 50_000);
+
+    let err = ScriptRunner::new(console)
+        .run(&mut cpu, &script)
+        .unwrap_err();
+    assert_eq!(err.step, 1);
+    assert_eq!(err.pattern, b"login:");
+    assert!(cpu.cycles() >= 50_000);
+}
+
+#[test]
+fn parsed_script_drives_the_guest() {
+    let (mut cpu, console) = echo_machine();
+    let script = parser::parse(
+        "timeout 100000\n\
+         expect \"> \"\n\
+         send \"ping\\r\"\n\
+         expect \"ping\\r\"\n",
+    )
+    .unwrap();
+
+    ScriptRunner::new(console).run(&mut cpu, &script).unwrap();
+}
+
+fn finisher_machine(power: &PowerControl) -> Cpu {
+    common::machine(&RESET_THEN_FAIL, |bus| {
+        bus.map_to(FINISHER_BASE, Box::new(TestFinisher::new(power.clone())));
+    })
+    .with_power(power.clone())
+}
+
+#[test]
+fn guest_power_requests_end_the_run() {
+    let power = PowerControl::new();
+    let mut cpu = finisher_machine(&power);
+
+    let resets = Rc::new(Cell::new(0));
+    let counter = resets.clone();
+    let mut runner = ScriptRunner::new(MemoryBackend::new()).with_power(power, move |cpu| {
+        counter.set(counter.get() + 1);
+        cpu.reset_machine(RAM_BASE);
+    });
+    let outcome = runner
+        .run(&mut cpu, &Script::new().expect("login:", 100_000))
+        .unwrap();
+
+    assert_eq!(outcome, Outcome::PoweredOff { code: 3 });
+    assert_eq!(resets.get(), 1);
+}
+
+#[test]
+fn unhandled_power_request_stops_the_run() {
+    let power = PowerControl::new();
+    let mut cpu = finisher_machine(&power);
+
+    let outcome = ScriptRunner::new(MemoryBackend::new())
+        .run(&mut cpu, &Script::new().expect("login:", 100_000))
+        .unwrap();
+
+    assert_eq!(outcome, Outcome::Stopped);
+    assert!(cpu.cycles() < 100_000);
+    assert_eq!(power.take(), Some(PowerRequest::Reset));
+}